@@ -13,7 +13,7 @@ use clap::Parser;
 ///
 /// Example:
 ///     $ age-plugin-tlock --generate --remote <URL> > fastnet.key
-///     $ cat fastnet.key | grep 'recipient' | sed 's/.*\(age1.*\)/\1/' > fastnet.key.pub
+///     $ age-plugin-tlock --generate --recipient-only --remote <URL> > fastnet.key.pub
 ///     $ tar cvz ~/data | ROUND="<ROUND>" age -R myremote.key.pub > data.tar.gz.age
 ///     $ age --decrypt -o data.tar.gz data.tar.gz.age
 #[derive(Parser)]
@@ -26,6 +26,9 @@ pub struct Cli {
     pub age_plugin: Option<String>,
     #[arg(long, default_value_t = false)]
     pub generate: bool,
+    /// Only output the recipient, without an identity.
+    #[arg(long, default_value_t = false)]
+    pub recipient_only: bool,
     /// REMOTE is a drand remote URL. You can find a non-exhaustive list on https://github.com/thibmeu/drand-rs#common-remotes.
     #[arg(short, long)]
     pub remote: Option<String>,
@@ -1,4 +1,4 @@
-use std::{collections::HashMap, io};
+use std::{collections::HashMap, io, thread, time::Duration};
 
 use age::{Identity, Recipient};
 use age_core::format::{FileKey, Stanza};
@@ -7,13 +7,104 @@ use age_plugin::{
     recipient::{self, RecipientPluginV1},
     Callbacks,
 };
+use bech32::{ToBase32, Variant};
 use bincode::{config, Decode, Encode};
+use drand_core::HttpClient;
 
-use tlock_age::{internal::STANZA_TAG, Header};
+use tlock_age::{
+    internal::{MAX_STANZAS, STANZA_TAG},
+    ChainHash, Header, Round,
+};
 
 /// Environment variable read to get round information non-interactively.
+///
+/// It supports the same formats as the prompt: a specific round (123), a duration (30s), or an RFC3339 date (2023-06-28T21:30:22Z).
 pub const ROUND_ENV: &str = "ROUND";
 
+/// Environment variable read to set the number of attempts to fetch a beacon.
+///
+/// Defaults to [`DEFAULT_FETCH_ATTEMPTS`].
+pub const FETCH_ATTEMPTS_ENV: &str = "TLOCK_FETCH_ATTEMPTS";
+
+/// Number of attempts to fetch a beacon when [`FETCH_ATTEMPTS_ENV`] is not set.
+pub const DEFAULT_FETCH_ATTEMPTS: usize = 3;
+
+/// Environment variable allowing to encrypt to round 0 when set to `1`, for testing.
+///
+/// drand rounds start at 1, a message encrypted to round 0 cannot be decrypted.
+pub const ALLOW_ZERO_ROUND_ENV: &str = "TLOCK_ALLOW_ZERO_ROUND";
+
+/// Failure of a single attempt, telling [`retry_with_backoff`] whether to try again.
+pub enum Attempt<E> {
+    /// The operation might succeed later, such as a timeout or a server error.
+    Transient(E),
+    /// The operation cannot succeed, such as a round that is not available yet.
+    Permanent(E),
+}
+
+/// Call `f` up to `attempts` times, until it succeeds or fails permanently.
+///
+/// The delay between attempts starts at `delay`, and doubles after each transient failure.
+/// The last error is returned.
+pub fn retry_with_backoff<T, E>(
+    attempts: usize,
+    mut delay: Duration,
+    mut f: impl FnMut() -> Result<T, Attempt<E>>,
+) -> Result<T, E> {
+    let mut attempt = 1;
+    loop {
+        match f() {
+            Ok(t) => return Ok(t),
+            Err(Attempt::Permanent(e)) => return Err(e),
+            Err(Attempt::Transient(e)) if attempt >= attempts => return Err(e),
+            Err(Attempt::Transient(_)) => {
+                thread::sleep(delay);
+                delay *= 2;
+                attempt += 1;
+            }
+        }
+    }
+}
+
+/// Source of drand chain information and beacons.
+///
+/// The plugin fetches beacons through this trait, implement it to use another transport than HTTP, such as gRPC, gossip, or a local cache.
+/// [`run_state_machine`] takes a function creating a provider from the URL stored in an HTTP identity. [`HttpClient`] is the default implementation.
+///
+/// The trait is synchronous: age plugins run synchronously, and async functions in traits require a newer Rust than the crate MSRV.
+pub trait BeaconProvider {
+    /// Information of the chain beacons are provided for.
+    fn chain_info(&self) -> Result<RecipientInfo, String>;
+
+    /// Signature of `round`. A round that is not available yet fails with [`Attempt::Permanent`].
+    fn signature(&self, round: Round) -> Result<Vec<u8>, Attempt<String>>;
+}
+
+impl BeaconProvider for HttpClient {
+    fn chain_info(&self) -> Result<RecipientInfo, String> {
+        let info = HttpClient::chain_info(self).map_err(|err| err.to_string())?;
+        Ok(RecipientInfo::new(
+            &info.hash(),
+            &info.public_key(),
+            info.genesis_time(),
+            info.period(),
+        ))
+    }
+
+    fn signature(&self, round: Round) -> Result<Vec<u8>, Attempt<String>> {
+        match self.get(round.value()) {
+            Ok(beacon) => Ok(beacon.signature()),
+            Err(err) => match self.latest() {
+                Ok(latest) if latest.round() < round.value() => Err(Attempt::Permanent(format!(
+                    "round {round} is not available yet, latest round is {}",
+                    latest.round()
+                ))),
+                _ => Err(Attempt::Transient(err.to_string())),
+            },
+        }
+    }
+}
+
 #[derive(Debug, Encode, Decode, PartialEq, Clone)]
 /// Recipient information as defined for the age-plugin-tlock
 /// These are required to encrypt information offline
@@ -58,18 +149,45 @@ impl RecipientInfo {
     pub fn period(&self) -> u64 {
         self.period
     }
+
+    /// Fetch the information of the chain `provider` serves.
+    pub fn fetch(provider: &impl BeaconProvider) -> Result<Self, String> {
+        provider.chain_info()
+    }
+
+    /// Whether both describe the same chain, with the same hash, public key, genesis time, and period.
+    ///
+    /// The drand chain hash commits to the chain scheme, comparing hashes covers it.
+    pub fn matches(&self, other: &RecipientInfo) -> bool {
+        self.hash == other.hash
+            && self.public_key_bytes == other.public_key_bytes
+            && self.genesis_time == other.genesis_time
+            && self.period == other.period
+    }
+
+    /// Whether `provider` still serves the chain this information describes.
+    ///
+    /// Different mirrors may serve the same chain. Checking a cached recipient before trusting it guards against silently switching chains.
+    pub fn matches_remote(&self, provider: &impl BeaconProvider) -> Result<bool, String> {
+        Ok(self.matches(&Self::fetch(provider)?))
+    }
 }
 
 struct RecipientPlugin {
     plugin_name: String,
+    index: usize,
     info: Option<RecipientInfo>,
-    parse_round: fn(&RecipientInfo, &str) -> u64,
+    parse_round: fn(&RecipientInfo, &str) -> Result<u64, String>,
 }
 
 impl RecipientPlugin {
-    pub fn new(plugin_name: &str, parse_round: fn(&RecipientInfo, &str) -> u64) -> Self {
+    pub fn new(
+        plugin_name: &str,
+        parse_round: fn(&RecipientInfo, &str) -> Result<u64, String>,
+    ) -> Self {
         Self {
             plugin_name: plugin_name.to_owned(),
+            index: 0,
             info: None,
             parse_round,
         }
@@ -83,7 +201,7 @@ impl RecipientPlugin {
         self.info.clone()
     }
 
-    pub fn parse_round(&self, round: &str) -> u64 {
+    pub fn parse_round(&self, round: &str) -> Result<u64, String> {
         (self.parse_round)(&self.info().unwrap(), round)
     }
 }
@@ -97,6 +215,14 @@ impl RecipientPluginV1 for RecipientPlugin {
     ) -> Result<(), recipient::Error> {
         if plugin_name == self.plugin_name() {
             let chain = RecipientInfo::deserialize(bytes);
+            // Reject an invalid chain hash now, rather than when wrapping file keys
+            if let Err(err) = ChainHash::try_from(chain.hash.as_slice()) {
+                return Err(recipient::Error::Recipient {
+                    index,
+                    message: err.to_string(),
+                });
+            }
+            self.index = index;
             self.info = Some(chain);
             Ok(())
         } else {
@@ -109,11 +235,14 @@ impl RecipientPluginV1 for RecipientPlugin {
 
     fn add_identity(
         &mut self,
-        _index: usize,
+        index: usize,
         _plugin_name: &str,
         _bytes: &[u8],
     ) -> Result<(), recipient::Error> {
-        todo!()
+        Err(recipient::Error::Identity {
+            index,
+            message: "encrypting to an identity is not supported".to_owned(),
+        })
     }
 
     fn wrap_file_keys(
@@ -130,16 +259,26 @@ impl RecipientPluginV1 for RecipientPlugin {
                 Err(err) => return Err(err),
             }
         };
-        let round = self.parse_round(&round);
+        let round = match self.parse_round(&round) {
+            Ok(round) => round,
+            Err(message) => return Ok(Err(vec![recipient::Error::Internal { message }])),
+        };
 
         let info = self.info().unwrap();
 
-        let recipient =
-            tlock_age::internal::Recipient::new(&info.hash, &info.public_key_bytes, round);
-        Ok(Ok(file_keys
-            .into_iter()
-            .map(|file_key| recipient.wrap_file_key(&file_key).unwrap())
-            .collect()))
+        let hash = ChainHash::try_from(info.hash.as_slice())
+            .expect("chain hash is checked when adding the recipient");
+        let recipient = tlock_age::internal::Recipient::new(hash, &info.public_key_bytes, round);
+        let stanzas = file_keys
+            .iter()
+            .map(|file_key| recipient.wrap_file_key(file_key))
+            .collect::<Result<_, _>>();
+        Ok(stanzas.map_err(|err| {
+            vec![recipient::Error::Recipient {
+                index: self.index,
+                message: err.to_string(),
+            }]
+        }))
     }
 }
 
@@ -214,26 +353,53 @@ impl HTTPIdentityInfo {
     }
 }
 
-struct IdentityPlugin {
+/// URL of the beacon needed to decrypt a message with the given header.
+///
+/// `base_url` is the chain URL, as stored in an HTTP identity, such as `https://api.drand.sh/52db9ba70e0cc0f6eaf7803dd07447a1f5477735fd3f661792ba94600c84e971`.
+/// [`HttpClient`] builds this URL itself, and does not accept a full URL. This helper gives the same URL for error messages, and for users fetching the beacon with another tool.
+pub fn beacon_url(base_url: &str, header: &Header) -> String {
+    format!(
+        "{}/public/{}",
+        base_url.trim_end_matches('/'),
+        header.round()
+    )
+}
+
+struct IdentityPlugin<P: BeaconProvider> {
     plugin_name: String,
     info: Option<IdentityInfo>,
-    get_signature: fn(url: &str, header: &Header) -> Vec<u8>,
+    connect: fn(url: &str) -> Result<P, String>,
 }
 
-impl IdentityPlugin {
-    pub fn new(
-        plugin_name: &str,
-        get_signature: fn(url: &str, header: &Header) -> Vec<u8>,
-    ) -> Self {
+impl<P: BeaconProvider> IdentityPlugin<P> {
+    pub fn new(plugin_name: &str, connect: fn(url: &str) -> Result<P, String>) -> Self {
         Self {
             plugin_name: plugin_name.to_owned(),
             info: None,
-            get_signature,
+            connect,
         }
     }
+
+    /// Fetch the signature needed to decrypt a message with the given header, from the chain at `url`.
+    ///
+    /// Transient failures are retried with an exponential backoff, up to [`FETCH_ATTEMPTS_ENV`] times.
+    /// A round that has not been emitted yet is not retried.
+    fn fetch_signature(&self, url: &str, header: &Header) -> Result<Vec<u8>, String> {
+        let provider = (self.connect)(url)?;
+        let round = header.round();
+        let attempts = std::env::var(FETCH_ATTEMPTS_ENV)
+            .ok()
+            .and_then(|attempts| attempts.parse().ok())
+            .unwrap_or(DEFAULT_FETCH_ATTEMPTS);
+
+        retry_with_backoff(attempts, Duration::from_millis(500), || {
+            provider.signature(round)
+        })
+        .map_err(|err| format!("cannot fetch beacon {}: {err}", beacon_url(url, header)))
+    }
 }
 
-impl IdentityPluginV1 for IdentityPlugin {
+impl<P: BeaconProvider> IdentityPluginV1 for IdentityPlugin<P> {
     fn add_identity(
         &mut self,
         index: usize,
@@ -260,35 +426,76 @@ impl IdentityPluginV1 for IdentityPlugin {
         let mut file_keys = HashMap::with_capacity(files.len());
 
         for (file, stanzas) in files.iter().enumerate() {
-            for (_stanza_index, stanza) in stanzas.iter().enumerate() {
+            let count = stanzas
+                .iter()
+                .filter(|stanza| stanza.tag == STANZA_TAG)
+                .count();
+            if count > MAX_STANZAS {
+                file_keys.insert(
+                    file,
+                    Err(vec![identity::Error::Identity {
+                        index: file,
+                        message: format!(
+                            "too many tlock stanzas: {count}, maximum is {MAX_STANZAS}"
+                        ),
+                    }]),
+                );
+                continue;
+            }
+            // Stanzas are order independent, a failing stanza does not prevent a later one from succeeding
+            let mut errors = vec![];
+            for stanza in stanzas.iter() {
                 if stanza.tag != STANZA_TAG {
                     continue;
                 }
-                if stanza.args.len() != 2 {
-                    continue; // TODO: should be an error
-                }
-                let [round, hash] = [stanza.args[0].clone(), stanza.args[1].clone()];
-                let round = round.parse().unwrap();
-                let hash = hex::decode(hash).unwrap();
-                let header = Header::new(round, &hash);
+                let error = |message: String| identity::Error::Identity {
+                    index: file,
+                    message,
+                };
+                let (round, hash) = match stanza.args.as_slice() {
+                    [round, hash] => (round.parse::<u64>(), hash.parse::<ChainHash>()),
+                    _ => {
+                        errors.push(error("invalid tlock stanza".to_owned()));
+                        continue;
+                    }
+                };
+                let (Ok(round), Ok(hash)) = (round, hash) else {
+                    errors.push(error("invalid tlock stanza".to_owned()));
+                    continue;
+                };
+                let header = Header::new(round, hash);
 
                 let signature = match self.info.as_ref().unwrap() {
                     IdentityInfo::HTTPIdentityInfo(info) => {
-                        (self.get_signature)(info.url.as_str(), &header)
+                        match self.fetch_signature(info.url.as_str(), &header) {
+                            Ok(signature) => signature,
+                            Err(e) => {
+                                errors.push(error(e));
+                                continue;
+                            }
+                        }
                     }
                     IdentityInfo::RawIdentityInfo(info) => info.signature.clone(),
                 };
-                let identity = tlock_age::internal::Identity::new(&hash, &signature);
-
-                let file_key = identity.unwrap_stanza(stanza).unwrap();
-                let r = file_key.map_err(|e| {
-                    vec![identity::Error::Identity {
-                        index: file,
-                        message: format!("{e}"),
-                    }]
-                });
+                let identity = match tlock_age::internal::Identity::new(hash, &signature) {
+                    Ok(identity) => identity,
+                    Err(e) => {
+                        errors.push(error(format!("invalid signature: {e}")));
+                        continue;
+                    }
+                };
 
-                file_keys.entry(file).or_insert_with(|| r);
+                match identity.unwrap_stanza(stanza) {
+                    Some(Ok(file_key)) => {
+                        file_keys.insert(file, Ok(file_key));
+                        break;
+                    }
+                    Some(Err(e)) => errors.push(error(format!("{e}"))),
+                    None => {}
+                }
+            }
+            if !errors.is_empty() {
+                file_keys.entry(file).or_insert(Err(errors));
             }
         }
         Ok(file_keys)
@@ -297,21 +504,454 @@ impl IdentityPluginV1 for IdentityPlugin {
 
 /// Run the state machine for the plugin, as defined on [GitHub](https://github.com/C2SP/C2SP/blob/main/age-plugin.md).
 /// This is the entry point for the plugin. It is called by the age client.
-pub fn run_state_machine(
+///
+/// `parse_round` converts the round requested when encrypting into a drand round, its errors are reported to the age client.
+/// `connect` creates the [`BeaconProvider`] beacons are fetched from, given the chain URL stored in an HTTP identity.
+pub fn run_state_machine<P: BeaconProvider>(
     state_machine: String,
     plugin_name: &str,
-    parse_round: fn(&RecipientInfo, &str) -> u64,
-    get_signature: fn(&str, &Header) -> Vec<u8>,
+    parse_round: fn(&RecipientInfo, &str) -> Result<u64, String>,
+    connect: fn(url: &str) -> Result<P, String>,
 ) -> io::Result<()> {
     // The plugin was started by an age client; run the state machine.
     age_plugin::run_state_machine(
         &state_machine,
         || RecipientPlugin::new(plugin_name, parse_round),
-        || IdentityPlugin::new(plugin_name, get_signature),
+        || IdentityPlugin::new(plugin_name, connect),
+    )
+}
+
+/// Encode the recipient as an age recipient string, `age1<plugin_name>1...`.
+///
+/// The recipient is derived from chain information only, and can be shared without an identity.
+pub fn encode_recipient(plugin_name: &str, recipient: &RecipientInfo) -> String {
+    bech32::encode(
+        &format!("age1{plugin_name}"),
+        recipient.serialize().to_base32(),
+        Variant::Bech32,
     )
+    .unwrap()
+}
+
+/// Print the new recipient, without an identity.
+pub fn print_new_recipient(plugin_name: &str, recipient: &RecipientInfo) {
+    println!("{}", encode_recipient(plugin_name, recipient))
 }
 
 /// Print the new identity information.
 pub fn print_new_identity(plugin_name: &str, identity: &IdentityInfo, recipient: &RecipientInfo) {
     age_plugin::print_new_identity(plugin_name, &identity.serialize(), &recipient.serialize())
 }
+
+#[cfg(test)]
+mod tests {
+    use age::secrecy::{ExposeSecret, SecretString};
+    use bech32::FromBase32;
+
+    use super::*;
+
+    const PLUGIN_NAME: &str = "tlock";
+    // testnet-unchained-3s, public key on G1
+    const CHAIN_HASH: &str = "7672797f548f3f4748ac4bf3352fc6c6b6468c9ad40ad456a397545c6e2df5bf";
+    const PUBLIC_KEY: &str = "8200fc249deb0148eb918d6e213980c5d01acd7fc251900d9260136da3b54836ce125172399ddc69c4e3e11429b62c11";
+    // signature for round 1000
+    const SIGNATURE: &str = "a4721e6c3eafcd823f138cd29c6c82e8c5149101d0bb4bafddbac1c2d1fe3738895e4e21dd4b8b41bf007046440220910bb1cdb91f50a84a0d7f33ff2e8577aa62ac64b35a291a728a9db5ac91e06d1312b48a376138d77b4d6ad27c24221afe";
+
+    /// Callbacks answering the round prompt, as an age client would.
+    struct TestCallbacks {
+        round: Option<String>,
+    }
+
+    impl<E> Callbacks<E> for TestCallbacks {
+        fn message(&mut self, _message: &str) -> age_core::plugin::Result<()> {
+            Ok(Ok(()))
+        }
+
+        fn confirm(
+            &mut self,
+            _message: &str,
+            _yes_string: &str,
+            _no_string: Option<&str>,
+        ) -> age_core::plugin::Result<bool> {
+            Ok(Ok(true))
+        }
+
+        fn request_public(&mut self, _message: &str) -> age_core::plugin::Result<String> {
+            Ok(self.round.clone().ok_or(age_core::plugin::Error::Fail))
+        }
+
+        fn request_secret(&mut self, _message: &str) -> age_core::plugin::Result<SecretString> {
+            Ok(Err(age_core::plugin::Error::Fail))
+        }
+
+        fn error(&mut self, _error: E) -> age_core::plugin::Result<()> {
+            Ok(Ok(()))
+        }
+    }
+
+    /// Provider serving the round 1000 signature, as the chain at `https://example.com` would.
+    struct TestProvider;
+
+    impl BeaconProvider for TestProvider {
+        fn chain_info(&self) -> Result<RecipientInfo, String> {
+            Ok(RecipientInfo::new(
+                &hex::decode(CHAIN_HASH).unwrap(),
+                &hex::decode(PUBLIC_KEY).unwrap(),
+                0,
+                3,
+            ))
+        }
+
+        fn signature(&self, round: Round) -> Result<Vec<u8>, Attempt<String>> {
+            match round.value() {
+                1000 => Ok(hex::decode(SIGNATURE).unwrap()),
+                _ => Err(Attempt::Permanent(format!(
+                    "round {round} is not available yet"
+                ))),
+            }
+        }
+    }
+
+    fn connect(url: &str) -> Result<TestProvider, String> {
+        match url {
+            "https://example.com" => Ok(TestProvider),
+            _ => Err(format!("cannot connect to {url}")),
+        }
+    }
+
+    fn recipient_plugin() -> RecipientPlugin {
+        let info = RecipientInfo::new(
+            &hex::decode(CHAIN_HASH).unwrap(),
+            &hex::decode(PUBLIC_KEY).unwrap(),
+            0,
+            3,
+        );
+        let mut plugin = RecipientPlugin::new(PLUGIN_NAME, |_, round| {
+            round.parse().map_err(|_| format!("invalid round {round}"))
+        });
+        plugin
+            .add_recipient(0, PLUGIN_NAME, &info.serialize())
+            .ok()
+            .unwrap();
+        plugin
+    }
+
+    fn wrap(plugin: &mut RecipientPlugin, round: Option<&str>) -> Vec<Stanza> {
+        let callbacks = TestCallbacks {
+            round: round.map(str::to_owned),
+        };
+        let mut stanzas = plugin
+            .wrap_file_keys(vec![FileKey::from([7u8; 16])], callbacks)
+            .unwrap()
+            .ok()
+            .unwrap();
+        stanzas.remove(0)
+    }
+
+    // age Stanza does not implement Clone
+    fn copy_stanza(stanza: &Stanza) -> Stanza {
+        Stanza {
+            tag: stanza.tag.clone(),
+            args: stanza.args.clone(),
+            body: stanza.body.clone(),
+        }
+    }
+
+    fn unwrap(plugin: &mut IdentityPlugin<TestProvider>, stanzas: &[Stanza]) -> [u8; 16] {
+        let callbacks = TestCallbacks { round: None };
+        let stanzas = stanzas.iter().map(copy_stanza).collect();
+        let mut file_keys = plugin.unwrap_file_keys(vec![stanzas], callbacks).unwrap();
+        let file_key = file_keys.remove(&0).unwrap().ok().unwrap();
+        *file_key.expose_secret()
+    }
+
+    #[test]
+    fn test_encrypt_decrypt() {
+        // Both round sources are exercised in the same test, as the environment is shared between tests
+        std::env::remove_var(ROUND_ENV);
+        let prompted = wrap(&mut recipient_plugin(), Some("1000"));
+        std::env::set_var(ROUND_ENV, "1000");
+        let from_env = wrap(&mut recipient_plugin(), None);
+        std::env::remove_var(ROUND_ENV);
+
+        for stanzas in [prompted, from_env] {
+            assert_eq!(
+                stanzas[0].args,
+                vec!["1000".to_owned(), CHAIN_HASH.to_owned()]
+            );
+
+            let info: IdentityInfo = RawIdentityInfo::new(&hex::decode(SIGNATURE).unwrap()).into();
+            let mut raw = IdentityPlugin::<TestProvider>::new(PLUGIN_NAME, |_| unreachable!());
+            raw.add_identity(0, PLUGIN_NAME, &info.serialize())
+                .ok()
+                .unwrap();
+            assert_eq!(unwrap(&mut raw, &stanzas), [7u8; 16]);
+
+            let info: IdentityInfo = HTTPIdentityInfo::new("https://example.com").into();
+            let mut http = IdentityPlugin::new(PLUGIN_NAME, connect);
+            http.add_identity(0, PLUGIN_NAME, &info.serialize())
+                .ok()
+                .unwrap();
+            assert_eq!(unwrap(&mut http, &stanzas), [7u8; 16]);
+        }
+    }
+
+    #[test]
+    fn test_unwrap_file_keys_order_independent() {
+        // stanzas are wrapped directly, the round environment variable is shared between tests
+        let wrap = |round: u64| {
+            tlock_age::internal::Recipient::new(
+                CHAIN_HASH.parse::<ChainHash>().unwrap(),
+                &hex::decode(PUBLIC_KEY).unwrap(),
+                round,
+            )
+            .wrap_file_key(&FileKey::from([7u8; 16]))
+            .unwrap()
+            .remove(0)
+        };
+        let valid = wrap(1000);
+        let other_round = wrap(1001);
+        let malformed = Stanza {
+            tag: STANZA_TAG.to_owned(),
+            args: vec!["not a round".to_owned(), CHAIN_HASH.to_owned()],
+            body: vec![],
+        };
+        let grease = age_core::format::grease_the_joint();
+
+        let info: IdentityInfo = RawIdentityInfo::new(&hex::decode(SIGNATURE).unwrap()).into();
+        let mut raw = IdentityPlugin::<TestProvider>::new(PLUGIN_NAME, |_| unreachable!());
+        raw.add_identity(0, PLUGIN_NAME, &info.serialize())
+            .ok()
+            .unwrap();
+
+        assert_eq!(unwrap(&mut raw, &[grease, copy_stanza(&valid)]), [7u8; 16]);
+        assert_eq!(
+            unwrap(&mut raw, &[copy_stanza(&other_round), copy_stanza(&valid)]),
+            [7u8; 16]
+        );
+        assert_eq!(
+            unwrap(&mut raw, &[copy_stanza(&malformed), valid]),
+            [7u8; 16]
+        );
+
+        let callbacks = TestCallbacks { round: None };
+        let mut file_keys = raw
+            .unwrap_file_keys(vec![vec![malformed, other_round]], callbacks)
+            .unwrap();
+        let Some(Err(errors)) = file_keys.remove(&0) else {
+            panic!("expected an error per tlock stanza");
+        };
+        assert_eq!(errors.len(), 2);
+    }
+
+    #[test]
+    fn test_unwrap_file_keys_fetch_error() {
+        let stanza = tlock_age::internal::Recipient::new(
+            CHAIN_HASH.parse::<ChainHash>().unwrap(),
+            &hex::decode(PUBLIC_KEY).unwrap(),
+            1001,
+        )
+        .wrap_file_key(&FileKey::from([7u8; 16]))
+        .unwrap()
+        .remove(0);
+
+        // round not emitted yet, and unreachable chain
+        for (url, expected) in [
+            ("https://example.com", "https://example.com/public/1001"),
+            (
+                "https://unknown.com",
+                "cannot connect to https://unknown.com",
+            ),
+        ] {
+            let info: IdentityInfo = HTTPIdentityInfo::new(url).into();
+            let mut http = IdentityPlugin::new(PLUGIN_NAME, connect);
+            http.add_identity(0, PLUGIN_NAME, &info.serialize())
+                .ok()
+                .unwrap();
+
+            let callbacks = TestCallbacks { round: None };
+            let mut file_keys = http
+                .unwrap_file_keys(vec![vec![copy_stanza(&stanza)]], callbacks)
+                .unwrap();
+            let Some(Err(errors)) = file_keys.remove(&0) else {
+                panic!("expected a fetch error");
+            };
+            let [identity::Error::Identity { message, .. }] = errors.as_slice() else {
+                panic!("expected a single identity error");
+            };
+            assert!(message.contains(expected), "{message}");
+        }
+    }
+
+    #[test]
+    fn test_wrap_file_keys_errors() {
+        let callbacks = |round: &str| TestCallbacks {
+            round: Some(round.to_owned()),
+        };
+        let file_keys = || vec![FileKey::from([7u8; 16])];
+
+        let mut plugin = recipient_plugin();
+        let result = plugin
+            .wrap_file_keys(file_keys(), callbacks("never"))
+            .unwrap();
+        let Err(errors) = result else {
+            panic!("expected an invalid round error");
+        };
+        assert!(matches!(
+            errors.as_slice(),
+            [recipient::Error::Internal { message }] if message == "invalid round never"
+        ));
+
+        let info = RecipientInfo::new(&hex::decode(CHAIN_HASH).unwrap(), &[0u8; 10], 0, 3);
+        plugin
+            .add_recipient(2, PLUGIN_NAME, &info.serialize())
+            .ok()
+            .unwrap();
+        let result = plugin
+            .wrap_file_keys(file_keys(), callbacks("1000"))
+            .unwrap();
+        let Err(errors) = result else {
+            panic!("expected an invalid public key error");
+        };
+        assert!(matches!(
+            errors.as_slice(),
+            [recipient::Error::Recipient { index: 2, .. }]
+        ));
+    }
+
+    #[test]
+    fn test_encode_recipient() {
+        let info = RecipientInfo::new(
+            &hex::decode(CHAIN_HASH).unwrap(),
+            &hex::decode(PUBLIC_KEY).unwrap(),
+            0,
+            3,
+        );
+        let recipient = encode_recipient(PLUGIN_NAME, &info);
+        assert!(recipient.starts_with("age1tlock1"));
+
+        let (hrp, data, variant) = bech32::decode(&recipient).unwrap();
+        assert_eq!(hrp, "age1tlock");
+        assert_eq!(variant, Variant::Bech32);
+        let bytes = Vec::<u8>::from_base32(&data).unwrap();
+        assert_eq!(RecipientInfo::deserialize(&bytes), info);
+    }
+
+    #[test]
+    fn test_recipient_info_matches() {
+        let info = RecipientInfo::new(
+            &hex::decode(CHAIN_HASH).unwrap(),
+            &hex::decode(PUBLIC_KEY).unwrap(),
+            0,
+            3,
+        );
+        assert!(info.matches(&info.clone()));
+
+        let others = [
+            RecipientInfo::new(&[0u8; 32], &info.public_key_bytes, 0, 3),
+            RecipientInfo::new(&info.hash, &[0u8; 48], 0, 3),
+            RecipientInfo::new(&info.hash, &info.public_key_bytes, 1, 3),
+            RecipientInfo::new(&info.hash, &info.public_key_bytes, 0, 30),
+        ];
+        for other in others {
+            assert!(!info.matches(&other));
+        }
+    }
+
+    /// Provider serving a single chain from memory, up to round 1000.
+    struct MemoryProvider(RecipientInfo);
+
+    impl BeaconProvider for MemoryProvider {
+        fn chain_info(&self) -> Result<RecipientInfo, String> {
+            Ok(self.0.clone())
+        }
+
+        fn signature(&self, round: Round) -> Result<Vec<u8>, Attempt<String>> {
+            match round.value() {
+                1000 => Ok(hex::decode(SIGNATURE).unwrap()),
+                round if round > 1000 => Err(Attempt::Permanent(format!("round {round}"))),
+                round => Err(Attempt::Transient(format!("round {round}"))),
+            }
+        }
+    }
+
+    #[test]
+    fn test_beacon_provider() {
+        let info = RecipientInfo::new(
+            &hex::decode(CHAIN_HASH).unwrap(),
+            &hex::decode(PUBLIC_KEY).unwrap(),
+            0,
+            3,
+        );
+        let provider = MemoryProvider(info.clone());
+        assert_eq!(RecipientInfo::fetch(&provider).unwrap(), info);
+        assert!(info.matches_remote(&provider).unwrap());
+        let other = RecipientInfo::new(&[0u8; 32], &info.public_key_bytes, 0, 3);
+        assert!(!other.matches_remote(&provider).unwrap());
+
+        let signature = retry_with_backoff(3, Duration::ZERO, || provider.signature(1000.into()));
+        assert_eq!(signature, Ok(hex::decode(SIGNATURE).unwrap()));
+        assert!(retry_with_backoff(3, Duration::ZERO, || provider.signature(1001.into())).is_err());
+    }
+
+    #[test]
+    fn test_retry_with_backoff() {
+        let mut calls = 0;
+        let result: Result<(), &str> = retry_with_backoff(3, Duration::ZERO, || {
+            calls += 1;
+            Err(Attempt::Transient("transient"))
+        });
+        assert_eq!((result, calls), (Err("transient"), 3));
+
+        let mut calls = 0;
+        let result: Result<(), &str> = retry_with_backoff(3, Duration::ZERO, || {
+            calls += 1;
+            Err(Attempt::Permanent("permanent"))
+        });
+        assert_eq!((result, calls), (Err("permanent"), 1));
+
+        let mut calls = 0;
+        let result = retry_with_backoff(3, Duration::ZERO, || {
+            calls += 1;
+            if calls < 2 {
+                Err(Attempt::Transient("transient"))
+            } else {
+                Ok(calls)
+            }
+        });
+        assert_eq!(result, Ok(2));
+    }
+
+    #[test]
+    fn test_beacon_url() {
+        let header = Header::new(1000, CHAIN_HASH.parse::<ChainHash>().unwrap());
+        let expected = format!("https://api.drand.sh/{CHAIN_HASH}/public/1000");
+        assert_eq!(
+            beacon_url(&format!("https://api.drand.sh/{CHAIN_HASH}"), &header),
+            expected
+        );
+        assert_eq!(
+            beacon_url(&format!("https://api.drand.sh/{CHAIN_HASH}/"), &header),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_unsupported_plugin() {
+        let info = RecipientInfo::new(&[], &[], 0, 3);
+        let mut recipient = RecipientPlugin::new(PLUGIN_NAME, |_, _| Ok(0));
+        assert!(recipient
+            .add_recipient(0, "other", &info.serialize())
+            .is_err());
+        assert!(recipient
+            .add_recipient(0, PLUGIN_NAME, &info.serialize())
+            .is_err());
+        assert!(recipient.add_identity(0, PLUGIN_NAME, &[]).is_err());
+
+        let info: IdentityInfo = RawIdentityInfo::new(&[]).into();
+        let mut identity = IdentityPlugin::new(PLUGIN_NAME, connect);
+        assert!(identity
+            .add_identity(0, "other", &info.serialize())
+            .is_err());
+    }
+}
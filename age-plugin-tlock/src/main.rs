@@ -1,51 +1,107 @@
-use age_plugin_tlock::{HTTPIdentityInfo, RecipientInfo};
-use drand_core::{beacon, chain, HttpClient};
-use tlock_age::Header;
+use age_plugin_tlock::{HTTPIdentityInfo, RecipientInfo, ALLOW_ZERO_ROUND_ENV};
+use drand_core::HttpClient;
 
 mod cli;
 
 pub const PLUGIN_NAME: &str = "tlock";
 
+/// Parse round provided through `ROUND` environment variable or prompt.
+/// It can be a specific round, a duration, or an RFC3339 date, as defined by [`tlock::parse_target`].
+///
+/// drand rounds start at 1. Round 0 is rejected, unless `TLOCK_ALLOW_ZERO_ROUND=1` is set for testing.
+fn parse_round(info: &RecipientInfo, round: &str) -> Result<u64, String> {
+    let round = tlock::parse_target(round)
+        .map_err(|err| err.to_string())?
+        .round(info.genesis_time(), info.period())
+        .value();
+    if round == 0 && std::env::var(ALLOW_ZERO_ROUND_ENV).as_deref() != Ok("1") {
+        return Err(format!("round 0 is never emitted by drand, the message could not be decrypted. set {ALLOW_ZERO_ROUND_ENV}=1 to allow it for testing"));
+    }
+    Ok(round)
+}
+
+/// Connect to the chain at `url` over HTTP, to fetch beacons from it.
+fn connect(url: &str) -> Result<HttpClient, String> {
+    HttpClient::new(url, None).map_err(|err| err.to_string())
+}
+
 pub fn run_state_machine(state_machine: String) {
     // The plugin was started by an age client; run the state machine.
-    age_plugin_tlock::run_state_machine(
-        state_machine,
-        PLUGIN_NAME,
-        |info: &RecipientInfo, round: &str| {
-            beacon::RandomnessBeaconTime::new(
-                &chain::ChainTimeInfo::new(info.genesis_time(), info.period()),
-                round,
-            )
-            .round()
-        },
-        |url: &str, header: &Header| {
-            HttpClient::new(url, None)
-                .unwrap()
-                .get(header.round())
-                .unwrap()
-                .signature()
-        },
-    )
-    .unwrap();
+    age_plugin_tlock::run_state_machine(state_machine, PLUGIN_NAME, parse_round, connect).unwrap();
+}
+
+fn recipient_info(client: &HttpClient) -> RecipientInfo {
+    RecipientInfo::fetch(client).unwrap_or_else(|err| panic!("cannot fetch chain info: {err}"))
 }
 
 pub fn generate(url: &str) {
     let client = HttpClient::new(url, None).unwrap();
     let identity = HTTPIdentityInfo::new(&client.base_url());
-    let recipient = RecipientInfo::new(
-        &client.chain_info().unwrap().hash(),
-        &client.chain_info().unwrap().public_key(),
-        client.chain_info().unwrap().genesis_time(),
-        client.chain_info().unwrap().period(),
-    );
+    let recipient = recipient_info(&client);
     age_plugin_tlock::print_new_identity(PLUGIN_NAME, &identity.into(), &recipient)
 }
 
+pub fn generate_recipient(url: &str) {
+    let client = HttpClient::new(url, None).unwrap();
+    age_plugin_tlock::print_new_recipient(PLUGIN_NAME, &recipient_info(&client))
+}
+
 fn main() {
     let cli = cli::build();
     if let Some(state_machine) = cli.age_plugin {
         return run_state_machine(state_machine);
     }
 
-    return generate(cli.remote.unwrap().as_str());
+    let remote = cli.remote.unwrap();
+    if cli.recipient_only {
+        generate_recipient(remote.as_str())
+    } else {
+        generate(remote.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    use super::*;
+
+    // quicknet genesis time and period
+    const GENESIS_TIME: u64 = 1692803367;
+    const PERIOD: u64 = 3;
+
+    fn info() -> RecipientInfo {
+        RecipientInfo::new(&[], &[], GENESIS_TIME, PERIOD)
+    }
+
+    #[test]
+    fn test_parse_round_number() {
+        assert_eq!(parse_round(&info(), "123"), Ok(123));
+    }
+
+    #[test]
+    fn test_parse_round_invalid() {
+        let err = parse_round(&info(), "0").unwrap_err();
+        assert!(err.contains("round 0 is never emitted"), "{err}");
+        assert!(parse_round(&info(), "not a round").is_err());
+    }
+
+    #[test]
+    fn test_parse_round_duration() {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let expected = (now + 30 - GENESIS_TIME) / PERIOD;
+        let round = parse_round(&info(), "30s").unwrap();
+        assert!((expected..=expected + 2).contains(&round));
+    }
+
+    #[test]
+    fn test_parse_round_date() {
+        // 2023-08-24T00:00:00Z is 1692835200
+        let expected = (1692835200 - GENESIS_TIME) / PERIOD;
+        let round = parse_round(&info(), "2023-08-24T00:00:00Z").unwrap();
+        assert!((expected..=expected + 1).contains(&round));
+    }
 }
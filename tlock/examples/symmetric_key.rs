@@ -0,0 +1,34 @@
+use chacha20poly1305::{
+    aead::{Aead, KeyInit},
+    ChaCha20Poly1305, Nonce,
+};
+use sha2::{Digest, Sha256};
+
+// tlock encrypts 16 bytes. To encrypt larger payloads, a random key is time-locked,
+// and used with a standalone AEAD. This is what tlock_age does with age.
+fn main() {
+    // curl -sS https://api.drand.sh/7672797f548f3f4748ac4bf3352fc6c6b6468c9ad40ad456a397545c6e2df5bf/info | jq -r '.public_key'
+    let pk_bytes = hex::decode("8200fc249deb0148eb918d6e213980c5d01acd7fc251900d9260136da3b54836ce125172399ddc69c4e3e11429b62c11").unwrap();
+    let round = 1000;
+
+    let msg = b"a message longer than sixteen bytes";
+
+    // Encryption: time-lock a random key, then encrypt the message with a key derived from it
+    let mut locked_key = vec![];
+    let key = tlock::encrypt_random_key(&mut locked_key, &pk_bytes, round).unwrap();
+    let cipher = ChaCha20Poly1305::new(&Sha256::digest(key));
+    // The key is used once, a fixed nonce is fine
+    let nonce = Nonce::default();
+    let encrypted = cipher.encrypt(&nonce, msg.as_slice()).unwrap();
+
+    // Decryption: once the round is emitted, unlock the key, then decrypt the message
+    // curl -sS https://api.drand.sh/7672797f548f3f4748ac4bf3352fc6c6b6468c9ad40ad456a397545c6e2df5bf/public/1000 | jq -r '.signature'
+    let signature = hex::decode("a4721e6c3eafcd823f138cd29c6c82e8c5149101d0bb4bafddbac1c2d1fe3738895e4e21dd4b8b41bf007046440220910bb1cdb91f50a84a0d7f33ff2e8577aa62ac64b35a291a728a9db5ac91e06d1312b48a376138d77b4d6ad27c24221afe").unwrap();
+    let mut unlocked_key = vec![];
+    tlock::decrypt(&mut unlocked_key, locked_key.as_slice(), &signature).unwrap();
+    let cipher = ChaCha20Poly1305::new(&Sha256::digest(&unlocked_key));
+    let decrypted = cipher.decrypt(&nonce, encrypted.as_slice()).unwrap();
+
+    assert_eq!(msg.as_slice(), decrypted);
+    println!("Encryption and decryption were successful");
+}
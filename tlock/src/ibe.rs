@@ -7,33 +7,53 @@ use ark_ec::{
     pairing::{Pairing, PairingOutput},
     AffineRepr, CurveGroup,
 };
-use ark_ff::{field_hashers::DefaultFieldHasher, PrimeField};
+use ark_ff::{field_hashers::DefaultFieldHasher, BigInteger, Field, One, PrimeField, Zero};
 use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
-use itertools::Itertools;
-use rand::distributions::Uniform;
+use hkdf::Hkdf;
 use rand::Rng;
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
+#[cfg(feature = "serde")]
 use serde_with::DeserializeAs;
 use sha2::{digest::Update, Digest, Sha256};
-use std::{marker::PhantomData, ops::Mul};
+use std::{marker::PhantomData, ops::Mul, sync::OnceLock};
 use thiserror::Error;
+use zeroize::Zeroize;
 
 #[derive(Error, Debug)]
 pub enum IBEError {
+    #[error("V and W must be {MESSAGE_SIZE} bytes, got {v} and {w}")]
+    CiphertextSize { v: usize, w: usize },
+    #[error("share index {0} appears more than once")]
+    DuplicateShare(u32),
+    #[error("message cannot be expanded to a valid scalar")]
+    ExpandMessage,
     #[error("hash cannot be mapped to {0}")]
     HashToCurve(String),
+    #[error("signature is the point at infinity, it cannot decrypt anything")]
+    InfinitySignature,
+    #[error("bytes do not encode a point on {0}")]
+    InvalidPoint(&'static str),
     #[error("cannot initialise mapper for {hash} to BLS12-381 {field}")]
     MapperInitialisation { hash: String, field: String },
-    #[error("sigma does not fit in 16 bytes")]
+    #[error("message does not fit in {MESSAGE_SIZE} bytes")]
     MessageSize,
+    #[error("partial signatures must all be on the same curve")]
+    MixedGroups,
+    #[error("at least one partial signature is required")]
+    NoPartialSignature,
     #[error("pairing requires affines to be on different curves")]
     Pairing,
-    #[error("invalid public key size")]
-    PublicKeySize,
+    #[error("invalid public key size: expected 48 (G1) or 96 (G2) bytes, got {0}")]
+    PublicKeySize(usize),
     #[error("serialization failed")]
     Serialisation,
+    #[error("signature does not match ciphertext, it might be for a different round")]
+    SignatureMismatch,
     #[error("unknown data store error")]
     Unknown,
+    #[error("scalar is zero, the point would be at infinity")]
+    ZeroScalar,
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -42,6 +62,7 @@ pub enum GAffine {
     G2Affine(G2Affine),
 }
 
+#[cfg(feature = "serde")]
 impl Serialize for GAffine {
     fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
     where
@@ -61,6 +82,7 @@ impl Serialize for GAffine {
     }
 }
 
+#[cfg(feature = "serde")]
 impl<'de> Deserialize<'de> for GAffine {
     fn deserialize<D>(deserializer: D) -> std::result::Result<GAffine, D::Error>
     where
@@ -83,47 +105,73 @@ impl<'de> Deserialize<'de> for GAffine {
     }
 }
 
+type G1Mapper = MapToCurveBasedHasher<
+    short_weierstrass::Projective<g1::Config>,
+    DefaultFieldHasher<sha2::Sha256, 128>,
+    WBMap<g1::Config>,
+>;
+type G2Mapper = MapToCurveBasedHasher<
+    short_weierstrass::Projective<g2::Config>,
+    DefaultFieldHasher<sha2::Sha256, 128>,
+    WBMap<g2::Config>,
+>;
+
+/// Mapper hashing identities to G1.
+///
+/// A mapper only depends on its domain, it is initialised once and shared across calls and threads.
+fn g1_mapper() -> Result<&'static G1Mapper, IBEError> {
+    static MAPPER: OnceLock<Option<G1Mapper>> = OnceLock::new();
+    MAPPER
+        .get_or_init(|| G1Mapper::new(G1_DOMAIN).ok())
+        .as_ref()
+        .ok_or_else(|| IBEError::MapperInitialisation {
+            hash: "sha2".to_owned(),
+            field: "G1".to_owned(),
+        })
+}
+
+/// Mapper hashing identities to G2. See [`g1_mapper`].
+fn g2_mapper() -> Result<&'static G2Mapper, IBEError> {
+    static MAPPER: OnceLock<Option<G2Mapper>> = OnceLock::new();
+    MAPPER
+        .get_or_init(|| G2Mapper::new(G2_DOMAIN).ok())
+        .as_ref()
+        .ok_or_else(|| IBEError::MapperInitialisation {
+            hash: "sha2".to_owned(),
+            field: "G2".to_owned(),
+        })
+}
+
 impl GAffine {
     pub fn projective_pairing(
         &self,
         id: &[u8],
     ) -> anyhow::Result<PairingOutput<ark_bls12_381::Bls12_381>> {
+        Ok(self.pairing(&self.hash_to_curve(id)?)?)
+    }
+
+    /// Hash `id` to the group opposite to this point.
+    ///
+    /// A public key on G1 has signatures on G2, and the other way around.
+    pub fn hash_to_curve(&self, id: &[u8]) -> Result<GAffine, IBEError> {
         match self {
-            GAffine::G1Affine(g) => {
-                let mapper = MapToCurveBasedHasher::<
-                    short_weierstrass::Projective<g2::Config>,
-                    DefaultFieldHasher<sha2::Sha256, 128>,
-                    WBMap<g2::Config>,
-                >::new(G2_DOMAIN)
-                .map_err(|_| IBEError::MapperInitialisation {
-                    hash: "sha2".to_owned(),
-                    field: "G2".to_owned(),
-                })?;
+            GAffine::G1Affine(_) => {
                 let qid = G2Projective::from(
-                    mapper
+                    g2_mapper()?
                         .hash(id)
                         .map_err(|_| IBEError::HashToCurve("G2".to_owned()))?,
                 )
                 .into_affine();
-                Ok(Bls12_381::pairing(g, qid))
+                Ok(GAffine::G2Affine(qid))
             }
-            GAffine::G2Affine(g) => {
-                let mapper = MapToCurveBasedHasher::<
-                    short_weierstrass::Projective<g1::Config>,
-                    DefaultFieldHasher<sha2::Sha256, 128>,
-                    WBMap<g1::Config>,
-                >::new(G1_DOMAIN)
-                .map_err(|_| IBEError::MapperInitialisation {
-                    hash: "sha2".to_owned(),
-                    field: "G1".to_owned(),
-                })?;
+            GAffine::G2Affine(_) => {
                 let qid = G1Projective::from(
-                    mapper
+                    g1_mapper()?
                         .hash(id)
                         .map_err(|_| IBEError::HashToCurve("G1".to_owned()))?,
                 )
                 .into_affine();
-                Ok(Bls12_381::pairing(qid, g))
+                Ok(GAffine::G1Affine(qid))
             }
         }
     }
@@ -153,6 +201,24 @@ impl GAffine {
         }
     }
 
+    /// Same as [`GAffine::mul`], but rejects the zero scalar.
+    ///
+    /// Multiplying by zero yields the point at infinity, which is not a valid ciphertext `U`.
+    pub fn try_mul(&self, s: ScalarField) -> Result<Self, IBEError> {
+        if s.is_zero() {
+            return Err(IBEError::ZeroScalar);
+        }
+        Ok(self.mul(s))
+    }
+
+    /// Whether this is the point at infinity, the identity element of its group.
+    pub fn is_infinity(&self) -> bool {
+        match self {
+            GAffine::G1Affine(g) => g.is_zero(),
+            GAffine::G2Affine(g) => g.is_zero(),
+        }
+    }
+
     pub fn to_compressed(&self) -> anyhow::Result<Vec<u8>, IBEError> {
         let mut compressed = vec![];
         match self {
@@ -168,30 +234,98 @@ impl GAffine {
     }
 }
 
+/// Signatures unlock every ciphertext for their round, they can be zeroized once no longer needed.
+impl Zeroize for GAffine {
+    fn zeroize(&mut self) {
+        match self {
+            GAffine::G1Affine(g) => g.zeroize(),
+            GAffine::G2Affine(g) => g.zeroize(),
+        }
+    }
+}
+
 impl TryFrom<&[u8]> for GAffine {
     type Error = IBEError;
 
     fn try_from(bytes: &[u8]) -> anyhow::Result<Self, Self::Error> {
         if bytes.len() == G1_SIZE {
-            let g = G1Affine::deserialize_compressed(bytes).map_err(|_| IBEError::PublicKeySize)?;
+            let g = G1Affine::deserialize_compressed(bytes)
+                .map_err(|_| IBEError::InvalidPoint("G1"))?;
             Ok(GAffine::G1Affine(g))
         } else if bytes.len() == G2_SIZE {
-            let g = G2Affine::deserialize_compressed(bytes).map_err(|_| IBEError::PublicKeySize)?;
+            let g = G2Affine::deserialize_compressed(bytes)
+                .map_err(|_| IBEError::InvalidPoint("G2"))?;
             Ok(GAffine::G2Affine(g))
         } else {
-            Err(IBEError::PublicKeySize)
+            Err(IBEError::PublicKeySize(bytes.len()))
         }
     }
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Ciphertext {
     pub u: GAffine,
     pub v: Vec<u8>,
     pub w: Vec<u8>,
 }
 
+impl Ciphertext {
+    /// Assemble a ciphertext from its compressed U, V, and W.
+    ///
+    /// The group of U is inferred from its length, V and W are [`MESSAGE_SIZE`] bytes.
+    ///
+    /// ```rust
+    /// use tlock::ibe::{Ciphertext, G1_SIZE, MESSAGE_SIZE};
+    ///
+    /// # let pk_bytes = hex::decode("8200fc249deb0148eb918d6e213980c5d01acd7fc251900d9260136da3b54836ce125172399ddc69c4e3e11429b62c11").unwrap();
+    /// let mut encrypted = vec![];
+    /// tlock::encrypt(&mut encrypted, [8u8; 16].as_slice(), &pk_bytes, 1000).unwrap();
+    ///
+    /// let (u, vw) = encrypted.split_at(G1_SIZE);
+    /// let (v, w) = vw.split_at(MESSAGE_SIZE);
+    /// let c = Ciphertext::from_parts(u, v, w).unwrap();
+    /// ```
+    pub fn from_parts(u_bytes: &[u8], v: &[u8], w: &[u8]) -> Result<Self, IBEError> {
+        if v.len() != MESSAGE_SIZE || w.len() != MESSAGE_SIZE {
+            return Err(IBEError::CiphertextSize {
+                v: v.len(),
+                w: w.len(),
+            });
+        }
+        Ok(Self {
+            u: u_bytes.try_into()?,
+            v: v.to_vec(),
+            w: w.to_vec(),
+        })
+    }
+}
+
+#[cfg(feature = "debug-hex")]
+impl Ciphertext {
+    /// Labelled hex dump of U, V, and W, to compare with intermediate outputs of other implementations.
+    pub fn debug_hex(&self) -> String {
+        let group = match &self.u {
+            GAffine::G1Affine(_) => "G1",
+            GAffine::G2Affine(_) => "G2",
+        };
+        let u = self.u.to_compressed().unwrap_or_default();
+        format!(
+            "U ({group}, {} bytes): {}\nV ({} bytes): {}\nW ({} bytes): {}\n",
+            u.len(),
+            hex::encode(&u),
+            self.v.len(),
+            hex::encode(&self.v),
+            self.w.len(),
+            hex::encode(&self.w),
+        )
+    }
+}
+
 const BLOCK_SIZE: usize = 32;
+/// Size of sigma, V, and W. Messages up to this size can be encrypted, shorter ones are padded with zeros.
+pub const MESSAGE_SIZE: usize = 16;
+const DETERMINISTIC_SIGMA_DOMAIN: &[u8] = b"tlock-deterministic-sigma";
 #[cfg(feature = "rfc9380")]
 pub const G1_DOMAIN: &[u8] = b"BLS_SIG_BLS12381G1_XMD:SHA-256_SSWU_RO_NUL_";
 #[cfg(not(feature = "rfc9380"))]
@@ -201,122 +335,333 @@ pub const G2_DOMAIN: &[u8] = b"BLS_SIG_BLS12381G2_XMD:SHA-256_SSWU_RO_NUL_";
 pub const G1_SIZE: usize = 48;
 pub const G2_SIZE: usize = 96;
 
+/// Domain separation prefixes of the IBE hash functions IBE-H2, IBE-H3, and IBE-H4.
+///
+/// [`IbeParams::DRAND`], the default, is the only value interoperable with drand and other tlock implementations.
+/// Other prefixes are meant to experiment with tlock variants. A ciphertext only decrypts with the prefixes it was encrypted with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IbeParams {
+    /// Prefix of IBE-H2, masking sigma with the pairing output.
+    pub h2_prefix: &'static [u8],
+    /// Prefix of IBE-H3, deriving the scalar r from sigma and the message.
+    pub h3_prefix: &'static [u8],
+    /// Prefix of IBE-H4, masking the message with sigma.
+    pub h4_prefix: &'static [u8],
+}
+
+impl IbeParams {
+    /// Prefixes used by drand, and by every tlock implementation.
+    pub const DRAND: Self = Self {
+        h2_prefix: b"IBE-H2",
+        h3_prefix: b"IBE-H3",
+        h4_prefix: b"IBE-H4",
+    };
+}
+
+impl Default for IbeParams {
+    fn default() -> Self {
+        Self::DRAND
+    }
+}
+
 pub fn encrypt<I: AsRef<[u8]>, M: AsRef<[u8]>>(
     master: GAffine,
     id: I,
     msg: M,
 ) -> anyhow::Result<Ciphertext, anyhow::Error> {
-    assert!(
-        msg.as_ref().len() <= BLOCK_SIZE,
-        "plaintext too long for the block size"
-    );
+    encrypt_with_params(master, id, msg, &IbeParams::DRAND)
+}
 
+/// Encrypt with custom hash prefixes. The ciphertext only decrypts with [`decrypt_with_params`] and the same `params`.
+pub fn encrypt_with_params<I: AsRef<[u8]>, M: AsRef<[u8]>>(
+    master: GAffine,
+    id: I,
+    msg: M,
+    params: &IbeParams,
+) -> anyhow::Result<Ciphertext, anyhow::Error> {
+    let msg = pad_message(msg.as_ref())?;
     let mut rng = rand::thread_rng();
-    // 1. Compute Gid = e(master,Q_id)
-    let gid = master.projective_pairing(id.as_ref())?;
-
-    // 2. Derive random sigma
-    let sigma: [u8; 16] = (0..16)
-        .map(|_| rng.sample(Uniform::new(0u8, 8u8)))
-        .collect_vec()
-        .try_into()
-        .map_err(|_| IBEError::MessageSize)?;
-
-    // 3. Derive r from sigma and msg
-    let r: ScalarField = {
-        let hash = Sha256::new()
-            .chain(b"IBE-H3")
-            .chain(sigma.as_slice())
-            .chain(msg.as_ref())
-            .finalize();
-        let r = hash.as_slice();
 
-        let mut buf = [0u8; BLOCK_SIZE];
-        ExpandMsgDrand::<Sha256>::expand_message(r, &[], &mut buf);
-        ScalarField::from_le_bytes_mod_order(&buf)
-    };
+    // 1. Derive random sigma
+    let mut sigma = [0u8; MESSAGE_SIZE];
+    rng.fill(&mut sigma);
+    debug_assert!(
+        looks_random(&sigma),
+        "sigma lacks entropy, the random number generator is degenerate"
+    );
 
-    // 4. Compute U = G^r
-    let u = master.generator().mul(r);
+    // 2. Derive r from sigma and msg
+    let r = h3(params.h3_prefix, &sigma, &msg)?;
 
-    // 5. Compute V = sigma XOR H(rGid)
-    let v = {
-        let r_gid_out = gid.mul(r);
-        let mut r_gid = vec![];
-        r_gid_out
-            .serialize_with_mode(&mut r_gid, ark_serialize::Compress::Yes)
-            .map_err(|_| IBEError::Serialisation)?;
-        let r_gid = &r_gid.into_iter().rev().collect_vec();
-
-        let hash = sha2::Sha256::new()
-            .chain(b"IBE-H2") // dst
-            .chain(r_gid)
-            .finalize();
+    encrypt_with_sigma_and_r(master, id.as_ref(), &msg, &sigma, r, params)
+}
 
-        let h_r_git = &hash.to_vec()[0..16];
+/// Encrypt with sigma derived from `secret`, so identical inputs yield identical ciphertexts.
+///
+/// Sigma is derived with HKDF-SHA256 from `secret`, the public key, `id`, and the padded message.
+/// This allows deduplicating ciphertexts, at the cost of semantic security: anyone can tell two ciphertexts encrypted with the same secret hold the same message for the same key and id.
+/// `secret` has to be uniformly random and kept private. Otherwise, the message can be recovered by brute force before the round is reached, since a guess can be checked by reencrypting it.
+pub fn encrypt_deterministic<I: AsRef<[u8]>, M: AsRef<[u8]>>(
+    master: GAffine,
+    id: I,
+    msg: M,
+    secret: &[u8],
+) -> anyhow::Result<Ciphertext, anyhow::Error> {
+    let msg = pad_message(msg.as_ref())?;
 
-        xor(&sigma, h_r_git)
-    };
+    // 1. Derive sigma from the secret and all public inputs. Id is last, as it is the only input of variable size
+    let mut info = master.to_compressed()?;
+    info.extend_from_slice(&msg);
+    info.extend_from_slice(id.as_ref());
+    let mut sigma = [0u8; MESSAGE_SIZE];
+    Hkdf::<Sha256>::new(Some(DETERMINISTIC_SIGMA_DOMAIN), secret)
+        .expand(&info, &mut sigma)
+        .expect("MESSAGE_SIZE is a valid HKDF-SHA256 output length");
+
+    // 2. Derive r from sigma and msg
+    let params = IbeParams::DRAND;
+    let r = h3(params.h3_prefix, &sigma, &msg)?;
+
+    encrypt_with_sigma_and_r(master, id.as_ref(), &msg, &sigma, r, &params)
+}
+
+/// Encrypt with a fixed `sigma` and ephemeral scalar `r`, to reproduce test vectors byte for byte.
+///
+/// The ciphertext depends only on the inputs, so it can be compared byte for byte with a vector generated with the same `sigma` and `r`.
+/// They only decrypt if `r` is the one derived from `sigma` and `msg`, otherwise the `U = G^r` check fails with [`IBEError::SignatureMismatch`].
+/// Fixing sigma removes all randomness, this MUST NOT be used outside of tests.
+#[cfg(any(test, feature = "test-util"))]
+pub fn encrypt_with_r<I: AsRef<[u8]>, M: AsRef<[u8]>>(
+    master: GAffine,
+    id: I,
+    msg: M,
+    sigma: &[u8; MESSAGE_SIZE],
+    r: ScalarField,
+) -> anyhow::Result<Ciphertext, anyhow::Error> {
+    let msg = pad_message(msg.as_ref())?;
+    encrypt_with_sigma_and_r(master, id.as_ref(), &msg, sigma, r, &IbeParams::DRAND)
+}
+
+fn encrypt_with_sigma_and_r(
+    master: GAffine,
+    id: &[u8],
+    msg: &[u8; MESSAGE_SIZE],
+    sigma: &[u8; MESSAGE_SIZE],
+    r: ScalarField,
+    params: &IbeParams,
+) -> anyhow::Result<Ciphertext, anyhow::Error> {
+    // 3. Compute Gid = e(master,Q_id)
+    let gid = master.projective_pairing(id)?;
+
+    // 4. Compute U = G^r, r is derived from a hash and should never be zero
+    let u = master.generator().try_mul(r)?;
+
+    // 5. Compute V = sigma XOR H(rGid)
+    let v = xor(sigma, &h2(params.h2_prefix, &gid.mul(r))?);
 
     // 6. Compute W = M XOR H(sigma)
-    let w = {
-        let hash = sha2::Sha256::new()
-            .chain(b"IBE-H4")
-            .chain(sigma.as_slice())
-            .finalize();
-        let h_sigma = &hash.to_vec()[0..16];
-        xor(msg.as_ref(), h_sigma)
-    };
+    let w = xor(msg, &h4(params.h4_prefix, sigma));
 
     Ok(Ciphertext { u, v, w })
 }
 
+/// Pad `msg` with zeros to [`MESSAGE_SIZE`] bytes.
+fn pad_message(msg: &[u8]) -> Result<[u8; MESSAGE_SIZE], IBEError> {
+    if msg.len() > MESSAGE_SIZE {
+        return Err(IBEError::MessageSize);
+    }
+    let mut padded = [0u8; MESSAGE_SIZE];
+    padded[..msg.len()].copy_from_slice(msg);
+    Ok(padded)
+}
+
+/// Sanity check that `sigma` was drawn from a healthy random number generator.
+///
+/// Bytes of a uniformly random sigma cover at least 5 bits and take at least 4 distinct values, except with negligible probability.
+/// This catches generators restricted to a small range or stuck on a few values, it is not a statistical test.
+fn looks_random(sigma: &[u8; MESSAGE_SIZE]) -> bool {
+    let bits = sigma.iter().fold(0u8, |acc, b| acc | b).count_ones();
+    let mut distinct = sigma.to_vec();
+    distinct.sort_unstable();
+    distinct.dedup();
+    bits >= 5 && distinct.len() >= 4
+}
+
+/// Decrypt `c` with `private`, the signature of the identity it is encrypted to.
+///
+/// Ciphertexts with a V or W that is not [`MESSAGE_SIZE`] bytes, as can be built from public fields, return [`IBEError::CiphertextSize`].
 pub fn decrypt(private: GAffine, c: &Ciphertext) -> anyhow::Result<Vec<u8>, IBEError> {
-    assert!(
-        c.w.len() <= BLOCK_SIZE,
-        "ciphertext too long for the block size"
-    );
+    decrypt_with_params(private, c, &IbeParams::DRAND)
+}
+
+/// Decrypt a ciphertext produced by [`encrypt_with_params`] with the same `params`.
+pub fn decrypt_with_params(
+    private: GAffine,
+    c: &Ciphertext,
+    params: &IbeParams,
+) -> anyhow::Result<Vec<u8>, IBEError> {
+    let (sigma, msg) = unmask(&private, c, params)?;
+
+    // 3. Check U = G^r
+    let r_g = c.u.generator().mul(h3(params.h3_prefix, &sigma, &msg)?);
+    if c.u != r_g {
+        return Err(IBEError::SignatureMismatch);
+    }
+
+    Ok(msg)
+}
+
+/// Decrypt without checking U = G^r.
+///
+/// This skips a scalar multiplication, but does not verify the ciphertext authenticity.
+/// A signature that does not match the ciphertext decrypts to garbage instead of returning an error.
+pub fn decrypt_unchecked(private: GAffine, c: &Ciphertext) -> anyhow::Result<Vec<u8>, IBEError> {
+    let (_sigma, msg) = unmask(&private, c, &IbeParams::DRAND)?;
+    Ok(msg)
+}
+
+/// Decrypt using a precomputed pairing `gid = e(U, private)`.
+///
+/// This skips the pairing, which is the most expensive step of decryption.
+/// The caller is responsible for computing `gid` from the ciphertext U and a valid signature, for instance as part of a batch verification.
+/// U = G^r is still checked, so a wrong `gid` returns an error.
+pub fn decrypt_with_gid(
+    gid: PairingOutput<Bls12_381>,
+    c: &Ciphertext,
+) -> anyhow::Result<Vec<u8>, IBEError> {
+    let params = IbeParams::DRAND;
+    let (sigma, msg) = unmask_with_gid(&gid, c, &params)?;
+
+    let r_g = c.u.generator().mul(h3(params.h3_prefix, &sigma, &msg)?);
+    if c.u != r_g {
+        return Err(IBEError::SignatureMismatch);
+    }
+
+    Ok(msg)
+}
+
+/// Recover sigma and the message from the ciphertext.
+///
+/// A signature at infinity pairs to the identity whatever the ciphertext, it is rejected.
+fn unmask(
+    private: &GAffine,
+    c: &Ciphertext,
+    params: &IbeParams,
+) -> Result<(Vec<u8>, Vec<u8>), IBEError> {
+    if private.is_infinity() {
+        return Err(IBEError::InfinitySignature);
+    }
+    unmask_with_gid(&private.pairing(&c.u)?, c, params)
+}
+
+/// Recover sigma and the message from the ciphertext, given `gid = e(U, private)`.
+///
+/// V and W are [`MESSAGE_SIZE`] bytes, as produced by [`encrypt`].
+fn unmask_with_gid(
+    gid: &PairingOutput<Bls12_381>,
+    c: &Ciphertext,
+    params: &IbeParams,
+) -> Result<(Vec<u8>, Vec<u8>), IBEError> {
+    if c.v.len() != MESSAGE_SIZE || c.w.len() != MESSAGE_SIZE {
+        return Err(IBEError::CiphertextSize {
+            v: c.v.len(),
+            w: c.w.len(),
+        });
+    }
 
     // 1. Compute sigma = V XOR H2(e(rP,private))
-    let sigma = {
-        let r_gid_out = private.pairing(&c.u)?;
-        let mut r_gid = vec![];
-        r_gid_out
-            .serialize_with_mode(&mut r_gid, ark_serialize::Compress::Yes)
-            .map_err(|_| IBEError::Serialisation)?;
-        let r_gid = &r_gid.into_iter().rev().collect_vec();
-
-        let hash = sha2::Sha256::new().chain(b"IBE-H2").chain(r_gid).finalize();
-        let h_r_git = &hash.to_vec()[0..16];
-        xor(h_r_git, &c.v[c.v.len() - 16..])
-    };
+    let sigma = xor(&h2(params.h2_prefix, gid)?, &c.v);
 
     // 2. Compute Msg = W XOR H4(sigma)
-    let msg = {
-        let hash = sha2::Sha256::new()
-            .chain(b"IBE-H4")
-            .chain(&sigma)
-            .finalize();
-        let h_sigma = &hash.to_vec()[0..16];
-        xor(h_sigma, &c.w[c.w.len() - 16..])
-    };
+    let msg = xor(&h4(params.h4_prefix, &sigma), &c.w);
 
-    // 3. Check U = G^r
-    let r_g = {
-        let hash = sha2::Sha256::new()
-            .chain(b"IBE-H3")
-            .chain(&sigma)
-            .chain(&msg)
-            .finalize();
-        let r = hash.as_slice();
-        let mut buf = [0u8; BLOCK_SIZE];
-        ExpandMsgDrand::<Sha256>::expand_message(r, &[], &mut buf);
-        let r = ScalarField::from_le_bytes_mod_order(&buf);
-        c.u.generator().mul(r)
+    Ok((sigma, msg))
+}
+
+/// Recover the group signature of a threshold network from partial signatures, by Lagrange interpolation at 0.
+///
+/// Each partial is paired with the share index of the node which produced it. Indices start at 0, as in drand: share `i` is the evaluation of the group secret polynomial at `i + 1`.
+/// All partials are interpolated. With a `t`-of-`n` network, pass exactly `t` of them, fewer yield a different point.
+/// Partials are not verified, an invalid one yields an invalid signature. Check the result against the group public key before use.
+pub fn recover_signature(partials: &[(u32, GAffine)]) -> Result<GAffine, IBEError> {
+    let Some((_, first)) = partials.first() else {
+        return Err(IBEError::NoPartialSignature);
     };
-    assert_eq!(c.u, r_g);
+    let xs: Vec<ScalarField> = partials
+        .iter()
+        .map(|(index, _)| ScalarField::from(u64::from(*index) + 1))
+        .collect();
 
-    Ok(msg)
+    let mut g1 = G1Projective::zero();
+    let mut g2 = G2Projective::zero();
+    for (j, (index, partial)) in partials.iter().enumerate() {
+        // lambda_j = prod_{m != j} x_m / (x_m - x_j)
+        let mut numerator = ScalarField::one();
+        let mut denominator = ScalarField::one();
+        for (m, x) in xs.iter().enumerate() {
+            if m == j {
+                continue;
+            }
+            if *x == xs[j] {
+                return Err(IBEError::DuplicateShare(*index));
+            }
+            numerator *= x;
+            denominator *= *x - xs[j];
+        }
+        let lambda = numerator
+            * denominator
+                .inverse()
+                .expect("indices are distinct, the denominator is not zero");
+
+        match (first, partial) {
+            (GAffine::G1Affine(_), GAffine::G1Affine(p)) => g1 += p.mul(lambda),
+            (GAffine::G2Affine(_), GAffine::G2Affine(p)) => g2 += p.mul(lambda),
+            _ => return Err(IBEError::MixedGroups),
+        }
+    }
+
+    Ok(match first {
+        GAffine::G1Affine(_) => GAffine::G1Affine(g1.into_affine()),
+        GAffine::G2Affine(_) => GAffine::G2Affine(g2.into_affine()),
+    })
+}
+
+/// IBE-H2, hashes a pairing output to a [`MESSAGE_SIZE`] mask.
+///
+/// The pairing output is serialised compressed and its bytes reversed, to match drand big-endian encoding.
+/// The mask is the first [`MESSAGE_SIZE`] bytes of `sha256(prefix || reversed(r_gid))`, where prefix is `IBE-H2` for drand.
+fn h2(prefix: &[u8], r_gid: &PairingOutput<Bls12_381>) -> Result<Vec<u8>, IBEError> {
+    let mut bytes = vec![];
+    r_gid
+        .serialize_with_mode(&mut bytes, ark_serialize::Compress::Yes)
+        .map_err(|_| IBEError::Serialisation)?;
+    bytes.reverse();
+
+    let hash = Sha256::new().chain(prefix).chain(bytes).finalize();
+    Ok(hash[..MESSAGE_SIZE].to_vec())
+}
+
+/// IBE-H3, derives the scalar r from sigma and the message.
+///
+/// `sha256(prefix || sigma || msg)`, where prefix is `IBE-H3` for drand, is expanded with [`ExpandMsgDrand`] until it fits in the scalar field.
+fn h3(prefix: &[u8], sigma: &[u8], msg: &[u8]) -> Result<ScalarField, IBEError> {
+    let hash = Sha256::new()
+        .chain(prefix)
+        .chain(sigma)
+        .chain(msg)
+        .finalize();
+
+    let mut buf = [0u8; BLOCK_SIZE];
+    ExpandMsgDrand::<Sha256>::expand(hash.as_slice(), &mut buf)?;
+    Ok(ScalarField::from_le_bytes_mod_order(&buf))
+}
+
+/// IBE-H4, hashes sigma to a [`MESSAGE_SIZE`] mask.
+///
+/// The mask is the first [`MESSAGE_SIZE`] bytes of `sha256(prefix || sigma)`, where prefix is `IBE-H4` for drand.
+fn h4(prefix: &[u8], sigma: &[u8]) -> Vec<u8> {
+    let hash = Sha256::new().chain(prefix).chain(sigma).finalize();
+    hash[..MESSAGE_SIZE].to_vec()
 }
 
 fn xor(a: &[u8], b: &[u8]) -> Vec<u8> {
@@ -326,18 +671,58 @@ fn xor(a: &[u8], b: &[u8]) -> Vec<u8> {
     a.iter().zip(b.iter()).map(|(a, b)| a ^ b).collect()
 }
 
-/// Placeholder type for implementing expand_message_drand based on a hash function
+/// expand_message_drand, the hash to scalar used by drand IBE-H3, based on a hash function.
+///
+/// This is not the RFC 9380 `expand_message`. It follows `h3` of [drand/kyber IBE](https://github.com/drand/kyber/blob/master/encrypt/ibe/ibe.go), for BLS12-381:
+///
+/// 1. For a counter `i` starting at 1, compute `h = HashT(i || msg)`, with `i` encoded as a little endian `u16`.
+/// 2. Mask the top bit of `h` by shifting its first byte right by one, so that `h`, read big endian, is below 2^255.
+/// 3. Reverse `h`, to get the little endian encoding expected by arkworks scalars.
+/// 4. Accept `h` if it is below the scalar field order r, as kyber `UnmarshalBinary` does. Otherwise, increment `i` and start over.
+///
+/// About one candidate in ten is rejected. Callers derive the scalar with [`PrimeField::from_le_bytes_mod_order`], which leaves an accepted candidate unchanged.
 #[derive(Debug)]
 pub struct ExpandMsgDrand<HashT> {
     phantom: PhantomData<HashT>,
 }
 
-/// ExpandMsgXmd implements expand_message_drand for the ExpandMsg trait
 impl<HashT> ExpandMsgDrand<HashT>
 where
     HashT: Digest + Update,
 {
-    fn expand_message(msg: &[u8], _dst: &[u8], buf: &mut [u8]) {
+    /// Expand `msg` into `buf`, which has to be the size of the `HashT` output.
+    ///
+    /// ```rust
+    /// use ark_ff::PrimeField;
+    /// use sha2::Sha256;
+    /// use tlock::ibe::ExpandMsgDrand;
+    ///
+    /// let mut buf = [0u8; 32];
+    /// ExpandMsgDrand::<Sha256>::expand(b"msg", &mut buf).unwrap();
+    /// let scalar = ark_bls12_381::Fr::from_le_bytes_mod_order(&buf);
+    /// ```
+    pub fn expand(msg: &[u8], buf: &mut [u8]) -> Result<(), IBEError> {
+        if buf.len() != <HashT as Digest>::output_size() {
+            return Err(IBEError::ExpandMessage);
+        }
+        // a candidate is canonical if reducing it modulo r leaves it unchanged
+        Self::expand_message_with(msg, buf, |candidate| {
+            ScalarField::from_le_bytes_mod_order(candidate)
+                .into_bigint()
+                .to_bytes_le()
+                == candidate
+        })
+    }
+
+    /// Expands `msg` into `buf`, accepting the first little endian candidate for which `is_valid` holds.
+    ///
+    /// Returns [`IBEError::ExpandMessage`] if no candidate is accepted within `u16::MAX` iterations,
+    /// leaving `buf` untouched rather than handing an all-zero scalar to the caller.
+    fn expand_message_with(
+        msg: &[u8],
+        buf: &mut [u8],
+        is_valid: impl Fn(&[u8]) -> bool,
+    ) -> Result<(), IBEError> {
         // drand "hash"
         const BITS_TO_MASK_FOR_BLS12381: usize = 1;
         for i in 1..u16::MAX {
@@ -352,14 +737,12 @@ where
             // test if we can build a valid scalar out of n
             // this is a hash method to be compatible with the existing implementation
             let rev: Vec<u8> = h.iter().copied().rev().collect();
-            if ScalarField::from_le_bytes_mod_order(&rev)
-                .serialized_size(ark_serialize::Compress::Yes)
-                > 0
-            {
+            if is_valid(&rev) {
                 buf.copy_from_slice(&rev);
-                return;
+                return Ok(());
             }
         }
+        Err(IBEError::ExpandMessage)
     }
 }
 
@@ -367,6 +750,412 @@ where
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_h2() {
+        let gt = Bls12_381::pairing(G1Affine::generator(), G2Affine::generator());
+        let expected = hex::decode("cb87319f24560b5231579a09ad79f12e").unwrap();
+        assert_eq!(h2(b"IBE-H2", &gt).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_h3() {
+        let mut r = vec![];
+        h3(b"IBE-H3", &[0u8; 16], &[0u8; 16])
+            .unwrap()
+            .serialize_compressed(&mut r)
+            .unwrap();
+        let expected =
+            hex::decode("4eb665e995edd83e87663f8d50328e1c5ef08e94355dab578b0da350f3e29510")
+                .unwrap();
+        assert_eq!(r, expected);
+    }
+
+    #[test]
+    fn test_expand() {
+        let msg = Sha256::new().chain(b"IBE-H3").chain([0u8; 32]).finalize();
+        let mut buf = [0u8; BLOCK_SIZE];
+        ExpandMsgDrand::<Sha256>::expand(&msg, &mut buf).unwrap();
+
+        // first candidate: sha256(1u16 || msg), top bit masked, reversed
+        let mut expected = Sha256::new()
+            .chain(1u16.to_le_bytes())
+            .chain(msg)
+            .finalize();
+        expected[0] >>= 1;
+        expected.reverse();
+        assert_eq!(buf.as_slice(), expected.as_slice());
+        assert_eq!(
+            hex::encode(buf),
+            "4eb665e995edd83e87663f8d50328e1c5ef08e94355dab578b0da350f3e29510"
+        );
+
+        for len in [0, 16, 33, 64] {
+            let mut buf = vec![0u8; len];
+            assert!(matches!(
+                ExpandMsgDrand::<Sha256>::expand(&msg, &mut buf),
+                Err(IBEError::ExpandMessage)
+            ));
+        }
+    }
+
+    #[test]
+    fn test_expand_rejects_non_canonical() {
+        let candidate = |i: u16, msg: &[u8]| {
+            let mut h = Sha256::new().chain(i.to_le_bytes()).chain(msg).finalize();
+            h[0] >>= 1;
+            h.reverse();
+            h
+        };
+
+        // the first candidate for this message is not below r, drand moves on to the second one
+        let msg = [6u8; 32];
+        let first = candidate(1, &msg);
+        assert_ne!(
+            ScalarField::from_le_bytes_mod_order(&first)
+                .into_bigint()
+                .to_bytes_le(),
+            first.as_slice()
+        );
+
+        let mut buf = [0u8; BLOCK_SIZE];
+        ExpandMsgDrand::<Sha256>::expand(&msg, &mut buf).unwrap();
+        assert_eq!(buf.as_slice(), candidate(2, &msg).as_slice());
+        assert_eq!(
+            hex::encode(buf),
+            "077e0c4ddc254fca2862297209e83e6e43f47c514e5138ba657e6c0fc2b48e09"
+        );
+    }
+
+    #[test]
+    fn test_expand_message_exhausted() {
+        let mut buf = [0u8; BLOCK_SIZE];
+        let result = ExpandMsgDrand::<Sha256>::expand_message_with(&[0u8; 32], &mut buf, |_| false);
+        assert!(matches!(result, Err(IBEError::ExpandMessage)));
+        assert_eq!(buf, [0u8; BLOCK_SIZE]);
+    }
+
+    #[test]
+    fn test_try_mul_zero_scalar() {
+        for g in [
+            GAffine::G1Affine(G1Affine::generator()),
+            GAffine::G2Affine(G2Affine::generator()),
+        ] {
+            assert!(matches!(
+                g.try_mul(ScalarField::zero()),
+                Err(IBEError::ZeroScalar)
+            ));
+            assert_eq!(g.try_mul(ScalarField::from(1u64)).unwrap(), g);
+        }
+    }
+
+    #[test]
+    fn test_gaffine_try_from_invalid() {
+        let err = GAffine::try_from([0u8; 49].as_slice()).unwrap_err();
+        assert!(matches!(err, IBEError::PublicKeySize(49)));
+        assert_eq!(
+            err.to_string(),
+            "invalid public key size: expected 48 (G1) or 96 (G2) bytes, got 49"
+        );
+
+        // compressed flag, with an x coordinate larger than the field modulus
+        let mut g1 = [0xffu8; G1_SIZE];
+        g1[0] = 0x9f;
+        assert!(matches!(
+            GAffine::try_from(g1.as_slice()),
+            Err(IBEError::InvalidPoint("G1"))
+        ));
+        let mut g2 = [0xffu8; G2_SIZE];
+        g2[0] = 0x9f;
+        assert!(matches!(
+            GAffine::try_from(g2.as_slice()),
+            Err(IBEError::InvalidPoint("G2"))
+        ));
+    }
+
+    #[test]
+    fn test_hash_to_curve_cached_mapper() {
+        let id = h4(b"IBE-H4", b"tlock");
+        let fresh = G1Mapper::new(G1_DOMAIN).unwrap().hash(&id).unwrap();
+        let pk = GAffine::G2Affine(G2Affine::generator());
+
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let (pk, id) = (pk.clone(), id.clone());
+                std::thread::spawn(move || pk.hash_to_curve(&id).unwrap())
+            })
+            .collect();
+        for handle in handles {
+            assert_eq!(handle.join().unwrap(), GAffine::G1Affine(fresh));
+        }
+
+        let fresh = G2Mapper::new(G2_DOMAIN).unwrap().hash(&id).unwrap();
+        let pk = GAffine::G1Affine(G1Affine::generator());
+        assert_eq!(pk.hash_to_curve(&id).unwrap(), GAffine::G2Affine(fresh));
+    }
+
+    #[test]
+    fn test_looks_random() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..1000 {
+            let mut sigma = [0u8; 16];
+            rng.fill(&mut sigma);
+            assert!(looks_random(&sigma));
+        }
+
+        let narrow: [u8; 16] = std::array::from_fn(|_| rng.gen_range(0u8..8));
+        let repeated: [u8; 16] = std::array::from_fn(|i| [0x5a, 0xa5][i % 2]);
+        for sigma in [[0u8; 16], [0xff; 16], narrow, repeated] {
+            assert!(!looks_random(&sigma));
+        }
+    }
+
+    #[test]
+    fn test_encrypt_with_r() {
+        // testnet-unchained-3s, public key on G1, signature for round 1000
+        let pk = GAffine::try_from(hex::decode("8200fc249deb0148eb918d6e213980c5d01acd7fc251900d9260136da3b54836ce125172399ddc69c4e3e11429b62c11").unwrap().as_slice()).unwrap();
+        let signature = GAffine::try_from(hex::decode("a4721e6c3eafcd823f138cd29c6c82e8c5149101d0bb4bafddbac1c2d1fe3738895e4e21dd4b8b41bf007046440220910bb1cdb91f50a84a0d7f33ff2e8577aa62ac64b35a291a728a9db5ac91e06d1312b48a376138d77b4d6ad27c24221afe").unwrap().as_slice()).unwrap();
+        let id = crate::round_id(1000.into());
+
+        let msg = [8u8; 16];
+        let sigma = [7u8; 16];
+        let r = h3(b"IBE-H3", &sigma, &msg).unwrap();
+        let c = encrypt_with_r(pk.clone(), &id, msg, &sigma, r).unwrap();
+        let again = encrypt_with_r(pk.clone(), &id, msg, &sigma, r).unwrap();
+        assert_eq!(c.u, again.u);
+        assert_eq!((&c.v, &c.w), (&again.v, &again.w));
+        assert_eq!(decrypt(signature.clone(), &c).unwrap(), msg);
+
+        // r not derived from sigma and msg fails the U = G^r check
+        let c = encrypt_with_r(pk, &id, msg, &sigma, r + ScalarField::from(1u64)).unwrap();
+        assert!(matches!(
+            decrypt(signature, &c),
+            Err(IBEError::SignatureMismatch)
+        ));
+    }
+
+    #[test]
+    fn test_encrypt_deterministic() {
+        let pk = GAffine::G2Affine(G2Affine::generator());
+        let signature = pk.hash_to_curve(b"id").unwrap();
+
+        let c = encrypt_deterministic(pk.clone(), b"id", [8u8; 16], b"secret").unwrap();
+        let again = encrypt_deterministic(pk.clone(), b"id", [8u8; 16], b"secret").unwrap();
+        assert_eq!(c.u, again.u);
+        assert_eq!((&c.v, &c.w), (&again.v, &again.w));
+        assert_eq!(decrypt(signature, &c).unwrap(), [8u8; 16]);
+
+        // changing any input changes the ciphertext
+        for other in [
+            encrypt_deterministic(pk.clone(), b"id", [8u8; 16], b"other").unwrap(),
+            encrypt_deterministic(pk.clone(), b"other", [8u8; 16], b"secret").unwrap(),
+            encrypt_deterministic(pk.clone(), b"id", [9u8; 16], b"secret").unwrap(),
+        ] {
+            assert_ne!(c.u, other.u);
+        }
+    }
+
+    #[test]
+    fn test_encrypt_message_size() {
+        let pk = GAffine::G2Affine(G2Affine::generator());
+        let signature = pk.hash_to_curve(b"id").unwrap();
+
+        let c = encrypt(pk.clone(), b"id", [8u8; 10]).unwrap();
+        assert_eq!((c.v.len(), c.w.len()), (MESSAGE_SIZE, MESSAGE_SIZE));
+        let mut expected = [0u8; MESSAGE_SIZE];
+        expected[..10].fill(8);
+        assert_eq!(decrypt(signature, &c).unwrap(), expected);
+
+        let err = encrypt(pk, b"id", [8u8; MESSAGE_SIZE + 1]).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<IBEError>(),
+            Some(IBEError::MessageSize)
+        ));
+    }
+
+    #[test]
+    fn test_ibe_params() {
+        assert_eq!(IbeParams::default(), IbeParams::DRAND);
+
+        let pk = GAffine::G2Affine(G2Affine::generator());
+        let signature = pk.hash_to_curve(b"id").unwrap();
+        let msg = [8u8; MESSAGE_SIZE];
+
+        // default prefixes are byte identical to encrypt and decrypt
+        let sigma = [7u8; MESSAGE_SIZE];
+        let r = h3(IbeParams::DRAND.h3_prefix, &sigma, &msg).unwrap();
+        let with_params =
+            encrypt_with_sigma_and_r(pk.clone(), b"id", &msg, &sigma, r, &IbeParams::default())
+                .unwrap();
+        let with_r = encrypt_with_r(pk.clone(), b"id", msg, &sigma, r).unwrap();
+        assert_eq!(
+            (with_params.u, with_params.v, with_params.w),
+            (with_r.u.clone(), with_r.v.clone(), with_r.w.clone())
+        );
+        let c = encrypt_with_params(pk.clone(), b"id", msg, &IbeParams::default()).unwrap();
+        assert_eq!(decrypt(signature.clone(), &c).unwrap(), msg);
+        assert_eq!(
+            decrypt_with_params(signature.clone(), &with_r, &IbeParams::default()).unwrap(),
+            msg
+        );
+
+        // experimental prefixes only decrypt with themselves
+        let params = IbeParams {
+            h2_prefix: b"EXP-H2",
+            h3_prefix: b"EXP-H3",
+            h4_prefix: b"EXP-H4",
+        };
+        let c = encrypt_with_params(pk, b"id", msg, &params).unwrap();
+        assert_eq!(
+            decrypt_with_params(signature.clone(), &c, &params).unwrap(),
+            msg
+        );
+        assert!(matches!(
+            decrypt(signature, &c),
+            Err(IBEError::SignatureMismatch)
+        ));
+    }
+
+    #[test]
+    fn test_decrypt_ciphertext_size() {
+        let pk = GAffine::G2Affine(G2Affine::generator());
+        let signature = pk.hash_to_curve(b"id").unwrap();
+        let c = encrypt(pk, b"id", [8u8; MESSAGE_SIZE]).unwrap();
+        let gid = signature.pairing(&c.u).unwrap();
+
+        for (v, w) in [(0, 16), (16, 0), (15, 16), (16, 15), (17, 16), (16, 32)] {
+            let c = Ciphertext {
+                u: c.u.clone(),
+                v: vec![0u8; v],
+                w: vec![0u8; w],
+            };
+            assert!(matches!(
+                decrypt(signature.clone(), &c),
+                Err(IBEError::CiphertextSize { v: v_len, w: w_len }) if (v_len, w_len) == (v, w)
+            ));
+            assert!(matches!(
+                decrypt_unchecked(signature.clone(), &c),
+                Err(IBEError::CiphertextSize { .. })
+            ));
+            assert!(matches!(
+                decrypt_with_gid(gid, &c),
+                Err(IBEError::CiphertextSize { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn test_recover_signature() {
+        // 3-of-5 network, secret polynomial f(x) = 42 + 7x + 3x^2, group secret f(0)
+        let f = |x: u64| ScalarField::from(42 + 7 * x + 3 * x * x);
+        let secret = f(0);
+        let msg = [8u8; MESSAGE_SIZE];
+
+        for pk in [
+            GAffine::G1Affine(G1Affine::generator()),
+            GAffine::G2Affine(G2Affine::generator()),
+        ] {
+            let h = pk.hash_to_curve(b"id").unwrap();
+            let partials: Vec<(u32, GAffine)> =
+                (0..5).map(|i| (i, h.mul(f(u64::from(i) + 1)))).collect();
+            let c = encrypt(pk.mul(secret), b"id", msg).unwrap();
+
+            for subset in [[0, 1, 2], [0, 2, 4], [4, 3, 1]] {
+                let subset: Vec<_> = subset.iter().map(|i| partials[*i].clone()).collect();
+                let signature = recover_signature(&subset).unwrap();
+                assert_eq!(signature, h.mul(secret));
+                assert_eq!(decrypt(signature, &c).unwrap(), msg);
+            }
+
+            // below threshold, interpolation yields another point
+            assert_ne!(recover_signature(&partials[..2]).unwrap(), h.mul(secret));
+            assert!(matches!(
+                recover_signature(&[partials[0].clone(), partials[0].clone()]),
+                Err(IBEError::DuplicateShare(0))
+            ));
+        }
+
+        let g1 = GAffine::G1Affine(G1Affine::generator());
+        let g2 = GAffine::G2Affine(G2Affine::generator());
+        assert!(matches!(
+            recover_signature(&[(0, g1), (1, g2)]),
+            Err(IBEError::MixedGroups)
+        ));
+        assert!(matches!(
+            recover_signature(&[]),
+            Err(IBEError::NoPartialSignature)
+        ));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_ciphertext_serde() {
+        for (pk, u_len) in [
+            (GAffine::G1Affine(G1Affine::generator()), G1_SIZE),
+            (GAffine::G2Affine(G2Affine::generator()), G2_SIZE),
+        ] {
+            let c = encrypt(pk, b"id", [8u8; MESSAGE_SIZE]).unwrap();
+            let json = serde_json::to_value(&c).unwrap();
+            assert_eq!(json["u"].as_array().unwrap().len(), u_len);
+
+            let parsed: Ciphertext = serde_json::from_value(json).unwrap();
+            assert_eq!(parsed.u, c.u);
+            assert_eq!(parsed.v, c.v);
+            assert_eq!(parsed.w, c.w);
+        }
+
+        for u in [vec![], vec![0u8; G1_SIZE - 1], vec![0u8; G2_SIZE + 1]] {
+            let json = serde_json::json!({ "u": u, "v": vec![0u8; MESSAGE_SIZE], "w": vec![0u8; MESSAGE_SIZE] });
+            let err = serde_json::from_value::<Ciphertext>(json).unwrap_err();
+            assert!(
+                err.to_string().contains("Invalid len Should be 48 of 96"),
+                "{err}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_ciphertext_from_parts() {
+        let g1 = GAffine::G1Affine(G1Affine::generator())
+            .to_compressed()
+            .unwrap();
+        let g2 = GAffine::G2Affine(G2Affine::generator())
+            .to_compressed()
+            .unwrap();
+        let (v, w) = ([1u8; MESSAGE_SIZE], [2u8; MESSAGE_SIZE]);
+
+        let c = Ciphertext::from_parts(&g1, &v, &w).unwrap();
+        assert_eq!(c.u, GAffine::G1Affine(G1Affine::generator()));
+        assert_eq!(
+            (c.v.as_slice(), c.w.as_slice()),
+            (v.as_slice(), w.as_slice())
+        );
+        let c = Ciphertext::from_parts(&g2, &v, &w).unwrap();
+        assert_eq!(c.u, GAffine::G2Affine(G2Affine::generator()));
+
+        assert!(matches!(
+            Ciphertext::from_parts(&g1, &v[1..], &w),
+            Err(IBEError::CiphertextSize { v: 15, w: 16 })
+        ));
+        assert!(matches!(
+            Ciphertext::from_parts(&g1, &v, &[0u8; 32]),
+            Err(IBEError::CiphertextSize { v: 16, w: 32 })
+        ));
+        assert!(matches!(
+            Ciphertext::from_parts(&g1[1..], &v, &w),
+            Err(IBEError::PublicKeySize(47))
+        ));
+        assert!(matches!(
+            Ciphertext::from_parts(&[0x9f; G1_SIZE], &v, &w),
+            Err(IBEError::InvalidPoint("G1"))
+        ));
+    }
+
+    #[test]
+    fn test_h4() {
+        let expected = hex::decode("e98934fb796adfa42b207a1b701a473d").unwrap();
+        assert_eq!(h4(b"IBE-H4", &[0u8; 16]), expected);
+    }
+
     #[test]
     fn test_xor_extended_truth_table() {
         let a = vec![0b00000000u8, 0b11111111, 0b00000000, 0b11111111];
@@ -379,7 +1168,7 @@ mod tests {
     fn test_xor_empty() {
         let a = vec![];
         let b = vec![];
-        let x = vec![];
+        let x: Vec<u8> = vec![];
         assert_eq!(xor(&a, &b), x);
     }
 }
@@ -10,29 +10,354 @@
 //! ## Example
 //!
 //! For a working example, refer to [examples/example1.rs](../examples/example1.rs).
+//!
+//! ## Features
+//!
+//! - `rfc9380`: Hash to G1 following RFC 9380, as used by quicknet. Enabled by default.
+//! - `serde`: Implement `Serialize` and `Deserialize` for [`ibe::Ciphertext`], [`ibe::GAffine`], [`Round`], and [`ChainHash`]. Enabled by default.
+//! - `tracing`: Emit tracing spans around encryption and decryption, and log the drand scheme ciphertexts are compatible with. Encrypting with the legacy G1 hashing domain is a warning. Enabled by default.
+//! - `debug-hex`: Enable `debug_hex`, printing ciphertext components as hex to debug interoperability.
+//! - `http`: Enable `encrypt_with_client`, fetching the public key from a [drand_core](https://github.com/thibmeu/drand-rs) client, `encrypt_future_with_client`, which also rejects rounds already emitted, and `fetch_signatures`.
+//! - `js`: Expose `encrypt` and `decrypt` to JavaScript through [wasm-bindgen](https://github.com/rustwasm/wasm-bindgen).
+//! - `test-util`: Enable the `mock` module, a deterministic drand chain to write tests without network access, and `ibe::encrypt_with_r` to reproduce test vectors.
+//!
+//! ## Low-level API
+//!
+//! The [`ibe`] module exposes the underlying identity based encryption, for custom pipelines such as batch verification.
+//! Most users should rely on the top-level functions instead.
 
-mod ibe;
+pub mod ibe;
+#[cfg(feature = "js")]
+mod lib_js;
+#[cfg(feature = "test-util")]
+pub mod mock;
+mod target;
 
 use crate::ibe::Ciphertext;
+pub use crate::ibe::IBEError;
+pub use crate::target::{max_round, parse_target, TargetTime};
 
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
 use ibe::GAffine;
+use rand::Rng;
 use sha2::Digest;
-use std::io;
+#[cfg(feature = "http")]
+use std::collections::HashMap;
+use std::{fmt, io, str::FromStr, time::Duration};
 use thiserror::Error;
+#[cfg(feature = "tracing")]
 use tracing::info_span;
 
 #[derive(Error, Debug)]
 pub enum TLockError {
+    #[error("buffer too small, expected at least {expected} bytes, got {actual}")]
+    BufferSize { expected: usize, actual: usize },
+    #[error("cannot decode ciphertext: {0}")]
+    Decoding(String),
+    #[error("signature is hex encoded, decode it to {} bytes first", .0 / 2)]
+    HexSignature(usize),
     #[error(transparent)]
     IBE(#[from] crate::ibe::IBEError),
+    #[error("invalid chain hash {0}, expected 32 bytes hex encoded")]
+    InvalidChainHash(String),
+    #[error("signature is not valid for round {0}")]
+    InvalidSignature(Round),
+    #[error("invalid target {0}, expected a round, a duration, or an RFC 3339 date")]
+    InvalidTarget(String),
     #[error(transparent)]
     IOError(#[from] io::Error),
+    #[error("{count} partial signatures provided, {threshold} are required")]
+    NotEnoughPartials { count: usize, threshold: usize },
+    #[error("round {round} is already emitted, latest round is {latest}. the message could be decrypted immediately")]
+    PastRound { round: Round, latest: Round },
+    #[error("invalid signature size: expected 48 (G1) or 96 (G2) bytes, got {0}")]
+    SignatureSize(usize),
+    #[error("truncated ciphertext: expected {expected} bytes for group {group}, got {actual}")]
+    TruncatedCiphertext {
+        expected: usize,
+        actual: usize,
+        group: Group,
+    },
+    #[error("scheme {0} is not supported, supported schemes are {SUPPORTED_SCHEMES:?}")]
+    UnsupportedScheme(String),
+}
+
+/// drand beacon round number.
+///
+/// Distinguishes rounds from other numbers such as Unix timestamps or durations.
+/// Public APIs accept `impl Into<Round>`, bare `u64` can still be used.
+///
+/// drand rounds start at 1, emitted at the chain genesis time. Round `n` is emitted `(n - 1) * period` after genesis.
+/// Round 0 is never emitted, a message encrypted to it cannot be decrypted.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+pub struct Round(u64);
+
+impl Round {
+    /// Largest representable round. Round computations saturate to it rather than wrapping.
+    pub const MAX: Self = Self(u64::MAX);
+
+    pub const fn new(round: u64) -> Self {
+        Self(round)
+    }
+
+    /// Round number.
+    pub const fn value(&self) -> u64 {
+        self.0
+    }
+
+    /// Round emitted `duration` after this one, on a chain producing a beacon every `period`.
+    ///
+    /// The target is computed from the period alone, without reading the local clock.
+    /// Passing the latest round fetched from the network makes drand the trusted time source, which protects against local clock skew.
+    /// This differs from durations parsed by `age-plugin-tlock`, which are resolved against the local system time and the chain genesis.
+    /// Partial periods are rounded up, so the returned round is never emitted earlier than `duration`.
+    /// The result saturates at [`Round::MAX`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `period` is zero.
+    ///
+    /// ```rust
+    /// use std::time::Duration;
+    /// use tlock::Round;
+    ///
+    /// // latest quicknet round, as returned by https://api.drand.sh/52db9ba70e0cc0f6eaf7803dd07447a1f5477735fd3f661792ba94600c84e971/public/latest
+    /// let latest = Round::new(1000);
+    /// let round = latest.after(Duration::from_secs(3600), Duration::from_secs(3));
+    /// assert_eq!(round, Round::new(2200));
+    /// ```
+    pub fn after(self, duration: Duration, period: Duration) -> Self {
+        let rounds = duration.as_nanos().div_ceil(period.as_nanos());
+        Self(
+            self.0
+                .saturating_add(u64::try_from(rounds).unwrap_or(u64::MAX)),
+        )
+    }
+
+    /// Check this round has not been emitted yet, given the `latest` round of the chain.
+    ///
+    /// A message encrypted to an emitted round can be decrypted immediately, as its signature is public.
+    /// Encrypting does not perform this check, so that past rounds remain usable for testing. Call it on the target round to opt in.
+    ///
+    /// ```rust
+    /// use tlock::Round;
+    ///
+    /// let latest = Round::new(1000);
+    /// assert_eq!(Round::new(1001).check_future(latest).unwrap(), Round::new(1001));
+    /// assert!(Round::new(1000).check_future(latest).is_err());
+    /// ```
+    pub fn check_future(self, latest: impl Into<Round>) -> Result<Self, TLockError> {
+        let latest = latest.into();
+        if self <= latest {
+            return Err(TLockError::PastRound {
+                round: self,
+                latest,
+            });
+        }
+        Ok(self)
+    }
+}
+
+impl From<u64> for Round {
+    fn from(round: u64) -> Self {
+        Self(round)
+    }
+}
+
+impl From<Round> for u64 {
+    fn from(round: Round) -> Self {
+        round.0
+    }
+}
+
+impl fmt::Display for Round {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// Hash of a drand chain, identifying it.
+///
+/// It is displayed and parsed hex encoded, as in drand URLs and tlock stanzas.
+///
+/// ```rust
+/// use tlock::ChainHash;
+///
+/// let chain_hash: ChainHash = "52db9ba70e0cc0f6eaf7803dd07447a1f5477735fd3f661792ba94600c84e971".parse().unwrap();
+/// assert_eq!(chain_hash.to_string(), "52db9ba70e0cc0f6eaf7803dd07447a1f5477735fd3f661792ba94600c84e971");
+/// assert!("52db9b".parse::<ChainHash>().is_err());
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ChainHash([u8; ChainHash::LEN]);
+
+impl ChainHash {
+    /// Length of a chain hash, in bytes.
+    pub const LEN: usize = 32;
+
+    pub const fn new(hash: [u8; Self::LEN]) -> Self {
+        Self(hash)
+    }
+
+    pub const fn as_bytes(&self) -> &[u8; Self::LEN] {
+        &self.0
+    }
+}
+
+impl From<[u8; ChainHash::LEN]> for ChainHash {
+    fn from(hash: [u8; ChainHash::LEN]) -> Self {
+        Self(hash)
+    }
+}
+
+impl From<&[u8; ChainHash::LEN]> for ChainHash {
+    fn from(hash: &[u8; ChainHash::LEN]) -> Self {
+        Self(*hash)
+    }
+}
+
+impl TryFrom<&[u8]> for ChainHash {
+    type Error = TLockError;
+
+    fn try_from(hash: &[u8]) -> Result<Self, Self::Error> {
+        hash.try_into()
+            .map(Self)
+            .map_err(|_| TLockError::InvalidChainHash(hex::encode(hash)))
+    }
+}
+
+impl FromStr for ChainHash {
+    type Err = TLockError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut hash = [0u8; Self::LEN];
+        hex::decode_to_slice(s, &mut hash)
+            .map_err(|_| TLockError::InvalidChainHash(s.to_owned()))?;
+        Ok(Self(hash))
+    }
+}
+
+impl AsRef<[u8]> for ChainHash {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl fmt::Display for ChainHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&hex::encode(self.0))
+    }
+}
+
+/// Serialized hex encoded, as displayed.
+#[cfg(feature = "serde")]
+impl serde::Serialize for ChainHash {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+/// Deserialized from a hex string, failing as [`ChainHash::from_str`] does.
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for ChainHash {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let hash = <String as serde::Deserialize>::deserialize(deserializer)?;
+        hash.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// drand scheme ids this library can decrypt, given the active `rfc9380` feature.
+///
+/// Scheme id is provided by drand `/info` endpoint as `schemeID`.
+#[cfg(feature = "rfc9380")]
+pub const SUPPORTED_SCHEMES: &[&str] = &["pedersen-bls-unchained", "bls-unchained-g1-rfc9380"];
+#[cfg(not(feature = "rfc9380"))]
+pub const SUPPORTED_SCHEMES: &[&str] = &["pedersen-bls-unchained", "bls-unchained-on-g1"];
+
+/// Cryptographic parameters of the library, as compiled. Returned by [`crypto_params`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CryptoParams {
+    /// Pairing-friendly curve, on which public keys and signatures are points.
+    pub curve: &'static str,
+    /// Order of the prime subgroup of G1 and G2, which is the scalar field modulus, as big-endian hex.
+    pub subgroup_order: &'static str,
+    /// Hash function used for hashing to curve, and for IBE-H2, IBE-H3, and IBE-H4.
+    pub hash: &'static str,
+    /// Hash to curve suite used to map round identities to signature points.
+    pub hash_to_curve: &'static str,
+    /// Domain separation tag hashing to G1, depending on the `rfc9380` feature.
+    pub g1_domain: &'static [u8],
+    /// Domain separation tag hashing to G2.
+    pub g2_domain: &'static [u8],
+    /// Message expansion deriving the IBE scalar r, see [`ibe::ExpandMsgDrand`].
+    pub expand_message: &'static str,
+    /// Size of compressed G1 points, in bytes.
+    pub g1_size: usize,
+    /// Size of compressed G2 points, in bytes.
+    pub g2_size: usize,
+    /// Size of encrypted messages, sigma, V, and W, in bytes.
+    pub message_size: usize,
+    /// drand schemes the library can decrypt, as [`SUPPORTED_SCHEMES`].
+    pub supported_schemes: &'static [&'static str],
+}
+
+/// Cryptographic parameters of the library, as compiled.
+///
+/// This gives auditors a single place to check the curve, hashes, and sizes in use at runtime. Values come from the constants [`ibe`] uses.
+///
+/// ```rust
+/// let params = tlock::crypto_params();
+/// assert_eq!(params.curve, "BLS12-381");
+/// assert_eq!(params.g2_domain, b"BLS_SIG_BLS12381G2_XMD:SHA-256_SSWU_RO_NUL_");
+/// ```
+pub const fn crypto_params() -> CryptoParams {
+    CryptoParams {
+        curve: "BLS12-381",
+        subgroup_order: "73eda753299d7d483339d80809a1d80553bda402fffe5bfeffffffff00000001",
+        hash: "SHA-256",
+        hash_to_curve: "expand_message_xmd with SHA-256, simplified SWU, random oracle (RFC 9380)",
+        g1_domain: ibe::G1_DOMAIN,
+        g2_domain: ibe::G2_DOMAIN,
+        expand_message: "expand_message_drand with SHA-256",
+        g1_size: ibe::G1_SIZE,
+        g2_size: ibe::G2_SIZE,
+        message_size: ibe::MESSAGE_SIZE,
+        supported_schemes: SUPPORTED_SCHEMES,
+    }
+}
+
+/// Verify the library can decrypt messages for a drand scheme.
+///
+/// G1 hashing domain is set at compile time with the `rfc9380` feature. Chains using a different domain, or chained schemes, cannot be used for timelock encryption.
+///
+/// ```rust
+/// tlock::verify_scheme("pedersen-bls-unchained").unwrap();
+/// // chained beacons depend on the previous signature, and cannot be used for timelock encryption
+/// assert!(tlock::verify_scheme("pedersen-bls-chained").is_err());
+/// ```
+pub fn verify_scheme(scheme_id: &str) -> Result<(), TLockError> {
+    if SUPPORTED_SCHEMES.contains(&scheme_id) {
+        Ok(())
+    } else {
+        Err(TLockError::UnsupportedScheme(scheme_id.to_owned()))
+    }
 }
 
 /// Encrypt 16 bytes using tlock encryption scheme.
 ///
 /// tlock relies on BLS, content is encrypted against BLS public key.
 /// Public key group is assessed based on the public key size.
+/// Shorter inputs are padded with zeros, longer ones fail with [`IBEError::MessageSize`].
 ///
 /// Example using an empty 16-byte message, fastnet public key, at round 1000
 ///
@@ -49,13 +374,78 @@ pub fn encrypt<W: io::Write, R: io::Read>(
     mut dst: W,
     mut src: R,
     public_key_bytes: &[u8],
-    round_number: u64,
+    round_number: impl Into<Round>,
 ) -> anyhow::Result<()> {
-    let mut message = [0; 16];
-    src.read(&mut message).map_err(TLockError::IOError)?;
+    let message = read_message(&mut src)?;
 
+    #[cfg(feature = "tracing")]
     let ct = info_span!("ibe::encryption")
-        .in_scope(|| time_lock(public_key_bytes, round_number, message))?;
+        .in_scope(|| time_lock(public_key_bytes, round_number.into(), message))?;
+    #[cfg(not(feature = "tracing"))]
+    let ct = time_lock(public_key_bytes, round_number.into(), message)?;
+
+    dst.write_all(&ct.u.to_compressed()?)?;
+    dst.write_all(&ct.v)?;
+    dst.write_all(&ct.w)?;
+
+    Ok(())
+}
+
+/// Generate a random 16-byte key, and encrypt it using tlock encryption scheme.
+///
+/// The key is returned to the caller, who can use it with the symmetric cipher of their choice.
+/// Anyone holding the ciphertext written to `dst` recovers the key once the round signature is published.
+/// For a complete example using an AEAD, refer to [examples/symmetric_key.rs](../examples/symmetric_key.rs).
+///
+/// ```rust
+/// let pk_bytes = hex::decode("a0b862a7527fee3a731bcb59280ab6abd62d5c0b6ea03dc4ddf6612fdfc9d01f01c31542541771903475eb1ec6615f8d0df0b8b6dce385811d6dcf8cbefb8759e5e616a3dfd054c928940766d9a5b9db91e3b697e5d70a975181e007f87fca5e").unwrap();
+///
+/// let mut encrypted = vec![];
+/// let key = tlock::encrypt_random_key(&mut encrypted, &pk_bytes, 1000).unwrap();
+/// ```
+pub fn encrypt_random_key<W: io::Write>(
+    dst: W,
+    public_key_bytes: &[u8],
+    round_number: impl Into<Round>,
+) -> anyhow::Result<[u8; ibe::MESSAGE_SIZE]> {
+    let mut key = [0u8; ibe::MESSAGE_SIZE];
+    rand::thread_rng().fill(&mut key);
+    encrypt(dst, key.as_slice(), public_key_bytes, round_number)?;
+    Ok(key)
+}
+
+/// Encrypt 16 bytes using tlock encryption scheme, deterministically.
+///
+/// Encrypting the same message, for the same public key and round, with the same `secret` yields the same ciphertext.
+/// This suits content-addressable stores deduplicating ciphertexts. Refer to [`ibe::encrypt_deterministic`] for how randomness is derived.
+///
+/// Security is weaker than [`encrypt`]: identical messages can be linked by anyone seeing the ciphertexts.
+/// `secret` MUST be at least 16 uniformly random bytes, and kept private. Otherwise, messages can be brute forced before the round is reached.
+///
+/// ```rust
+/// let pk_bytes = hex::decode("a0b862a7527fee3a731bcb59280ab6abd62d5c0b6ea03dc4ddf6612fdfc9d01f01c31542541771903475eb1ec6615f8d0df0b8b6dce385811d6dcf8cbefb8759e5e616a3dfd054c928940766d9a5b9db91e3b697e5d70a975181e007f87fca5e").unwrap();
+/// let secret = [42u8; 32];
+///
+/// let mut encrypted = vec![];
+/// tlock::encrypt_deterministic(&mut encrypted, [8u8; 16].as_slice(), &pk_bytes, 1000, &secret).unwrap();
+/// let mut again = vec![];
+/// tlock::encrypt_deterministic(&mut again, [8u8; 16].as_slice(), &pk_bytes, 1000, &secret).unwrap();
+/// assert_eq!(encrypted, again);
+/// ```
+pub fn encrypt_deterministic<W: io::Write, R: io::Read>(
+    mut dst: W,
+    mut src: R,
+    public_key_bytes: &[u8],
+    round_number: impl Into<Round>,
+    secret: &[u8],
+) -> anyhow::Result<()> {
+    let message = read_message(&mut src)?;
+
+    let public_key = GAffine::try_from(public_key_bytes)?;
+    #[cfg(feature = "tracing")]
+    trace_scheme(&public_key);
+    let ct =
+        ibe::encrypt_deterministic(public_key, round_id(round_number.into()), message, secret)?;
 
     dst.write_all(&ct.u.to_compressed()?)?;
     dst.write_all(&ct.v)?;
@@ -64,10 +454,207 @@ pub fn encrypt<W: io::Write, R: io::Read>(
     Ok(())
 }
 
+/// Encrypt 16 bytes using tlock encryption scheme, into a caller provided buffer.
+///
+/// Ciphertext is written at the start of `dst`, and the number of bytes written is returned.
+/// `dst` has to be at least [`ciphertext_len`] bytes long.
+///
+/// ```rust
+/// let pk_bytes = hex::decode("a0b862a7527fee3a731bcb59280ab6abd62d5c0b6ea03dc4ddf6612fdfc9d01f01c31542541771903475eb1ec6615f8d0df0b8b6dce385811d6dcf8cbefb8759e5e616a3dfd054c928940766d9a5b9db91e3b697e5d70a975181e007f87fca5e").unwrap();
+/// let src = vec![0u8; 16];
+///
+/// let mut encrypted = [0u8; 128];
+/// let n = tlock::encrypt_into(&mut encrypted, src.as_slice(), &pk_bytes, 1000).unwrap();
+/// assert_eq!(n, tlock::ciphertext_len(&pk_bytes).unwrap());
+/// ```
+pub fn encrypt_into<R: io::Read>(
+    dst: &mut [u8],
+    src: R,
+    public_key_bytes: &[u8],
+    round_number: impl Into<Round>,
+) -> anyhow::Result<usize> {
+    let len = ciphertext_len(public_key_bytes)?;
+    if dst.len() < len {
+        return Err(TLockError::BufferSize {
+            expected: len,
+            actual: dst.len(),
+        }
+        .into());
+    }
+
+    encrypt(&mut dst[..len], src, public_key_bytes, round_number)?;
+    Ok(len)
+}
+
+/// Encrypt for a round of the chain `client` is connected to.
+///
+/// Fetches the chain public key, then behaves as [`encrypt`]. Chain information does not change, callers encrypting many messages should fetch it once and use [`encrypt`] instead.
+///
+/// Requires the `http` feature.
+///
+/// ```rust,no_run
+/// use drand_core::HttpClient;
+///
+/// let client: HttpClient = "https://api.drand.sh/dbd506d6ef76e5f386f41c651dcb808c5bcbd75471cc4eafa3f4df7ad4e4c493".try_into().unwrap();
+///
+/// let mut encrypted = vec![];
+/// tlock::encrypt_with_client(&mut encrypted, [8u8; 16].as_slice(), &client, 1000).unwrap();
+/// ```
+#[cfg(feature = "http")]
+pub fn encrypt_with_client<W: io::Write, R: io::Read>(
+    dst: W,
+    src: R,
+    client: &drand_core::HttpClient,
+    round_number: impl Into<Round>,
+) -> anyhow::Result<()> {
+    let info = client.chain_info()?;
+    encrypt(dst, src, &info.public_key(), round_number)
+}
+
+/// Encrypt for a future round of the chain `client` is connected to.
+///
+/// Behaves as [`encrypt_with_client`], and fails with [`TLockError::PastRound`] if `round_number` is already emitted, as checked by [`Round::check_future`] against the latest beacon.
+/// This protects against miscomputed rounds, which would make the message decryptable immediately.
+///
+/// Requires the `http` feature.
+///
+/// ```rust,no_run
+/// use drand_core::HttpClient;
+///
+/// let client: HttpClient = "https://api.drand.sh/dbd506d6ef76e5f386f41c651dcb808c5bcbd75471cc4eafa3f4df7ad4e4c493".try_into().unwrap();
+///
+/// let mut encrypted = vec![];
+/// // round 1000 was emitted long ago
+/// assert!(tlock::encrypt_future_with_client(&mut encrypted, [8u8; 16].as_slice(), &client, 1000).is_err());
+/// ```
+#[cfg(feature = "http")]
+pub fn encrypt_future_with_client<W: io::Write, R: io::Read>(
+    dst: W,
+    src: R,
+    client: &drand_core::HttpClient,
+    round_number: impl Into<Round>,
+) -> anyhow::Result<()> {
+    let latest = client.latest()?.round();
+    let round = round_number.into().check_future(latest)?;
+    encrypt_with_client(dst, src, client, round)
+}
+
+/// Fetch the signatures of `rounds` from the chain `client` is connected to.
+///
+/// Rounds are deduplicated, and each beacon is fetched once. The latest beacon is fetched first: rounds after it, and round 0, are not emitted yet and are left out of the returned map.
+/// This allows services decrypting many ciphertexts to fetch all their signatures upfront, and retry the missing ones later.
+///
+/// Requires the `http` feature.
+///
+/// ```rust,no_run
+/// use drand_core::HttpClient;
+///
+/// let client: HttpClient = "https://api.drand.sh/dbd506d6ef76e5f386f41c651dcb808c5bcbd75471cc4eafa3f4df7ad4e4c493".try_into().unwrap();
+///
+/// let signatures = tlock::fetch_signatures(&client, &[1000.into(), 2000.into(), 1000.into()]).unwrap();
+/// let signature = &signatures[&1000.into()];
+/// ```
+#[cfg(feature = "http")]
+pub fn fetch_signatures(
+    client: &drand_core::HttpClient,
+    rounds: &[Round],
+) -> anyhow::Result<HashMap<Round, Vec<u8>>> {
+    let latest = client.latest()?.round();
+    fetch_signatures_with(rounds, latest.into(), |round| {
+        Ok(client.get(round.value())?.signature())
+    })
+}
+
+/// Fetch the signatures of emitted `rounds` with `get`, given the `latest` emitted round.
+#[cfg(feature = "http")]
+fn fetch_signatures_with(
+    rounds: &[Round],
+    latest: Round,
+    mut get: impl FnMut(Round) -> anyhow::Result<Vec<u8>>,
+) -> anyhow::Result<HashMap<Round, Vec<u8>>> {
+    let mut signatures = HashMap::new();
+    for &round in rounds {
+        if round.value() == 0 || round > latest || signatures.contains_key(&round) {
+            continue;
+        }
+        signatures.insert(round, get(round)?);
+    }
+    Ok(signatures)
+}
+
+/// Size of V and W, following U in a ciphertext.
+const VW_SIZE: usize = 2 * ibe::MESSAGE_SIZE;
+
+/// Length of a ciphertext encrypted against the given public key.
+///
+/// Ciphertext is composed of U, a point on the public key group, followed by 16-byte V and W.
+pub fn ciphertext_len(public_key_bytes: &[u8]) -> Result<usize, TLockError> {
+    match public_key_bytes.len() {
+        ibe::G1_SIZE => Ok(ibe::G1_SIZE + VW_SIZE),
+        ibe::G2_SIZE => Ok(ibe::G2_SIZE + VW_SIZE),
+        len => Err(IBEError::PublicKeySize(len).into()),
+    }
+}
+
+/// Length of a ciphertext decrypted with the given signature.
+///
+/// The signature and the public key are on different groups, U is on the public key group.
+pub fn ciphertext_len_for_signature(signature: &[u8]) -> Result<usize, TLockError> {
+    let (u_len, _) = signature_group(signature)?;
+    Ok(u_len + VW_SIZE)
+}
+
+/// Parse and validate a signature, to decrypt many ciphertexts for its round with [`decrypt_with_point`].
+///
+/// Signatures are 48 bytes on G1, or 96 bytes on G2. Hex encoded signatures are rejected with [`TLockError::HexSignature`].
+///
+/// ```rust
+/// let signature = hex::decode("b09eacd45767c4d58306b98901ad0d6086e2663766f3a4ec71d00cf26f0f49eaf248abc7151c60cf419c4e8b37e80412").unwrap();
+/// let point = tlock::parse_signature(&signature).unwrap();
+/// assert!(tlock::parse_signature(&signature[1..]).is_err());
+/// ```
+pub fn parse_signature(signature: &[u8]) -> Result<GAffine, TLockError> {
+    signature_group(signature)?;
+    Ok(GAffine::try_from(signature)?)
+}
+
+/// Size and group of U for ciphertexts decrypted with `signature`, which is on the other group.
+///
+/// Compressed points have their most significant bit set, so hex encoded signatures are told apart from 96-byte G2 signatures.
+fn signature_group(signature: &[u8]) -> Result<(usize, &'static str), TLockError> {
+    match signature.len() {
+        ibe::G1_SIZE => Ok((ibe::G2_SIZE, "G2")),
+        len if signature.iter().all(u8::is_ascii_hexdigit) && len > 0 => {
+            Err(TLockError::HexSignature(len))
+        }
+        ibe::G2_SIZE => Ok((ibe::G1_SIZE, "G1")),
+        len => Err(TLockError::SignatureSize(len)),
+    }
+}
+
+/// drand scheme id of chains with the given public key, given the active `rfc9380` feature.
+///
+/// Public keys on G1 are used by `pedersen-bls-unchained`. Public keys on G2 are used by `bls-unchained-g1-rfc9380`, or `bls-unchained-on-g1` without the `rfc9380` feature.
+///
+/// ```rust
+/// let pk_bytes = hex::decode("8200fc249deb0148eb918d6e213980c5d01acd7fc251900d9260136da3b54836ce125172399ddc69c4e3e11429b62c11").unwrap();
+/// assert_eq!(tlock::scheme_id(&pk_bytes).unwrap(), "pedersen-bls-unchained");
+/// ```
+pub fn scheme_id(public_key_bytes: &[u8]) -> Result<&'static str, TLockError> {
+    match public_key_bytes.len() {
+        ibe::G1_SIZE => Ok(SUPPORTED_SCHEMES[0]),
+        ibe::G2_SIZE => Ok(SUPPORTED_SCHEMES[1]),
+        len => Err(IBEError::PublicKeySize(len).into()),
+    }
+}
+
 /// Decrypt 16 bytes using tlock encryption scheme.
 ///
 /// tlock relies on BLS, content private key is a BLS signature.
-/// Signature group is assessed based on the public key size.
+/// Signature group is assessed based on its size: 48 bytes for G1, 96 bytes for G2. Signatures have to be decoded from hex first.
+///
+/// The group depends on the chain scheme, not on the drand API version, both `/public/{round}` and `/v2/beacons/{beacon}/rounds/{round}` return the same signature.
+/// `pedersen-bls-unchained` chains sign on G2, and `bls-unchained-g1-rfc9380` chains such as quicknet sign on G1.
 ///
 /// Example using an 16-byte message, fastnet public key, and round 1000
 ///
@@ -82,63 +669,375 @@ pub fn encrypt<W: io::Write, R: io::Read>(
 /// tlock::decrypt(decrypted, encrypted.as_slice(), &signature).unwrap();
 /// ```
 pub fn decrypt<W: io::Write, R: io::Read>(
+    dst: W,
+    src: R,
+    signature: &[u8],
+) -> anyhow::Result<(), TLockError> {
+    unlock_with(dst, src, &parse_signature(signature)?, time_unlock)
+}
+
+/// Decrypt 16 bytes using tlock encryption scheme, with a signature parsed by [`parse_signature`].
+///
+/// Behaves as [`decrypt`], without parsing the signature again. This is meant to decrypt many ciphertexts for the same round.
+///
+/// ```rust
+/// let signature = hex::decode("b09eacd45767c4d58306b98901ad0d6086e2663766f3a4ec71d00cf26f0f49eaf248abc7151c60cf419c4e8b37e80412").unwrap();
+/// let encrypted = hex::decode("9787b5ed1c3e36e84ce19064e975be835b81c0788d5aa2a49ab7edc98b2917f1d61ac21f196bdc693ed556194fb33da104ffafa3c036dbcfb55eb953aaf2d446871aad7a1266f531caac1d654247a2d8ee93b975a7a19f0286f44d3c646d76338f334f4450bddbb2db52daae55d9e20ec26503ea7855b165f713b4ea96e60376").unwrap();
+///
+/// let point = tlock::parse_signature(&signature).unwrap();
+/// let decrypted = vec![];
+/// tlock::decrypt_with_point(decrypted, encrypted.as_slice(), &point).unwrap();
+/// ```
+pub fn decrypt_with_point<W: io::Write, R: io::Read>(
+    dst: W,
+    src: R,
+    signature: &GAffine,
+) -> anyhow::Result<(), TLockError> {
+    unlock_with(dst, src, signature, time_unlock)
+}
+
+/// Decrypt 16 bytes using tlock encryption scheme, without verifying the ciphertext.
+///
+/// This skips the `U = G^r` consistency check performed by [`decrypt`], which costs a scalar multiplication.
+/// It MUST only be used on ciphertexts known to be authentic, as a signature for the wrong round decrypts to garbage instead of returning an error.
+///
+/// ```rust
+/// let signature = hex::decode("b09eacd45767c4d58306b98901ad0d6086e2663766f3a4ec71d00cf26f0f49eaf248abc7151c60cf419c4e8b37e80412").unwrap();
+/// let encrypted = hex::decode("9787b5ed1c3e36e84ce19064e975be835b81c0788d5aa2a49ab7edc98b2917f1d61ac21f196bdc693ed556194fb33da104ffafa3c036dbcfb55eb953aaf2d446871aad7a1266f531caac1d654247a2d8ee93b975a7a19f0286f44d3c646d76338f334f4450bddbb2db52daae55d9e20ec26503ea7855b165f713b4ea96e60376").unwrap();
+///
+/// let decrypted = vec![];
+/// tlock::decrypt_unchecked(decrypted, encrypted.as_slice(), &signature).unwrap();
+/// ```
+pub fn decrypt_unchecked<W: io::Write, R: io::Read>(
+    dst: W,
+    src: R,
+    signature: &[u8],
+) -> anyhow::Result<(), TLockError> {
+    unlock_with(
+        dst,
+        src,
+        &parse_signature(signature)?,
+        time_unlock_unchecked,
+    )
+}
+
+/// BLS12-381 group a point is on. Displayed as `G1` or `G2`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Group {
+    G1,
+    G2,
+}
+
+impl Group {
+    /// The other group of the pairing.
+    pub const fn other(self) -> Self {
+        match self {
+            Self::G1 => Self::G2,
+            Self::G2 => Self::G1,
+        }
+    }
+}
+
+impl From<&GAffine> for Group {
+    fn from(point: &GAffine) -> Self {
+        match point {
+            GAffine::G1Affine(_) => Self::G1,
+            GAffine::G2Affine(_) => Self::G2,
+        }
+    }
+}
+
+impl fmt::Display for Group {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::G1 => f.write_str("G1"),
+            Self::G2 => f.write_str("G2"),
+        }
+    }
+}
+
+/// Groups involved in a decryption, returned by [`decrypt_with_meta`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DecryptMeta {
+    /// Group of the ciphertext `U` point, which is also the group of the chain public key.
+    pub group: Group,
+    /// Group of the beacon signature, the other one.
+    pub signature_group: Group,
+}
+
+/// Decrypt 16 bytes using tlock encryption scheme, and return the groups the ciphertext and signature are on.
+///
+/// Behaves as [`decrypt`]. The groups allow to log and assert the cryptographic context of each decryption.
+///
+/// ```rust
+/// let signature = hex::decode("b09eacd45767c4d58306b98901ad0d6086e2663766f3a4ec71d00cf26f0f49eaf248abc7151c60cf419c4e8b37e80412").unwrap();
+/// let encrypted = hex::decode("9787b5ed1c3e36e84ce19064e975be835b81c0788d5aa2a49ab7edc98b2917f1d61ac21f196bdc693ed556194fb33da104ffafa3c036dbcfb55eb953aaf2d446871aad7a1266f531caac1d654247a2d8ee93b975a7a19f0286f44d3c646d76338f334f4450bddbb2db52daae55d9e20ec26503ea7855b165f713b4ea96e60376").unwrap();
+///
+/// let (decrypted, meta) = tlock::decrypt_with_meta(encrypted.as_slice(), &signature).unwrap();
+/// assert_eq!((meta.group, meta.signature_group), (tlock::Group::G2, tlock::Group::G1));
+/// assert_eq!(meta.group.to_string(), "G2");
+/// ```
+pub fn decrypt_with_meta<R: io::Read>(
+    src: R,
+    signature: &[u8],
+) -> Result<(Vec<u8>, DecryptMeta), TLockError> {
+    let point = parse_signature(signature)?;
+    let signature_group = Group::from(&point);
+
+    let mut plaintext = vec![];
+    unlock_with(&mut plaintext, src, &point, time_unlock)?;
+    Ok((
+        plaintext,
+        DecryptMeta {
+            group: signature_group.other(),
+            signature_group,
+        },
+    ))
+}
+
+/// Encrypt 16 bytes using tlock encryption scheme, and encode the ciphertext as base64.
+///
+/// ```rust
+/// let pk_bytes = hex::decode("a0b862a7527fee3a731bcb59280ab6abd62d5c0b6ea03dc4ddf6612fdfc9d01f01c31542541771903475eb1ec6615f8d0df0b8b6dce385811d6dcf8cbefb8759e5e616a3dfd054c928940766d9a5b9db91e3b697e5d70a975181e007f87fca5e").unwrap();
+/// let src = vec![0u8; 16];
+///
+/// let encrypted = tlock::encrypt_base64(src.as_slice(), &pk_bytes, 1000).unwrap();
+/// ```
+pub fn encrypt_base64<R: io::Read>(
+    src: R,
+    public_key_bytes: &[u8],
+    round_number: impl Into<Round>,
+) -> anyhow::Result<String> {
+    let mut encrypted = vec![];
+    encrypt(&mut encrypted, src, public_key_bytes, round_number)?;
+    Ok(BASE64.encode(encrypted))
+}
+
+/// Decrypt a base64 encoded ciphertext using tlock encryption scheme.
+pub fn decrypt_base64<W: io::Write>(
+    dst: W,
+    src: &str,
+    signature: &[u8],
+) -> anyhow::Result<(), TLockError> {
+    let encrypted = BASE64
+        .decode(src)
+        .map_err(|e| TLockError::Decoding(e.to_string()))?;
+    decrypt(dst, encrypted.as_slice(), signature)
+}
+
+/// Encrypt 16 bytes using tlock encryption scheme, and encode the ciphertext as hex.
+///
+/// ```rust
+/// let pk_bytes = hex::decode("a0b862a7527fee3a731bcb59280ab6abd62d5c0b6ea03dc4ddf6612fdfc9d01f01c31542541771903475eb1ec6615f8d0df0b8b6dce385811d6dcf8cbefb8759e5e616a3dfd054c928940766d9a5b9db91e3b697e5d70a975181e007f87fca5e").unwrap();
+/// let src = vec![0u8; 16];
+///
+/// let encrypted = tlock::encrypt_hex(src.as_slice(), &pk_bytes, 1000).unwrap();
+/// ```
+pub fn encrypt_hex<R: io::Read>(
+    src: R,
+    public_key_bytes: &[u8],
+    round_number: impl Into<Round>,
+) -> anyhow::Result<String> {
+    let mut encrypted = vec![];
+    encrypt(&mut encrypted, src, public_key_bytes, round_number)?;
+    Ok(hex::encode(encrypted))
+}
+
+/// Decrypt a hex encoded ciphertext using tlock encryption scheme.
+///
+/// ```rust
+/// let signature = hex::decode("b09eacd45767c4d58306b98901ad0d6086e2663766f3a4ec71d00cf26f0f49eaf248abc7151c60cf419c4e8b37e80412").unwrap();
+/// let encrypted = "9787b5ed1c3e36e84ce19064e975be835b81c0788d5aa2a49ab7edc98b2917f1d61ac21f196bdc693ed556194fb33da104ffafa3c036dbcfb55eb953aaf2d446871aad7a1266f531caac1d654247a2d8ee93b975a7a19f0286f44d3c646d76338f334f4450bddbb2db52daae55d9e20ec26503ea7855b165f713b4ea96e60376";
+///
+/// let decrypted = vec![];
+/// tlock::decrypt_hex(decrypted, encrypted, &signature).unwrap();
+/// ```
+pub fn decrypt_hex<W: io::Write>(
+    dst: W,
+    src: &str,
+    signature: &[u8],
+) -> anyhow::Result<(), TLockError> {
+    let encrypted = hex::decode(src).map_err(|e| TLockError::Decoding(e.to_string()))?;
+    decrypt(dst, encrypted.as_slice(), signature)
+}
+
+fn unlock_with<W: io::Write, R: io::Read>(
     mut dst: W,
     mut src: R,
-    signature: &[u8],
+    signature: &GAffine,
+    unlock: fn(&GAffine, &Ciphertext) -> Result<Vec<u8>, TLockError>,
 ) -> anyhow::Result<(), TLockError> {
     let c = {
-        let u = if signature.len() == ibe::G1_SIZE {
-            let mut u = [0u8; ibe::G2_SIZE];
-            src.read_exact(&mut u).map_err(TLockError::IOError)?;
-            u.to_vec()
-        } else {
-            let mut u = [0u8; ibe::G1_SIZE];
-            src.read_exact(&mut u).map_err(TLockError::IOError)?;
-            u.to_vec()
+        let group = Group::from(signature).other();
+        let u_len = match group {
+            Group::G1 => ibe::G1_SIZE,
+            Group::G2 => ibe::G2_SIZE,
         };
-        let mut v = [0u8; 16];
-        src.read_exact(&mut v).map_err(TLockError::IOError)?;
-        let v = [[0u8; 16], v].concat().to_vec();
-        let mut w = [0u8; 16];
-        src.read_exact(&mut w).map_err(TLockError::IOError)?;
-        let w = [[0u8; 16], w].concat().to_vec();
-        Ciphertext {
-            u: u.as_slice().try_into()?,
-            v,
-            w,
+        let mut buf = vec![0u8; u_len + VW_SIZE];
+        let read = read_full(&mut src, &mut buf)?;
+        if read < buf.len() {
+            return Err(TLockError::TruncatedCiphertext {
+                expected: buf.len(),
+                actual: read,
+                group,
+            });
         }
+        let (u, vw) = buf.split_at(u_len);
+        let (v, w) = vw.split_at(ibe::MESSAGE_SIZE);
+        Ciphertext::from_parts(u, v, w)?
     };
 
-    let mut pt = time_unlock(signature, &c)?;
+    #[cfg(feature = "tracing")]
+    let pt = info_span!("ibe::decryption").in_scope(|| unlock(signature, &c))?;
+    #[cfg(not(feature = "tracing"))]
+    let pt = unlock(signature, &c)?;
 
-    //note(thibault): I'm not sure why this condition was choosen, but this does not work as expected
-    // it stems to time_unlock always decrypting to 32 bytes
-    // thing is, sometimes, data to be encrypted ends with 0
-    // the following lines destroy this data
-    if let Some(i) = pt.iter().rposition(|x| *x != 0) {
-        pt.truncate(i + 1);
+    dst.write_all(&pt).map_err(TLockError::IOError)
+}
+
+/// Labelled hex dump of the components of a ciphertext produced by [`encrypt`].
+///
+/// The public key group is inferred from the ciphertext length.
+///
+/// ```rust
+/// let pk_bytes = hex::decode("8200fc249deb0148eb918d6e213980c5d01acd7fc251900d9260136da3b54836ce125172399ddc69c4e3e11429b62c11").unwrap();
+/// let mut encrypted = vec![];
+/// tlock::encrypt(&mut encrypted, [0u8; 16].as_slice(), &pk_bytes, 1000).unwrap();
+///
+/// println!("{}", tlock::debug_hex(&encrypted).unwrap());
+/// ```
+#[cfg(feature = "debug-hex")]
+pub fn debug_hex(ciphertext: &[u8]) -> Result<String, TLockError> {
+    let u_len = match ciphertext.len() {
+        len if len == ibe::G1_SIZE + VW_SIZE => ibe::G1_SIZE,
+        len if len == ibe::G2_SIZE + VW_SIZE => ibe::G2_SIZE,
+        len => return Err(IBEError::PublicKeySize(len.saturating_sub(VW_SIZE)).into()),
+    };
+    let (u, vw) = ciphertext.split_at(u_len);
+    let (v, w) = vw.split_at(ibe::MESSAGE_SIZE);
+    let c = Ciphertext::from_parts(u, v, w)?;
+    Ok(c.debug_hex())
+}
+
+/// Read from `src` until `buf` is full or the end of input, returning the number of bytes read.
+fn read_full<R: io::Read>(mut src: R, buf: &mut [u8]) -> Result<usize, TLockError> {
+    let mut read = 0;
+    while read < buf.len() {
+        match src.read(&mut buf[read..]) {
+            Ok(0) => break,
+            Ok(n) => read += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(TLockError::IOError(e)),
+        }
     }
+    Ok(read)
+}
 
-    dst.write_all(&pt).map_err(TLockError::IOError)
+/// Read a message of at most [`ibe::MESSAGE_SIZE`] bytes from `src`, padded with zeros.
+fn read_message<R: io::Read>(src: R) -> Result<[u8; ibe::MESSAGE_SIZE], TLockError> {
+    // one extra byte tells a message of exactly MESSAGE_SIZE bytes from a longer one
+    let mut buf = [0u8; ibe::MESSAGE_SIZE + 1];
+    if read_full(src, &mut buf)? > ibe::MESSAGE_SIZE {
+        return Err(IBEError::MessageSize.into());
+    }
+    let mut message = [0u8; ibe::MESSAGE_SIZE];
+    message.copy_from_slice(&buf[..ibe::MESSAGE_SIZE]);
+    Ok(message)
+}
+
+/// Verify a drand beacon signature for a round, against the chain public key.
+///
+/// This is a BLS signature verification, independent of any ciphertext. It checks `e(signature, G) == e(H(round), public_key)`, with G the generator of the public key group.
+///
+/// ```rust
+/// // curl -sS https://api.drand.sh/52db9ba70e0cc0f6eaf7803dd07447a1f5477735fd3f661792ba94600c84e971/info | jq -r '.public_key'
+/// let pk_bytes = hex::decode("83cf0f2896adee7eb8b5f01fcad3912212c437e0073e911fb90022d3e760183c8c4b450b6a0a6c3ac6a5776a2d1064510d1fec758c921cc22b0e17e63aaf4bcb5ed66304de9cf809bd274ca73bab4af5a6e9c76a4bc09e76eae8991ef5ece45a").unwrap();
+/// // curl -sS https://api.drand.sh/52db9ba70e0cc0f6eaf7803dd07447a1f5477735fd3f661792ba94600c84e971/public/1000 | jq -r '.signature'
+/// let signature = hex::decode("b44679b9a59af2ec876b1a6b1ad52ea9b1615fc3982b19576350f93447cb1125e342b73a8dd2bacbe47e4b6b63ed5e39").unwrap();
+///
+/// # #[cfg(feature = "rfc9380")]
+/// tlock::verify_beacon(&pk_bytes, 1000, &signature).unwrap();
+/// ```
+pub fn verify_beacon(
+    public_key_bytes: &[u8],
+    round_number: impl Into<Round>,
+    signature: &[u8],
+) -> anyhow::Result<()> {
+    let round_number = round_number.into();
+    let public_key = GAffine::try_from(public_key_bytes)?;
+    let signature = GAffine::try_from(signature)?;
+
+    let expected = public_key.projective_pairing(&round_id(round_number))?;
+    if signature.pairing(&public_key.generator())? != expected {
+        return Err(TLockError::InvalidSignature(round_number).into());
+    }
+    Ok(())
+}
+
+/// Recover the group signature of a round from the partial signatures of a threshold network.
+///
+/// `partials` pairs the share index of each node, starting at 0, with its partial signature for the round, encoded like a beacon signature.
+/// The first `threshold` partials are interpolated, the rest are ignored. The recovered signature is compressed, and can be passed to [`decrypt`].
+/// Partials are not verified. Check the result with [`verify_beacon`] against the group public key, an invalid partial yields an invalid signature.
+pub fn recover_signature(
+    partials: &[(u32, &[u8])],
+    threshold: usize,
+) -> Result<Vec<u8>, TLockError> {
+    if partials.len() < threshold {
+        return Err(TLockError::NotEnoughPartials {
+            count: partials.len(),
+            threshold,
+        });
+    }
+    let partials = partials[..threshold]
+        .iter()
+        .map(|(index, partial)| Ok((*index, parse_signature(partial)?)))
+        .collect::<Result<Vec<_>, TLockError>>()?;
+    Ok(ibe::recover_signature(&partials)?.to_compressed()?)
+}
+
+/// Identity of a round, `sha256(round)` with round big endian encoded.
+fn round_id(round_number: Round) -> Vec<u8> {
+    let mut hash = sha2::Sha256::new();
+    hash.update(round_number.value().to_be_bytes());
+    hash.finalize().to_vec()
 }
 
 fn time_lock<M: AsRef<[u8]>>(
     public_key_bytes: &[u8],
-    round_number: u64,
+    round_number: Round,
     message: M,
 ) -> Result<ibe::Ciphertext, anyhow::Error> {
     let public_key = GAffine::try_from(public_key_bytes)?;
-    let id = {
-        let mut hash = sha2::Sha256::new();
-        hash.update(round_number.to_be_bytes());
-        &hash.finalize().to_vec()[0..32]
-    };
+    #[cfg(feature = "tracing")]
+    trace_scheme(&public_key);
+    ibe::encrypt(public_key, round_id(round_number), message)
+}
+
+/// Log the drand scheme encryption is compatible with, as it is set at compile time by the `rfc9380` feature.
+///
+/// Public keys on G1 only support `pedersen-bls-unchained`. For public keys on G2, the G1 hashing domain decides the scheme,
+/// and the legacy `bls-unchained-on-g1` one is a warning, as drand networks now use `bls-unchained-g1-rfc9380`.
+#[cfg(feature = "tracing")]
+fn trace_scheme(public_key: &GAffine) {
+    match public_key {
+        GAffine::G1Affine(_) => tracing::debug!(scheme = "pedersen-bls-unchained", "encrypting"),
+        #[cfg(feature = "rfc9380")]
+        GAffine::G2Affine(_) => tracing::debug!(scheme = "bls-unchained-g1-rfc9380", "encrypting"),
+        #[cfg(not(feature = "rfc9380"))]
+        GAffine::G2Affine(_) => tracing::warn!(
+            scheme = "bls-unchained-on-g1",
+            "encrypting with the legacy G1 hashing domain, ciphertexts are not decryptable for bls-unchained-g1-rfc9380 chains such as quicknet. Enable the rfc9380 feature to use them"
+        ),
+    }
+}
 
-    ibe::encrypt(public_key, id, message)
+fn time_unlock(signature: &GAffine, c: &Ciphertext) -> Result<Vec<u8>, TLockError> {
+    ibe::decrypt(signature.clone(), c).map_err(TLockError::IBE)
 }
 
-fn time_unlock(signature: &[u8], c: &Ciphertext) -> Result<Vec<u8>, TLockError> {
-    ibe::decrypt(signature.try_into()?, c).map_err(TLockError::IBE)
+fn time_unlock_unchecked(signature: &GAffine, c: &Ciphertext) -> Result<Vec<u8>, TLockError> {
+    ibe::decrypt_unchecked(signature.clone(), c).map_err(TLockError::IBE)
 }
 
 #[cfg(test)]
@@ -150,14 +1049,550 @@ mod tests {
         let pk_bytes = hex::decode("8200fc249deb0148eb918d6e213980c5d01acd7fc251900d9260136da3b54836ce125172399ddc69c4e3e11429b62c11").unwrap();
 
         let msg = vec![8; 16];
-        let ct = time_lock(&pk_bytes, 1000, msg.clone()).unwrap();
+        let ct = time_lock(&pk_bytes, 1000.into(), msg.clone()).unwrap();
 
         let signature = hex::decode("a4721e6c3eafcd823f138cd29c6c82e8c5149101d0bb4bafddbac1c2d1fe3738895e4e21dd4b8b41bf007046440220910bb1cdb91f50a84a0d7f33ff2e8577aa62ac64b35a291a728a9db5ac91e06d1312b48a376138d77b4d6ad27c24221afe").unwrap();
 
-        let pt = time_unlock(&signature, &ct).unwrap();
+        let pt = time_unlock(&parse_signature(&signature).unwrap(), &ct).unwrap();
         assert_eq!(pt, msg)
     }
 
+    #[test]
+    fn test_decrypt_with_gid() {
+        let pk_bytes = hex::decode("8200fc249deb0148eb918d6e213980c5d01acd7fc251900d9260136da3b54836ce125172399ddc69c4e3e11429b62c11").unwrap();
+        let signature = hex::decode("a4721e6c3eafcd823f138cd29c6c82e8c5149101d0bb4bafddbac1c2d1fe3738895e4e21dd4b8b41bf007046440220910bb1cdb91f50a84a0d7f33ff2e8577aa62ac64b35a291a728a9db5ac91e06d1312b48a376138d77b4d6ad27c24221afe").unwrap();
+        let msg = [8u8; 16];
+
+        let ct = time_lock(&pk_bytes, Round::new(1000), msg).unwrap();
+        let private = GAffine::try_from(signature.as_slice()).unwrap();
+        let gid = private.pairing(&ct.u).unwrap();
+        assert_eq!(ibe::decrypt_with_gid(gid, &ct).unwrap(), msg);
+
+        let wrong_gid = private.pairing(&ct.u.mul(2u64.into())).unwrap();
+        assert!(matches!(
+            ibe::decrypt_with_gid(wrong_gid, &ct),
+            Err(IBEError::SignatureMismatch)
+        ));
+    }
+
+    #[test]
+    fn test_decrypt_unpadded() {
+        let signature = hex::decode("a4721e6c3eafcd823f138cd29c6c82e8c5149101d0bb4bafddbac1c2d1fe3738895e4e21dd4b8b41bf007046440220910bb1cdb91f50a84a0d7f33ff2e8577aa62ac64b35a291a728a9db5ac91e06d1312b48a376138d77b4d6ad27c24221afe").unwrap();
+
+        // 16 bytes of 0x42 encrypted at round 1000, before V and W were decrypted without padding
+        let encrypted = hex::decode("ab4f7c52007586196b3bd0f0f5ff85dbc06cb78aee6a61bb5cab31b38ac37db37fc403820747abe5f54a52181801b7e142c5639bad4acafcc26236892053ec278757fe2f24eb59896b403cefc188d7c6").unwrap();
+        let mut decrypted = vec![];
+        decrypt(&mut decrypted, encrypted.as_slice(), &signature).unwrap();
+        assert_eq!(decrypted, [0x42u8; 16]);
+
+        // V and W padded to 32 bytes, as they used to be, are rejected
+        let (u, vw) = encrypted.split_at(ibe::G1_SIZE);
+        let (v, w) = vw.split_at(ibe::MESSAGE_SIZE);
+        let padded = Ciphertext {
+            u: u.try_into().unwrap(),
+            v: [&[0u8; ibe::MESSAGE_SIZE], v].concat(),
+            w: [&[0u8; ibe::MESSAGE_SIZE], w].concat(),
+        };
+        assert!(matches!(
+            time_unlock(&parse_signature(&signature).unwrap(), &padded),
+            Err(TLockError::IBE(IBEError::CiphertextSize { v: 32, w: 32 }))
+        ));
+    }
+
+    #[test]
+    fn test_decrypt_trailing_zero() {
+        let pk_bytes = hex::decode("8200fc249deb0148eb918d6e213980c5d01acd7fc251900d9260136da3b54836ce125172399ddc69c4e3e11429b62c11").unwrap();
+        let signature = hex::decode("a4721e6c3eafcd823f138cd29c6c82e8c5149101d0bb4bafddbac1c2d1fe3738895e4e21dd4b8b41bf007046440220910bb1cdb91f50a84a0d7f33ff2e8577aa62ac64b35a291a728a9db5ac91e06d1312b48a376138d77b4d6ad27c24221afe").unwrap();
+
+        // one key in 256 from encrypt_random_key ends with a zero byte
+        let mut key = [8u8; ibe::MESSAGE_SIZE];
+        key[ibe::MESSAGE_SIZE - 1] = 0;
+        for msg in [key, [0u8; ibe::MESSAGE_SIZE]] {
+            let mut encrypted = vec![];
+            encrypt(&mut encrypted, msg.as_slice(), &pk_bytes, 1000).unwrap();
+            let mut decrypted = vec![];
+            decrypt(&mut decrypted, encrypted.as_slice(), &signature).unwrap();
+            assert_eq!(decrypted, msg);
+        }
+    }
+
+    #[test]
+    fn test_encrypt_message_size() {
+        let pk_bytes = hex::decode("8200fc249deb0148eb918d6e213980c5d01acd7fc251900d9260136da3b54836ce125172399ddc69c4e3e11429b62c11").unwrap();
+        let signature = hex::decode("a4721e6c3eafcd823f138cd29c6c82e8c5149101d0bb4bafddbac1c2d1fe3738895e4e21dd4b8b41bf007046440220910bb1cdb91f50a84a0d7f33ff2e8577aa62ac64b35a291a728a9db5ac91e06d1312b48a376138d77b4d6ad27c24221afe").unwrap();
+
+        // shorter messages are padded with zeros
+        let mut encrypted = vec![];
+        encrypt(&mut encrypted, [8u8; 10].as_slice(), &pk_bytes, 1000).unwrap();
+        let mut decrypted = vec![];
+        decrypt(&mut decrypted, encrypted.as_slice(), &signature).unwrap();
+        assert_eq!(decrypted, [[8u8; 10].as_slice(), &[0u8; 6]].concat());
+
+        // longer messages are rejected instead of silently truncated, including when split across reads
+        let long = [8u8; ibe::MESSAGE_SIZE + 1];
+        let chunked = io::Read::chain(&long[..8], &long[8..]);
+        let results = [
+            encrypt(vec![], long.as_slice(), &pk_bytes, 1000),
+            encrypt(vec![], chunked, &pk_bytes, 1000),
+            encrypt_deterministic(vec![], long.as_slice(), &pk_bytes, 1000, &[42u8; 32]),
+        ];
+        for result in results {
+            assert!(matches!(
+                result.unwrap_err().downcast::<TLockError>(),
+                Ok(TLockError::IBE(IBEError::MessageSize))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_decrypt_infinity_signature() {
+        use ark_ec::AffineRepr;
+
+        // compressed encoding of the point at infinity: compression and infinity flags set
+        let mut g1_infinity = [0u8; ibe::G1_SIZE];
+        g1_infinity[0] = 0xc0;
+        let mut g2_infinity = [0u8; ibe::G2_SIZE];
+        g2_infinity[0] = 0xc0;
+
+        let pk_g1 = hex::decode("8200fc249deb0148eb918d6e213980c5d01acd7fc251900d9260136da3b54836ce125172399ddc69c4e3e11429b62c11").unwrap();
+        let pk_g2 = ibe::GAffine::G2Affine(ark_bls12_381::G2Affine::generator())
+            .to_compressed()
+            .unwrap();
+        for (pk_bytes, signature) in [(pk_g1, g2_infinity.as_slice()), (pk_g2, &g1_infinity)] {
+            assert!(GAffine::try_from(signature).unwrap().is_infinity());
+
+            let mut encrypted = vec![];
+            encrypt(&mut encrypted, [8u8; 16].as_slice(), &pk_bytes, 1000).unwrap();
+            for result in [
+                decrypt(vec![], encrypted.as_slice(), signature),
+                decrypt_unchecked(vec![], encrypted.as_slice(), signature),
+            ] {
+                assert!(matches!(
+                    result,
+                    Err(TLockError::IBE(IBEError::InfinitySignature))
+                ));
+            }
+        }
+    }
+
+    #[test]
+    fn test_decrypt_truncated() {
+        let pk_bytes = hex::decode("8200fc249deb0148eb918d6e213980c5d01acd7fc251900d9260136da3b54836ce125172399ddc69c4e3e11429b62c11").unwrap();
+        let signature = hex::decode("a4721e6c3eafcd823f138cd29c6c82e8c5149101d0bb4bafddbac1c2d1fe3738895e4e21dd4b8b41bf007046440220910bb1cdb91f50a84a0d7f33ff2e8577aa62ac64b35a291a728a9db5ac91e06d1312b48a376138d77b4d6ad27c24221afe").unwrap();
+
+        let mut encrypted = vec![];
+        encrypt(&mut encrypted, [8u8; 16].as_slice(), &pk_bytes, 1000).unwrap();
+        let truncated = &encrypted[..encrypted.len() - 10];
+
+        let result = decrypt(vec![], truncated, &signature);
+        assert!(matches!(
+            result,
+            Err(TLockError::TruncatedCiphertext {
+                expected: 80,
+                actual: 70,
+                group: Group::G1
+            })
+        ));
+    }
+
+    #[cfg(feature = "debug-hex")]
+    #[test]
+    fn test_debug_hex() {
+        let pk_bytes = hex::decode("8200fc249deb0148eb918d6e213980c5d01acd7fc251900d9260136da3b54836ce125172399ddc69c4e3e11429b62c11").unwrap();
+        let mut encrypted = vec![];
+        encrypt(&mut encrypted, [8u8; 16].as_slice(), &pk_bytes, 1000).unwrap();
+
+        let dump = debug_hex(&encrypted).unwrap();
+        let lines: Vec<&str> = dump.lines().collect();
+        assert_eq!(
+            lines[0],
+            format!("U (G1, 48 bytes): {}", hex::encode(&encrypted[..48]))
+        );
+        assert_eq!(
+            lines[1],
+            format!("V (16 bytes): {}", hex::encode(&encrypted[48..64]))
+        );
+        assert_eq!(
+            lines[2],
+            format!("W (16 bytes): {}", hex::encode(&encrypted[64..]))
+        );
+        assert!(debug_hex(&encrypted[1..]).is_err());
+    }
+
+    #[test]
+    fn test_crypto_params() {
+        use ark_ec::AffineRepr;
+        use ark_ff::{BigInteger, PrimeField};
+        use ark_serialize::CanonicalSerialize;
+
+        let params = crypto_params();
+        assert_eq!(
+            hex::decode(params.subgroup_order).unwrap(),
+            ark_bls12_381::Fr::MODULUS.to_bytes_be()
+        );
+        assert_eq!(
+            params.g1_size,
+            ark_bls12_381::G1Affine::generator().compressed_size()
+        );
+        assert_eq!(
+            params.g2_size,
+            ark_bls12_381::G2Affine::generator().compressed_size()
+        );
+        assert_eq!(params.g1_domain, ibe::G1_DOMAIN);
+        assert_eq!(params.supported_schemes, SUPPORTED_SCHEMES);
+        assert_eq!(params.message_size, 16);
+    }
+
+    #[test]
+    fn test_scheme_id() {
+        let g1 = hex::decode("8200fc249deb0148eb918d6e213980c5d01acd7fc251900d9260136da3b54836ce125172399ddc69c4e3e11429b62c11").unwrap();
+        assert_eq!(scheme_id(&g1).unwrap(), "pedersen-bls-unchained");
+        // quicknet
+        let g2 = hex::decode("83cf0f2896adee7eb8b5f01fcad3912212c437e0073e911fb90022d3e760183c8c4b450b6a0a6c3ac6a5776a2d1064510d1fec758c921cc22b0e17e63aaf4bcb5ed66304de9cf809bd274ca73bab4af5a6e9c76a4bc09e76eae8991ef5ece45a").unwrap();
+        #[cfg(feature = "rfc9380")]
+        assert_eq!(scheme_id(&g2).unwrap(), "bls-unchained-g1-rfc9380");
+        #[cfg(not(feature = "rfc9380"))]
+        assert_eq!(scheme_id(&g2).unwrap(), "bls-unchained-on-g1");
+        for scheme in [scheme_id(&g1).unwrap(), scheme_id(&g2).unwrap()] {
+            assert!(verify_scheme(scheme).is_ok());
+        }
+        assert!(scheme_id(&g1[1..]).is_err());
+    }
+
+    #[test]
+    fn test_encrypt_deterministic() {
+        let pk_bytes = hex::decode("8200fc249deb0148eb918d6e213980c5d01acd7fc251900d9260136da3b54836ce125172399ddc69c4e3e11429b62c11").unwrap();
+        let signature = hex::decode("a4721e6c3eafcd823f138cd29c6c82e8c5149101d0bb4bafddbac1c2d1fe3738895e4e21dd4b8b41bf007046440220910bb1cdb91f50a84a0d7f33ff2e8577aa62ac64b35a291a728a9db5ac91e06d1312b48a376138d77b4d6ad27c24221afe").unwrap();
+        let msg = [8u8; 16];
+        let secret = [42u8; 32];
+
+        let encrypt_with = |round: u64| {
+            let mut encrypted = vec![];
+            encrypt_deterministic(&mut encrypted, msg.as_slice(), &pk_bytes, round, &secret)
+                .unwrap();
+            encrypted
+        };
+        let encrypted = encrypt_with(1000);
+        assert_eq!(encrypted, encrypt_with(1000));
+        assert_ne!(encrypted, encrypt_with(1001));
+
+        let mut decrypted = vec![];
+        decrypt(&mut decrypted, encrypted.as_slice(), &signature).unwrap();
+        assert_eq!(decrypted, msg);
+    }
+
+    #[test]
+    fn test_encrypt_into() {
+        let pk_bytes = hex::decode("8200fc249deb0148eb918d6e213980c5d01acd7fc251900d9260136da3b54836ce125172399ddc69c4e3e11429b62c11").unwrap();
+        let signature = hex::decode("a4721e6c3eafcd823f138cd29c6c82e8c5149101d0bb4bafddbac1c2d1fe3738895e4e21dd4b8b41bf007046440220910bb1cdb91f50a84a0d7f33ff2e8577aa62ac64b35a291a728a9db5ac91e06d1312b48a376138d77b4d6ad27c24221afe").unwrap();
+        let msg = vec![8; 16];
+
+        let mut encrypted = [0u8; 100];
+        let n = encrypt_into(&mut encrypted, msg.as_slice(), &pk_bytes, 1000).unwrap();
+        assert_eq!(n, ciphertext_len(&pk_bytes).unwrap());
+
+        let mut decrypted = vec![];
+        decrypt(&mut decrypted, &encrypted[..n], &signature).unwrap();
+        assert_eq!(decrypted, msg);
+
+        let mut too_small = [0u8; 79];
+        assert!(encrypt_into(&mut too_small, msg.as_slice(), &pk_bytes, 1000).is_err());
+    }
+
+    #[test]
+    fn test_text_encodings() {
+        let pk_bytes = hex::decode("8200fc249deb0148eb918d6e213980c5d01acd7fc251900d9260136da3b54836ce125172399ddc69c4e3e11429b62c11").unwrap();
+        let signature = hex::decode("a4721e6c3eafcd823f138cd29c6c82e8c5149101d0bb4bafddbac1c2d1fe3738895e4e21dd4b8b41bf007046440220910bb1cdb91f50a84a0d7f33ff2e8577aa62ac64b35a291a728a9db5ac91e06d1312b48a376138d77b4d6ad27c24221afe").unwrap();
+        let msg = vec![8; 16];
+
+        let encrypted = encrypt_base64(msg.as_slice(), &pk_bytes, 1000).unwrap();
+        let mut decrypted = vec![];
+        decrypt_base64(&mut decrypted, &encrypted, &signature).unwrap();
+        assert_eq!(decrypted, msg);
+
+        let encrypted = encrypt_hex(msg.as_slice(), &pk_bytes, 1000).unwrap();
+        let mut decrypted = vec![];
+        decrypt_hex(&mut decrypted, &encrypted, &signature).unwrap();
+        assert_eq!(decrypted, msg);
+
+        assert!(matches!(
+            decrypt_hex(vec![], "not hex", &signature),
+            Err(TLockError::Decoding(_))
+        ));
+    }
+
+    #[test]
+    fn test_verify_beacon() {
+        let pk_bytes = hex::decode("8200fc249deb0148eb918d6e213980c5d01acd7fc251900d9260136da3b54836ce125172399ddc69c4e3e11429b62c11").unwrap();
+        let signature = hex::decode("a4721e6c3eafcd823f138cd29c6c82e8c5149101d0bb4bafddbac1c2d1fe3738895e4e21dd4b8b41bf007046440220910bb1cdb91f50a84a0d7f33ff2e8577aa62ac64b35a291a728a9db5ac91e06d1312b48a376138d77b4d6ad27c24221afe").unwrap();
+
+        verify_beacon(&pk_bytes, 1000, &signature).unwrap();
+        assert!(verify_beacon(&pk_bytes, 1001, &signature).is_err());
+    }
+
+    #[cfg(feature = "rfc9380")]
+    #[test]
+    fn test_verify_beacon_g1_signature() {
+        // quicknet at round 1000
+        let pk_bytes = hex::decode("83cf0f2896adee7eb8b5f01fcad3912212c437e0073e911fb90022d3e760183c8c4b450b6a0a6c3ac6a5776a2d1064510d1fec758c921cc22b0e17e63aaf4bcb5ed66304de9cf809bd274ca73bab4af5a6e9c76a4bc09e76eae8991ef5ece45a").unwrap();
+        let signature = hex::decode("b44679b9a59af2ec876b1a6b1ad52ea9b1615fc3982b19576350f93447cb1125e342b73a8dd2bacbe47e4b6b63ed5e39").unwrap();
+
+        verify_beacon(&pk_bytes, 1000, &signature).unwrap();
+        assert!(verify_beacon(&pk_bytes, 1001, &signature).is_err());
+    }
+
+    #[test]
+    fn test_recover_signature() {
+        use ark_ec::AffineRepr;
+
+        // 2-of-3 network with secret polynomial f(x) = 5 + 11x, signing round 1000
+        let f = |x: u64| ark_bls12_381::Fr::from(5 + 11 * x);
+        let pk = GAffine::G1Affine(ark_bls12_381::G1Affine::generator());
+        let h = pk.hash_to_curve(&round_id(Round::new(1000))).unwrap();
+        let pk_bytes = pk.mul(f(0)).to_compressed().unwrap();
+        let partials: Vec<Vec<u8>> = (1..=3)
+            .map(|x| h.mul(f(x)).to_compressed().unwrap())
+            .collect();
+
+        let signature = recover_signature(
+            &[(2, &partials[2]), (0, &partials[0]), (1, &partials[1])],
+            2,
+        )
+        .unwrap();
+        verify_beacon(&pk_bytes, 1000, &signature).unwrap();
+
+        let mut ct = vec![];
+        encrypt(&mut ct, &[8u8; 16][..], &pk_bytes, 1000).unwrap();
+        let mut pt = vec![];
+        decrypt(&mut pt, &ct[..], &signature).unwrap();
+        assert_eq!(pt, [8u8; 16]);
+
+        assert!(matches!(
+            recover_signature(&[(0, &partials[0])], 2),
+            Err(TLockError::NotEnoughPartials {
+                count: 1,
+                threshold: 2
+            })
+        ));
+        assert!(matches!(
+            recover_signature(&[(0, &partials[0][1..]), (1, &partials[1])], 2),
+            Err(TLockError::SignatureSize(95))
+        ));
+    }
+
+    #[test]
+    fn test_decrypt_with_meta() {
+        use ark_ec::AffineRepr;
+
+        let msg = [8u8; 16];
+
+        // testnet-unchained-3s, public key on G1
+        let pk_bytes = hex::decode("8200fc249deb0148eb918d6e213980c5d01acd7fc251900d9260136da3b54836ce125172399ddc69c4e3e11429b62c11").unwrap();
+        let signature = hex::decode("a4721e6c3eafcd823f138cd29c6c82e8c5149101d0bb4bafddbac1c2d1fe3738895e4e21dd4b8b41bf007046440220910bb1cdb91f50a84a0d7f33ff2e8577aa62ac64b35a291a728a9db5ac91e06d1312b48a376138d77b4d6ad27c24221afe").unwrap();
+        let mut ct = vec![];
+        encrypt(&mut ct, &msg[..], &pk_bytes, 1000).unwrap();
+        let (pt, meta) = decrypt_with_meta(&ct[..], &signature).unwrap();
+        assert_eq!(pt, msg);
+        assert_eq!(
+            meta,
+            DecryptMeta {
+                group: Group::G1,
+                signature_group: Group::G2
+            }
+        );
+
+        // public key on G2, with a locally generated signature
+        let secret = ark_bls12_381::Fr::from(42);
+        let pk = GAffine::G2Affine(ark_bls12_381::G2Affine::generator());
+        let pk_bytes = pk.mul(secret).to_compressed().unwrap();
+        let signature = pk
+            .hash_to_curve(&round_id(Round::new(1000)))
+            .unwrap()
+            .mul(secret)
+            .to_compressed()
+            .unwrap();
+        let mut ct = vec![];
+        encrypt(&mut ct, &msg[..], &pk_bytes, 1000).unwrap();
+        let (pt, meta) = decrypt_with_meta(&ct[..], &signature).unwrap();
+        assert_eq!(pt, msg);
+        assert_eq!((meta.group, meta.signature_group), (Group::G2, Group::G1));
+
+        assert!(matches!(
+            decrypt_with_meta(&ct[1..], &signature),
+            Err(TLockError::TruncatedCiphertext {
+                group: Group::G2,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_round_conversion() {
+        for n in [0, 1, 1000, u64::MAX] {
+            let round = Round::from(n);
+            assert_eq!(round, Round::new(n));
+            assert_eq!(round.value(), n);
+            assert_eq!(u64::from(round), n);
+            assert_eq!(round.to_string(), n.to_string());
+        }
+    }
+
+    #[cfg(feature = "http")]
+    #[test]
+    fn test_fetch_signatures_with() {
+        let mut fetched = vec![];
+        let rounds = [1000, 0, 1001, 1000, 5000, 1].map(Round::new);
+        let signatures = fetch_signatures_with(&rounds, Round::new(1001), |round| {
+            fetched.push(round.value());
+            Ok(round.value().to_be_bytes().to_vec())
+        })
+        .unwrap();
+
+        assert_eq!(fetched, [1000, 1001, 1]);
+        assert_eq!(signatures.len(), 3);
+        assert_eq!(signatures[&Round::new(1000)], 1000u64.to_be_bytes());
+        assert!(!signatures.contains_key(&Round::new(5000)));
+        assert!(!signatures.contains_key(&Round::new(0)));
+
+        let result = fetch_signatures_with(&rounds, Round::new(1001), |_| {
+            Err(anyhow::anyhow!("unavailable"))
+        });
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_chain_hash() {
+        let hex = "7672797f548f3f4748ac4bf3352fc6c6b6468c9ad40ad456a397545c6e2df5bf";
+        let bytes = hex::decode(hex).unwrap();
+
+        let chain_hash: ChainHash = hex.parse().unwrap();
+        assert_eq!(chain_hash.to_string(), hex);
+        assert_eq!(chain_hash.as_ref(), bytes.as_slice());
+        assert_eq!(ChainHash::try_from(bytes.as_slice()).unwrap(), chain_hash);
+        assert_eq!(
+            ChainHash::from(<[u8; 32]>::try_from(bytes.as_slice()).unwrap()),
+            chain_hash
+        );
+        assert_eq!(hex.to_uppercase().parse::<ChainHash>().unwrap(), chain_hash);
+
+        for s in [
+            "",
+            "7672",
+            &hex[1..],
+            &format!("{hex}00"),
+            &hex.replace('7', "g"),
+        ] {
+            assert!(
+                matches!(s.parse::<ChainHash>(), Err(TLockError::InvalidChainHash(_))),
+                "{s}"
+            );
+        }
+        assert!(matches!(
+            ChainHash::try_from(&bytes[1..]),
+            Err(TLockError::InvalidChainHash(_))
+        ));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        let hex = "7672797f548f3f4748ac4bf3352fc6c6b6468c9ad40ad456a397545c6e2df5bf";
+        let chain_hash: ChainHash = hex.parse().unwrap();
+        assert_eq!(serde_json::to_value(chain_hash).unwrap(), hex);
+        assert_eq!(
+            serde_json::from_value::<ChainHash>(hex.into()).unwrap(),
+            chain_hash
+        );
+        for invalid in [serde_json::json!(&hex[2..]), serde_json::json!(1000)] {
+            assert!(serde_json::from_value::<ChainHash>(invalid).is_err());
+        }
+
+        let round = Round::new(1000);
+        assert_eq!(serde_json::to_value(round).unwrap(), 1000);
+        assert_eq!(serde_json::from_value::<Round>(1000.into()).unwrap(), round);
+        assert!(serde_json::from_value::<Round>((-1).into()).is_err());
+    }
+
+    #[test]
+    fn test_round_after() {
+        let period = Duration::from_secs(3);
+        let latest = Round::new(1000);
+        assert_eq!(latest.after(Duration::ZERO, period), latest);
+        assert_eq!(
+            latest.after(Duration::from_secs(3), period),
+            Round::new(1001)
+        );
+        assert_eq!(
+            latest.after(Duration::from_secs(4), period),
+            Round::new(1002)
+        );
+        assert_eq!(
+            latest.after(Duration::from_millis(1), period),
+            Round::new(1001)
+        );
+        assert_eq!(
+            Round::new(u64::MAX).after(Duration::from_secs(3), period),
+            Round::new(u64::MAX)
+        );
+    }
+
+    #[test]
+    fn test_round_check_future() {
+        let latest = Round::new(1000);
+        assert_eq!(
+            Round::new(1001).check_future(latest).unwrap(),
+            Round::new(1001)
+        );
+        assert_eq!(Round::MAX.check_future(latest).unwrap(), Round::MAX);
+        for round in [0, 1, 999, 1000] {
+            assert!(matches!(
+                Round::new(round).check_future(latest),
+                Err(TLockError::PastRound { round: r, latest: l }) if r == Round::new(round) && l == latest
+            ));
+        }
+    }
+
+    #[test]
+    fn test_round_after_overflow() {
+        let period = Duration::from_secs(3);
+        assert_eq!(
+            Round::new(u64::MAX - 1).after(Duration::from_secs(3), period),
+            Round::MAX
+        );
+        assert_eq!(
+            Round::new(u64::MAX - 1).after(Duration::from_secs(6), period),
+            Round::MAX
+        );
+        // Duration::MAX in nanoseconds does not fit in u64 rounds
+        assert_eq!(
+            Round::new(1).after(Duration::MAX, Duration::from_nanos(1)),
+            Round::MAX
+        );
+        assert_eq!(
+            Round::new(1).after(Duration::MAX, Duration::from_secs(1)),
+            Round::MAX
+        );
+        assert_eq!(
+            Round::new(0).after(Duration::from_secs(u64::MAX), Duration::from_secs(1)),
+            Round::MAX
+        );
+    }
+
+    #[test]
+    fn test_verify_scheme() {
+        assert!(verify_scheme("pedersen-bls-unchained").is_ok());
+        assert!(verify_scheme("pedersen-bls-chained").is_err());
+        assert!(verify_scheme("bls-bn254-unchained-on-g1").is_err());
+        assert_eq!(
+            verify_scheme("bls-unchained-g1-rfc9380").is_ok(),
+            cfg!(feature = "rfc9380")
+        );
+        assert_eq!(
+            verify_scheme("bls-unchained-on-g1").is_ok(),
+            cfg!(not(feature = "rfc9380"))
+        );
+    }
+
     #[cfg(not(feature = "rfc9380"))]
     #[test]
     fn test_pk_g2_sig_g1() {
@@ -167,11 +1602,11 @@ mod tests {
         // at round 1000
         // https://drand.cloudflare.com/dbd506d6ef76e5f386f41c651dcb808c5bcbd75471cc4eafa3f4df7ad4e4c493/public/1000
         let msg = vec![8; 16];
-        let ct = time_lock(&pk_bytes, 1000, msg.clone()).unwrap();
+        let ct = time_lock(&pk_bytes, 1000.into(), msg.clone()).unwrap();
 
         let signature = hex::decode("b09eacd45767c4d58306b98901ad0d6086e2663766f3a4ec71d00cf26f0f49eaf248abc7151c60cf419c4e8b37e80412").unwrap();
 
-        let pt = time_unlock(&signature, &ct).unwrap();
+        let pt = time_unlock(&parse_signature(&signature).unwrap(), &ct).unwrap();
         assert_eq!(pt, msg)
     }
 
@@ -184,11 +1619,50 @@ mod tests {
         // at round 1000
         // https://drand.cloudflare.com/52db9ba70e0cc0f6eaf7803dd07447a1f5477735fd3f661792ba94600c84e971/public/1000
         let msg = vec![8; 16];
-        let ct = time_lock(&pk_bytes, 1000, msg.clone()).unwrap();
+        let ct = time_lock(&pk_bytes, 1000.into(), msg.clone()).unwrap();
 
         let signature = hex::decode("b44679b9a59af2ec876b1a6b1ad52ea9b1615fc3982b19576350f93447cb1125e342b73a8dd2bacbe47e4b6b63ed5e39").unwrap();
 
-        let pt = time_unlock(&signature, &ct).unwrap();
+        let pt = time_unlock(&parse_signature(&signature).unwrap(), &ct).unwrap();
         assert_eq!(pt, msg)
     }
+
+    #[test]
+    fn test_decrypt_signature_encodings() {
+        let msg = [8u8; 16];
+        let check = |pk_bytes: &[u8], signature: &[u8]| {
+            let mut encrypted = vec![];
+            encrypt(&mut encrypted, msg.as_slice(), pk_bytes, 1000).unwrap();
+            assert_eq!(
+                encrypted.len(),
+                ciphertext_len_for_signature(signature).unwrap()
+            );
+            let mut decrypted = vec![];
+            decrypt(&mut decrypted, encrypted.as_slice(), signature).unwrap();
+            assert_eq!(decrypted, msg);
+
+            // hex encoded signatures are not mistaken for a point on another group
+            let hex_signature = hex::encode(signature);
+            assert!(matches!(
+                decrypt(vec![], encrypted.as_slice(), hex_signature.as_bytes()),
+                Err(TLockError::HexSignature(len)) if len == 2 * signature.len()
+            ));
+            assert!(matches!(
+                decrypt(vec![], encrypted.as_slice(), &signature[1..]),
+                Err(TLockError::SignatureSize(len)) if len == signature.len() - 1
+            ));
+        };
+
+        // testnet-unchained-3s, pedersen-bls-unchained, signature on G2
+        check(
+            &hex::decode("8200fc249deb0148eb918d6e213980c5d01acd7fc251900d9260136da3b54836ce125172399ddc69c4e3e11429b62c11").unwrap(),
+            &hex::decode("a4721e6c3eafcd823f138cd29c6c82e8c5149101d0bb4bafddbac1c2d1fe3738895e4e21dd4b8b41bf007046440220910bb1cdb91f50a84a0d7f33ff2e8577aa62ac64b35a291a728a9db5ac91e06d1312b48a376138d77b4d6ad27c24221afe").unwrap(),
+        );
+        // quicknet, bls-unchained-g1-rfc9380, signature on G1
+        #[cfg(feature = "rfc9380")]
+        check(
+            &hex::decode("83cf0f2896adee7eb8b5f01fcad3912212c437e0073e911fb90022d3e760183c8c4b450b6a0a6c3ac6a5776a2d1064510d1fec758c921cc22b0e17e63aaf4bcb5ed66304de9cf809bd274ca73bab4af5a6e9c76a4bc09e76eae8991ef5ece45a").unwrap(),
+            &hex::decode("b44679b9a59af2ec876b1a6b1ad52ea9b1615fc3982b19576350f93447cb1125e342b73a8dd2bacbe47e4b6b63ed5e39").unwrap(),
+        );
+    }
 }
@@ -0,0 +1,83 @@
+//! JavaScript bindings to the tlock primitive, for users building their own container on top of it.
+//!
+//! Byte arrays are exchanged as `Uint8Array`, rounds as `BigInt`, and errors are thrown as JavaScript `Error`.
+//!
+//! Tests run in Node.js with `wasm-pack test --node -- --features js`.
+
+use wasm_bindgen::prelude::*;
+
+/// Encrypt a 16-byte message towards `round` of the chain with public key `public_key`.
+///
+/// `round` is a `u64`, which wasm-bindgen maps to a JavaScript `BigInt`: call it as `encrypt(msg, publicKey, 1000n)`.
+#[wasm_bindgen]
+pub fn encrypt(msg: &[u8], public_key: &[u8], round: u64) -> Result<Vec<u8>, JsError> {
+    let mut dst = vec![];
+    crate::encrypt(&mut dst, msg, public_key, round)
+        .map_err(|err| JsError::new(&err.to_string()))?;
+    Ok(dst)
+}
+
+/// Decrypt a ciphertext produced by [`encrypt`] with the signature of its round.
+#[wasm_bindgen]
+pub fn decrypt(ciphertext: &[u8], signature: &[u8]) -> Result<Vec<u8>, JsError> {
+    let mut dst = vec![];
+    crate::decrypt(&mut dst, ciphertext, signature)
+        .map_err(|err| JsError::new(&err.to_string()))?;
+    Ok(dst)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encrypt_decrypt() {
+        // testnet-unchained-3s, public key on G1
+        let pk_bytes = hex::decode("8200fc249deb0148eb918d6e213980c5d01acd7fc251900d9260136da3b54836ce125172399ddc69c4e3e11429b62c11").unwrap();
+        let signature = hex::decode("a4721e6c3eafcd823f138cd29c6c82e8c5149101d0bb4bafddbac1c2d1fe3738895e4e21dd4b8b41bf007046440220910bb1cdb91f50a84a0d7f33ff2e8577aa62ac64b35a291a728a9db5ac91e06d1312b48a376138d77b4d6ad27c24221afe").unwrap();
+
+        let msg = [8u8; 16];
+        let encrypted = encrypt(&msg, &pk_bytes, 1000).unwrap();
+        assert_eq!(encrypted.len(), crate::ciphertext_len(&pk_bytes).unwrap());
+        assert_eq!(decrypt(&encrypted, &signature).unwrap(), msg);
+    }
+}
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod node_tests {
+    use super::*;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    // testnet-unchained-3s, public key on G1
+    const PUBLIC_KEY: &str = "8200fc249deb0148eb918d6e213980c5d01acd7fc251900d9260136da3b54836ce125172399ddc69c4e3e11429b62c11";
+    // signature for round 1000
+    const SIGNATURE: &str = "a4721e6c3eafcd823f138cd29c6c82e8c5149101d0bb4bafddbac1c2d1fe3738895e4e21dd4b8b41bf007046440220910bb1cdb91f50a84a0d7f33ff2e8577aa62ac64b35a291a728a9db5ac91e06d1312b48a376138d77b4d6ad27c24221afe";
+
+    /// Message of the JavaScript `Error` thrown for `err`.
+    fn message(err: JsError) -> String {
+        js_sys::Error::from(JsValue::from(err)).message().into()
+    }
+
+    #[wasm_bindgen_test]
+    fn test_encrypt_decrypt() {
+        let pk_bytes = hex::decode(PUBLIC_KEY).unwrap();
+        let signature = hex::decode(SIGNATURE).unwrap();
+
+        let msg = [8u8; 16];
+        let encrypted = encrypt(&msg, &pk_bytes, 1000).unwrap();
+        assert_eq!(decrypt(&encrypted, &signature).unwrap(), msg);
+    }
+
+    #[wasm_bindgen_test]
+    fn test_errors() {
+        let pk_bytes = hex::decode(PUBLIC_KEY).unwrap();
+        let signature = hex::decode(SIGNATURE).unwrap();
+
+        let err = encrypt(&[8u8; 16], &pk_bytes[1..], 1000).err().unwrap();
+        assert!(message(err).contains("invalid public key size"));
+
+        let encrypted = encrypt(&[8u8; 16], &pk_bytes, 1000).unwrap();
+        let err = decrypt(&encrypted[1..], &signature).err().unwrap();
+        assert!(message(err).contains("truncated ciphertext"));
+    }
+}
@@ -0,0 +1,112 @@
+//! Deterministic drand chain, for tests and examples without network access.
+//!
+//! The chain secret key is fixed, so that signatures for any round can be produced locally.
+//! It MUST NOT be used outside of tests, as anyone can derive these signatures.
+//!
+//! ```rust
+//! use tlock::mock::MockChain;
+//!
+//! let chain = MockChain::new_g1();
+//! let msg = vec![8u8; 16];
+//!
+//! let mut encrypted = vec![];
+//! tlock::encrypt(&mut encrypted, msg.as_slice(), &chain.public_key(), 1000).unwrap();
+//!
+//! let mut decrypted = vec![];
+//! tlock::decrypt(&mut decrypted, encrypted.as_slice(), &chain.signature(1000)).unwrap();
+//! assert_eq!(decrypted, msg);
+//! ```
+
+use ark_bls12_381::{Fr as ScalarField, G1Affine, G2Affine};
+use ark_ec::AffineRepr;
+use ark_ff::PrimeField;
+use sha2::{Digest, Sha256};
+
+use crate::{ibe::GAffine, round_id, Round};
+
+/// Genesis time of mock chains, 2023-08-23T15:09:27Z, same as quicknet.
+pub const GENESIS_TIME: u64 = 1692803367;
+/// Period of mock chains, in seconds.
+pub const PERIOD: u64 = 3;
+
+/// drand chain with a fixed secret key.
+pub struct MockChain {
+    secret: ScalarField,
+    public_key: GAffine,
+}
+
+impl MockChain {
+    /// Chain with its public key on G1, and signatures on G2.
+    pub fn new_g1() -> Self {
+        Self::new(GAffine::G1Affine(G1Affine::generator()))
+    }
+
+    /// Chain with its public key on G2, and signatures on G1, such as quicknet.
+    pub fn new_g2() -> Self {
+        Self::new(GAffine::G2Affine(G2Affine::generator()))
+    }
+
+    fn new(generator: GAffine) -> Self {
+        let secret =
+            ScalarField::from_le_bytes_mod_order(&Sha256::digest(b"tlock mock chain secret"));
+        Self {
+            secret,
+            public_key: generator.mul(secret),
+        }
+    }
+
+    /// Compressed public key.
+    pub fn public_key(&self) -> Vec<u8> {
+        self.public_key.to_compressed().unwrap()
+    }
+
+    /// Compressed signature for `round`.
+    pub fn signature(&self, round: impl Into<Round>) -> Vec<u8> {
+        self.public_key
+            .hash_to_curve(&round_id(round.into()))
+            .unwrap()
+            .mul(self.secret)
+            .to_compressed()
+            .unwrap()
+    }
+
+    /// Chain hash. It is derived from the public key, and does not follow drand chain info hashing.
+    pub fn hash(&self) -> Vec<u8> {
+        Sha256::digest(self.public_key()).to_vec()
+    }
+
+    /// Genesis time, as a unix timestamp.
+    pub fn genesis_time(&self) -> u64 {
+        GENESIS_TIME
+    }
+
+    /// Period between rounds, in seconds.
+    pub fn period(&self) -> u64 {
+        PERIOD
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mock_chain() {
+        for chain in [MockChain::new_g1(), MockChain::new_g2()] {
+            for round in [1, 1000, u64::MAX] {
+                let signature = chain.signature(round);
+                crate::verify_beacon(&chain.public_key(), round, &signature).unwrap();
+
+                let msg = vec![8u8; 16];
+                let mut encrypted = vec![];
+                crate::encrypt(&mut encrypted, msg.as_slice(), &chain.public_key(), round).unwrap();
+                let mut decrypted = vec![];
+                crate::decrypt(&mut decrypted, encrypted.as_slice(), &signature).unwrap();
+                assert_eq!(decrypted, msg);
+            }
+            assert!(
+                crate::verify_beacon(&chain.public_key(), 1001, &chain.signature(1000)).is_err()
+            );
+        }
+    }
+}
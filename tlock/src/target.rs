@@ -0,0 +1,317 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::{Round, TLockError};
+
+/// Point in time a message is encrypted to, as provided by a user.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TargetTime {
+    /// A specific round, such as `123`.
+    Round(Round),
+    /// A duration from now, such as `30s` or `1h30m`.
+    Duration(Duration),
+    /// A unix timestamp, parsed from a date such as `2023-06-28T21:30:22Z`.
+    Timestamp(u64),
+}
+
+impl TargetTime {
+    /// Round emitted at the target time, on a chain starting at `genesis_time` with a beacon every `period` seconds.
+    ///
+    /// Durations are resolved against the local system time.
+    pub fn round(&self, genesis_time: u64, period: u64) -> Round {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        self.round_at(genesis_time, period, now)
+    }
+
+    /// Same as [`TargetTime::round`], with durations resolved against `now`, a unix timestamp.
+    ///
+    /// The returned round is the latest one emitted at or before the target time.
+    /// Targets before genesis resolve to round 1, targets beyond `u64::MAX` seconds clamp to [`max_round`].
+    pub fn round_at(&self, genesis_time: u64, period: u64, now: u64) -> Round {
+        let time = match self {
+            Self::Round(round) => return *round,
+            Self::Duration(duration) => now.saturating_add(duration.as_secs()),
+            Self::Timestamp(timestamp) => *timestamp,
+        };
+        Round::new((time.saturating_sub(genesis_time) / period.max(1)).saturating_add(1))
+    }
+}
+
+/// Latest round of a chain starting at `genesis_time` with a beacon every `period` seconds, whose emission time is representable as a `u64` unix timestamp.
+///
+/// Targets further in the future are clamped to this round by [`TargetTime::round_at`].
+///
+/// ```rust
+/// use tlock::{max_round, Round};
+///
+/// assert_eq!(max_round(0, 1), Round::MAX);
+/// assert_eq!(max_round(1692803367, 3), Round::new((u64::MAX - 1692803367) / 3 + 1));
+/// ```
+pub fn max_round(genesis_time: u64, period: u64) -> Round {
+    TargetTime::Timestamp(u64::MAX).round_at(genesis_time, period, 0)
+}
+
+/// Parse a target time, using the same formats as `age-plugin-tlock`.
+///
+/// It can be a specific round (`123`), a duration (`30s`, `2h`, `1h30m`, with units `s`, `m`, `h`, and `d`), or a date.
+/// Dates follow RFC 3339 (`2023-06-28T21:30:22Z`, `2023-06-28T23:30:22+02:00`). A space can replace `T`, and dates without an offset are UTC (`2023-06-28 21:30:22`).
+///
+/// ```rust
+/// use std::time::Duration;
+/// use tlock::{parse_target, TargetTime};
+///
+/// assert_eq!(parse_target("123").unwrap(), TargetTime::Round(123.into()));
+/// assert_eq!(parse_target("30s").unwrap(), TargetTime::Duration(Duration::from_secs(30)));
+/// assert_eq!(parse_target("2023-06-28 21:30:22").unwrap(), TargetTime::Timestamp(1687987822));
+/// ```
+pub fn parse_target(s: &str) -> Result<TargetTime, TLockError> {
+    let invalid = || TLockError::InvalidTarget(s.to_owned());
+    let s = s.trim();
+    if !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit()) {
+        return s
+            .parse::<u64>()
+            .map(|round| TargetTime::Round(round.into()))
+            .map_err(|_| invalid());
+    }
+    if let Some(duration) = parse_duration(s) {
+        return Ok(TargetTime::Duration(duration));
+    }
+    parse_date(s).map(TargetTime::Timestamp).ok_or_else(invalid)
+}
+
+fn parse_duration(s: &str) -> Option<Duration> {
+    if s.is_empty() {
+        return None;
+    }
+    let mut secs: u64 = 0;
+    let mut rest = s;
+    while !rest.is_empty() {
+        let digits = rest.bytes().take_while(u8::is_ascii_digit).count();
+        if digits == 0 {
+            return None;
+        }
+        let value: u64 = rest[..digits].parse().ok()?;
+        let unit = match rest.as_bytes().get(digits)? {
+            b's' => 1,
+            b'm' => 60,
+            b'h' => 60 * 60,
+            b'd' => 24 * 60 * 60,
+            _ => return None,
+        };
+        secs = secs.checked_add(value.checked_mul(unit)?)?;
+        rest = &rest[digits + 1..];
+    }
+    Some(Duration::from_secs(secs))
+}
+
+fn parse_date(s: &str) -> Option<u64> {
+    let b = s.as_bytes();
+    if b.len() < 19
+        || b[4] != b'-'
+        || b[7] != b'-'
+        || !matches!(b[10], b'T' | b't' | b' ')
+        || b[13] != b':'
+        || b[16] != b':'
+    {
+        return None;
+    }
+    let number = |range: std::ops::Range<usize>| -> Option<i64> {
+        let digits = s.get(range)?;
+        if !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        digits.parse().ok()
+    };
+    let (year, month, day) = (number(0..4)?, number(5..7)?, number(8..10)?);
+    let (hour, minute, second) = (number(11..13)?, number(14..16)?, number(17..19)?);
+    if !(1..=12).contains(&month)
+        || day < 1
+        || day > days_in_month(year, month)
+        || hour > 23
+        || minute > 59
+        || second > 59
+    {
+        return None;
+    }
+
+    // Fractional seconds are accepted and ignored
+    let mut rest = &s[19..];
+    if let Some(fraction) = rest.strip_prefix('.') {
+        let digits = fraction.bytes().take_while(u8::is_ascii_digit).count();
+        if digits == 0 {
+            return None;
+        }
+        rest = &fraction[digits..];
+    }
+    let offset = match rest {
+        "" | "Z" | "z" => 0,
+        _ => {
+            let b = rest.as_bytes();
+            if b.len() != 6 || b[3] != b':' {
+                return None;
+            }
+            let sign = match b[0] {
+                b'+' => 1,
+                b'-' => -1,
+                _ => return None,
+            };
+            let hours: i64 = rest[1..3].parse().ok()?;
+            let minutes: i64 = rest[4..6].parse().ok()?;
+            if hours > 23 || minutes > 59 {
+                return None;
+            }
+            sign * (hours * 3600 + minutes * 60)
+        }
+    };
+
+    let timestamp =
+        days_from_civil(year, month, day) * 86400 + hour * 3600 + minute * 60 + second - offset;
+    u64::try_from(timestamp).ok()
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Days since 1970-01-01 in the proleptic Gregorian calendar.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // quicknet genesis time and period
+    const GENESIS_TIME: u64 = 1692803367;
+    const PERIOD: u64 = 3;
+
+    #[test]
+    fn test_parse_target_round() {
+        assert_eq!(parse_target("0").unwrap(), TargetTime::Round(0.into()));
+        assert_eq!(parse_target("123").unwrap(), TargetTime::Round(123.into()));
+        assert_eq!(
+            parse_target("123").unwrap().round(GENESIS_TIME, PERIOD),
+            Round::new(123)
+        );
+    }
+
+    #[test]
+    fn test_parse_target_duration() {
+        for (s, secs) in [
+            ("30s", 30),
+            ("2m", 120),
+            ("2h", 7200),
+            ("1d", 86400),
+            ("1h30m", 5400),
+        ] {
+            assert_eq!(
+                parse_target(s).unwrap(),
+                TargetTime::Duration(Duration::from_secs(secs))
+            );
+        }
+        let target = parse_target("30s").unwrap();
+        assert_eq!(
+            target.round_at(GENESIS_TIME, PERIOD, GENESIS_TIME),
+            Round::new(11)
+        );
+    }
+
+    #[test]
+    fn test_parse_target_date() {
+        // 2023-08-24T00:00:00Z is 1692835200
+        for s in [
+            "2023-08-24T00:00:00Z",
+            "2023-08-24t00:00:00z",
+            "2023-08-24 00:00:00",
+            "2023-08-24T00:00:00.123Z",
+            "2023-08-24T02:00:00+02:00",
+            "2023-08-23T22:30:00-01:30",
+        ] {
+            assert_eq!(
+                parse_target(s).unwrap(),
+                TargetTime::Timestamp(1692835200),
+                "{s}"
+            );
+        }
+        assert_eq!(
+            parse_target("2024-02-29T12:00:00Z").unwrap(),
+            TargetTime::Timestamp(1709208000)
+        );
+        let target = parse_target("2023-08-24T00:00:00Z").unwrap();
+        assert_eq!(
+            target.round_at(GENESIS_TIME, PERIOD, 0),
+            Round::new((1692835200 - GENESIS_TIME) / PERIOD + 1)
+        );
+        assert_eq!(
+            parse_target("1970-01-01T00:00:00Z")
+                .unwrap()
+                .round_at(GENESIS_TIME, PERIOD, 0),
+            Round::new(1)
+        );
+    }
+
+    #[test]
+    fn test_round_at_overflow() {
+        assert_eq!(
+            TargetTime::Timestamp(u64::MAX).round_at(0, 1, 0),
+            Round::MAX
+        );
+        assert_eq!(
+            TargetTime::Duration(Duration::MAX).round_at(0, 1, u64::MAX),
+            Round::MAX
+        );
+        assert_eq!(
+            TargetTime::Duration(Duration::from_secs(u64::MAX)).round_at(
+                GENESIS_TIME,
+                PERIOD,
+                GENESIS_TIME
+            ),
+            max_round(GENESIS_TIME, PERIOD)
+        );
+        assert_eq!(
+            TargetTime::Timestamp(u64::MAX - 1).round_at(0, 2, 0),
+            Round::new(u64::MAX / 2 + 1)
+        );
+        assert_eq!(max_round(0, 0), Round::MAX);
+        assert_eq!(max_round(u64::MAX, PERIOD), Round::new(1));
+    }
+
+    #[test]
+    fn test_parse_target_invalid() {
+        for s in [
+            "",
+            "abc",
+            "-1",
+            "30x",
+            "s",
+            "1h30",
+            "99999999999999999999",
+            "99999999999999999999s",
+            "2023-13-01T00:00:00Z",
+            "2023-02-29T00:00:00Z",
+            "2023-08-24T24:00:00Z",
+            "2023-08-24T00:00:00+2:00",
+            "2023-08-24T00:00:00.Z",
+            "2023-08-24",
+            "1969-12-31T23:59:59Z",
+        ] {
+            assert!(
+                matches!(parse_target(s), Err(TLockError::InvalidTarget(_))),
+                "{s}"
+            );
+        }
+    }
+}
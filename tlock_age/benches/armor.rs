@@ -0,0 +1,79 @@
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+
+#[cfg(unix)]
+use pprof::criterion::{Output, PProfProfiler};
+
+const MSG_LEN: usize = 1024 * 1024;
+
+fn encrypt(msg: &[u8], chain_hash: &[u8], pk_bytes: &[u8], armored: bool) -> Vec<u8> {
+    let round = 1000;
+    if !armored {
+        let mut encrypted = vec![];
+        tlock_age::encrypt(&mut encrypted, msg, chain_hash, pk_bytes, round).unwrap();
+        return encrypted;
+    }
+    let mut writer = tlock_age::armor::ArmoredWriter::wrap_output(vec![]).unwrap();
+    tlock_age::encrypt(&mut writer, msg, chain_hash, pk_bytes, round).unwrap();
+    writer.finish().unwrap()
+}
+
+fn criterion_benchmark(c: &mut Criterion) {
+    let chain_hash =
+        hex::decode("7672797f548f3f4748ac4bf3352fc6c6b6468c9ad40ad456a397545c6e2df5bf").unwrap();
+    let pk_bytes = hex::decode("8200fc249deb0148eb918d6e213980c5d01acd7fc251900d9260136da3b54836ce125172399ddc69c4e3e11429b62c11").unwrap();
+    let signature = hex::decode("a4721e6c3eafcd823f138cd29c6c82e8c5149101d0bb4bafddbac1c2d1fe3738895e4e21dd4b8b41bf007046440220910bb1cdb91f50a84a0d7f33ff2e8577aa62ac64b35a291a728a9db5ac91e06d1312b48a376138d77b4d6ad27c24221afe").unwrap();
+
+    let mut msg = vec![0u8; MSG_LEN];
+    msg.fill_with(rand::random);
+
+    let mut group = c.benchmark_group("lock 1 MiB with TLE + age");
+    group.throughput(Throughput::Bytes(MSG_LEN as u64));
+    for (name, armored) in [("binary", false), ("armored", true)] {
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                let encrypted = encrypt(
+                    black_box(&msg),
+                    black_box(&chain_hash),
+                    black_box(&pk_bytes),
+                    armored,
+                );
+                assert_ne!(encrypted.len(), 0);
+            })
+        });
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("unlock 1 MiB with TLE + age");
+    group.throughput(Throughput::Bytes(MSG_LEN as u64));
+    for (name, armored) in [("binary", false), ("armored", true)] {
+        let encrypted = encrypt(&msg, &chain_hash, &pk_bytes, armored);
+        group.bench_function(name, |b| {
+            b.iter_batched_ref(
+                || Vec::with_capacity(MSG_LEN),
+                |decrypted| {
+                    tlock_age::decrypt(
+                        black_box(&mut *decrypted),
+                        black_box(encrypted.as_slice()),
+                        black_box(&chain_hash),
+                        black_box(&signature),
+                    )
+                    .unwrap();
+                    assert_eq!(decrypted.len(), MSG_LEN);
+                },
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+#[cfg(unix)]
+criterion_group!(
+    name = benches;
+    config = Criterion::default()
+        .with_profiler(PProfProfiler::new(100, Output::Flamegraph(None)));
+    targets = criterion_benchmark
+);
+#[cfg(not(unix))]
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
@@ -1,6 +1,49 @@
-use std::io::{Result, Write};
+use std::io::{self, Result, Write};
 
-use crate::TLockAgeError;
+use crate::{
+    dearmor::{BEGIN_MARKER, COLUMNS_PER_LINE, END_MARKER},
+    TLockAgeError,
+};
+
+const BEGIN_MARKER_LEN: usize = BEGIN_MARKER.len();
+const END_MARKER_LEN: usize = END_MARKER.len();
+// age uses the platform line ending when armoring
+#[cfg(windows)]
+const LINE_ENDING_LEN: usize = 2;
+#[cfg(not(windows))]
+const LINE_ENDING_LEN: usize = 1;
+
+/// Size of the armored output for `binary_len` bytes of binary age output.
+///
+/// Armor wraps base64 in lines of 64 columns, between a begin and an end marker.
+/// This helps checking an armored file fits a size-limited medium before encrypting.
+///
+/// ```rust
+/// let binary_len = 300;
+/// // a QR code holds up to 2953 bytes
+/// assert!(tlock_age::armor::armored_len(binary_len) <= 2953);
+/// ```
+pub const fn armored_len(binary_len: usize) -> usize {
+    let encoded = binary_len.div_ceil(3) * 4;
+    let lines = if encoded == 0 {
+        0
+    } else {
+        (encoded - 1) / COLUMNS_PER_LINE
+    };
+    BEGIN_MARKER_LEN + END_MARKER_LEN + encoded + (lines + 3) * LINE_ENDING_LEN
+}
+
+/// Number of bytes armoring adds to `binary_len` bytes of binary age output, that is `armored_len(binary_len) - binary_len`.
+///
+/// The overhead is about a third of the input from base64, plus line endings and the begin and end markers.
+///
+/// ```rust
+/// assert_eq!(tlock_age::armor::expanded_len(0), tlock_age::armor::armored_len(0));
+/// assert!(tlock_age::armor::expanded_len(3000) > 1000);
+/// ```
+pub const fn expanded_len(binary_len: usize) -> usize {
+    armored_len(binary_len) - binary_len
+}
 
 /// Writer that applies the age ASCII armor format.
 pub struct ArmoredWriter<W: Write> {
@@ -15,11 +58,16 @@ impl<W: Write> ArmoredWriter<W> {
         Ok(Self { inner })
     }
 
-    /// Writes the end marker of the age file, if armoring was enabled.
+    /// Writes the end marker of the age file, if armoring was enabled, and flushes the output.
     ///
     /// You MUST call finish when you are done writing, in order to `finish` the armoring process. Failing to call `finish` will result in a truncated file that that will fail to decrypt.
+    /// Errors writing the end marker or flushing the output are returned with their original kind, rather than left to a buffered writer drop which would ignore them.
     pub fn finish(self) -> Result<W> {
-        self.inner.finish()
+        let context =
+            |err: io::Error| io::Error::new(err.kind(), format!("cannot finish armor: {err}"));
+        let mut inner = self.inner.finish().map_err(context)?;
+        inner.flush().map_err(context)?;
+        Ok(inner)
     }
 }
 
@@ -32,3 +80,68 @@ impl<W: Write> Write for ArmoredWriter<W> {
         self.inner.flush()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_armored_len() {
+        for len in [0, 1, 2, 3, 47, 48, 49, 96, 1000, 10_000] {
+            let mut writer = ArmoredWriter::wrap_output(vec![]).unwrap();
+            writer.write_all(&vec![0u8; len]).unwrap();
+            let armored = writer.finish().unwrap();
+            assert_eq!(armored_len(len), armored.len(), "binary length {len}");
+            assert_eq!(
+                expanded_len(len),
+                armored.len() - len,
+                "binary length {len}"
+            );
+        }
+    }
+
+    /// Writer accepting `capacity` bytes, and failing to flush.
+    struct FailingWriter {
+        written: Vec<u8>,
+        capacity: usize,
+    }
+
+    impl Write for FailingWriter {
+        fn write(&mut self, buf: &[u8]) -> Result<usize> {
+            if self.written.len() + buf.len() > self.capacity {
+                return Err(io::Error::new(io::ErrorKind::WriteZero, "disk full"));
+            }
+            self.written.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> Result<()> {
+            Err(io::Error::other("flush failed"))
+        }
+    }
+
+    #[test]
+    fn test_finish_error() {
+        // the final flush fails
+        let mut writer = ArmoredWriter::wrap_output(FailingWriter {
+            written: vec![],
+            capacity: usize::MAX,
+        })
+        .unwrap();
+        writer.write_all(&[0u8; 100]).unwrap();
+        let err = writer.finish().err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::Other);
+        assert!(err.to_string().contains("flush failed"), "{err}");
+
+        // the end marker does not fit
+        let mut writer = ArmoredWriter::wrap_output(FailingWriter {
+            written: vec![],
+            capacity: armored_len(100) - END_MARKER_LEN,
+        })
+        .unwrap();
+        writer.write_all(&[0u8; 100]).unwrap();
+        let err = writer.finish().err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::WriteZero);
+        assert!(err.to_string().contains("disk full"), "{err}");
+    }
+}
@@ -0,0 +1,457 @@
+//! Detection and extraction of armored age files, whether the `armor` feature is enabled or not.
+
+use std::io::{self, BufRead, Read, Result};
+#[cfg(feature = "async")]
+use std::{
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+
+#[cfg(feature = "async")]
+use futures::io::AsyncRead;
+
+pub(crate) const BEGIN_MARKER: &[u8] = b"-----BEGIN AGE ENCRYPTED FILE-----";
+pub(crate) const END_MARKER: &[u8] = b"-----END AGE ENCRYPTED FILE-----";
+const BINARY_PREFIX: &[u8] = b"age-encryption.org/";
+/// Width of armor lines, as expected by [`age::armor::ArmoredReader`].
+pub(crate) const COLUMNS_PER_LINE: usize = 64;
+/// Maximum number of bytes scanned for the begin marker, so that inputs without armor are not read to the end.
+pub(crate) const MAX_PREAMBLE_LEN: usize = 64 * 1024;
+/// Room left after [`MAX_PREAMBLE_LEN`] for the begin marker line, with quoting and indentation.
+const MAX_MARKER_LINE_LEN: usize = 256;
+
+#[derive(Clone, Copy, PartialEq)]
+enum State {
+    Start,
+    Binary,
+    Before,
+    Inside,
+    Done,
+}
+
+/// Extraction of an armored age file from surrounding text, one input line at a time.
+///
+/// Lines before the begin marker and after the end marker are ignored, and email quoting (`>`) and indentation are stripped from armor lines.
+/// Other tools may add header lines such as `Comment: ...` after the begin marker, or wrap base64 differently. Header lines are dropped, and base64 is rewrapped to [`COLUMNS_PER_LINE`] columns.
+/// Binary age files are passed through untouched.
+/// The output is meant to be parsed by [`age::armor::ArmoredReader`].
+struct Dearmor {
+    state: State,
+    /// bytes read before the begin marker
+    preamble_len: usize,
+    /// base64 read inside the armor, not yet written to a full line
+    base64: Vec<u8>,
+}
+
+impl Dearmor {
+    fn new() -> Self {
+        Self {
+            state: State::Start,
+            preamble_len: 0,
+            base64: vec![],
+        }
+    }
+
+    /// Number of bytes that can still be read looking for the begin marker line, if it has not been found yet.
+    fn preamble_left(&self) -> Option<usize> {
+        match self.state {
+            State::Start | State::Before => {
+                Some(MAX_PREAMBLE_LEN.saturating_sub(self.preamble_len) + MAX_MARKER_LINE_LEN)
+            }
+            _ => None,
+        }
+    }
+
+    /// Fail if `pending` bytes, not yet split into lines, exceed what can be read looking for the begin marker line.
+    #[cfg(feature = "async")]
+    fn check_preamble(&self, pending: usize) -> Result<()> {
+        match self.preamble_left() {
+            Some(left) if pending > left => Err(no_armor()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether the input is a binary age file, to be passed through from now on.
+    fn is_binary(&self) -> bool {
+        self.state == State::Binary
+    }
+
+    fn is_done(&self) -> bool {
+        self.state == State::Done
+    }
+
+    /// Process a raw input line, including its line ending, appending the lines to output to `out`.
+    ///
+    /// Fails once more than [`MAX_PREAMBLE_LEN`] bytes are read without finding the begin marker.
+    fn line(&mut self, raw: &[u8], out: &mut Vec<u8>) -> Result<()> {
+        if self.state == State::Start && raw.starts_with(BINARY_PREFIX) {
+            self.state = State::Binary;
+            out.extend_from_slice(raw);
+            return Ok(());
+        }
+
+        let stripped = strip_line(raw);
+        match self.state {
+            State::Start | State::Before if stripped == BEGIN_MARKER => {
+                self.state = State::Inside;
+                out.extend_from_slice(stripped);
+                out.push(b'\n');
+            }
+            State::Start | State::Before => {
+                self.state = State::Before;
+                self.preamble_len += raw.len();
+                if self.preamble_len > MAX_PREAMBLE_LEN {
+                    return Err(no_armor());
+                }
+            }
+            State::Inside if stripped == END_MARKER => {
+                self.flush_base64(out);
+                self.state = State::Done;
+                out.extend_from_slice(stripped);
+                out.push(b'\n');
+            }
+            // base64 has no colon, these are armor headers
+            State::Inside if stripped.contains(&b':') => {}
+            State::Inside => {
+                self.base64
+                    .extend(stripped.iter().filter(|b| !b.is_ascii_whitespace()));
+                while self.base64.len() >= COLUMNS_PER_LINE {
+                    out.extend(self.base64.drain(..COLUMNS_PER_LINE));
+                    out.push(b'\n');
+                }
+            }
+            State::Binary | State::Done => {}
+        }
+        Ok(())
+    }
+
+    /// Process the end of the input, failing if no armor was found.
+    fn end(&mut self, out: &mut Vec<u8>) -> Result<()> {
+        match self.state {
+            State::Start | State::Before => Err(no_armor()),
+            _ => {
+                self.flush_base64(out);
+                self.state = State::Done;
+                Ok(())
+            }
+        }
+    }
+
+    /// Writes the last, possibly partial, base64 line.
+    fn flush_base64(&mut self, out: &mut Vec<u8>) {
+        if !self.base64.is_empty() {
+            out.append(&mut self.base64);
+            out.push(b'\n');
+        }
+    }
+}
+
+/// Reader extracting an armored age file from surrounding text, as described by [`Dearmor`].
+pub(crate) struct LenientReader<R: BufRead> {
+    inner: R,
+    dearmor: Dearmor,
+    line: Vec<u8>,
+    pos: usize,
+}
+
+impl<R: BufRead> LenientReader<R> {
+    pub(crate) fn new(inner: R) -> Self {
+        Self {
+            inner,
+            dearmor: Dearmor::new(),
+            line: vec![],
+            pos: 0,
+        }
+    }
+
+    /// Fills `self.line` with the next lines to output, leaving it empty at the end of the input.
+    fn next_line(&mut self) -> Result<()> {
+        self.line.clear();
+        self.pos = 0;
+        while self.line.is_empty() && !self.dearmor.is_done() {
+            let mut raw = vec![];
+            // a line longer than what is left to scan is read partially, and fails
+            let read = match self.dearmor.preamble_left() {
+                Some(left) => (&mut self.inner)
+                    .take(left as u64)
+                    .read_until(b'\n', &mut raw)?,
+                None => self.inner.read_until(b'\n', &mut raw)?,
+            };
+            if read == 0 {
+                return self.dearmor.end(&mut self.line);
+            }
+            self.dearmor.line(&raw, &mut self.line)?;
+            if self.dearmor.is_binary() {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<R: BufRead> Read for LenientReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        if self.pos == self.line.len() {
+            if self.dearmor.is_binary() {
+                return self.inner.read(buf);
+            }
+            self.next_line()?;
+        }
+        let n = buf.len().min(self.line.len() - self.pos);
+        buf[..n].copy_from_slice(&self.line[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+/// Async counterpart of [`LenientReader`].
+#[cfg(feature = "async")]
+pub(crate) struct AsyncLenientReader<R: AsyncRead + Unpin> {
+    inner: R,
+    dearmor: Dearmor,
+    /// input read after the last complete line
+    pending: Vec<u8>,
+    line: Vec<u8>,
+    pos: usize,
+}
+
+#[cfg(feature = "async")]
+impl<R: AsyncRead + Unpin> AsyncLenientReader<R> {
+    pub(crate) fn new(inner: R) -> Self {
+        Self {
+            inner,
+            dearmor: Dearmor::new(),
+            pending: vec![],
+            line: vec![],
+            pos: 0,
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+impl<R: AsyncRead + Unpin> AsyncRead for AsyncLenientReader<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<Result<usize>> {
+        let this = self.get_mut();
+        loop {
+            if this.pos < this.line.len() {
+                let n = buf.len().min(this.line.len() - this.pos);
+                buf[..n].copy_from_slice(&this.line[this.pos..this.pos + n]);
+                this.pos += n;
+                return Poll::Ready(Ok(n));
+            }
+            this.line.clear();
+            this.pos = 0;
+            if this.dearmor.is_binary() {
+                return Pin::new(&mut this.inner).poll_read(cx, buf);
+            }
+            if this.dearmor.is_done() {
+                return Poll::Ready(Ok(0));
+            }
+
+            let mut chunk = [0u8; 8 * 1024];
+            let n = ready!(Pin::new(&mut this.inner).poll_read(cx, &mut chunk))?;
+            if n == 0 {
+                let last = std::mem::take(&mut this.pending);
+                if !last.is_empty() {
+                    this.dearmor.line(&last, &mut this.line)?;
+                }
+                if !this.dearmor.is_binary() {
+                    this.dearmor.end(&mut this.line)?;
+                }
+                continue;
+            }
+            this.pending.extend_from_slice(&chunk[..n]);
+            while let Some(end) = this.pending.iter().position(|b| *b == b'\n') {
+                let raw: Vec<u8> = this.pending.drain(..=end).collect();
+                this.dearmor.line(&raw, &mut this.line)?;
+                if this.dearmor.is_binary() {
+                    this.line.append(&mut this.pending);
+                    break;
+                }
+                if this.dearmor.is_done() {
+                    break;
+                }
+            }
+            this.dearmor.check_preamble(this.pending.len())?;
+        }
+    }
+}
+
+/// Wraps a reader that may contain an armored age file, possibly surrounded by text, or a binary one.
+pub(crate) fn reader<R: Read>(
+    src: R,
+) -> age::armor::ArmoredReader<io::BufReader<LenientReader<io::BufReader<R>>>> {
+    age::armor::ArmoredReader::new(LenientReader::new(io::BufReader::new(src)))
+}
+
+/// Async counterpart of [`reader`].
+#[cfg(feature = "async")]
+pub(crate) fn async_reader<R: AsyncRead + Unpin>(
+    src: R,
+) -> age::armor::ArmoredReader<futures::io::BufReader<AsyncLenientReader<R>>> {
+    age::armor::ArmoredReader::from_async_reader(AsyncLenientReader::new(src))
+}
+
+fn no_armor() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("no armored age file found in the first {MAX_PREAMBLE_LEN} bytes"),
+    )
+}
+
+/// Removes whitespace and email quoting (`>`) around a line.
+fn strip_line(line: &[u8]) -> &[u8] {
+    let start = line
+        .iter()
+        .position(|b| !b.is_ascii_whitespace() && *b != b'>')
+        .unwrap_or(line.len());
+    let end = line
+        .iter()
+        .rposition(|b| !b.is_ascii_whitespace())
+        .map_or(start, |end| end + 1);
+    &line[start..end.max(start)]
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    #[cfg(feature = "async")]
+    use futures::io::AsyncReadExt;
+
+    use super::*;
+
+    fn armor(data: &[u8]) -> String {
+        let mut writer =
+            age::armor::ArmoredWriter::wrap_output(vec![], age::armor::Format::AsciiArmor).unwrap();
+        writer.write_all(data).unwrap();
+        String::from_utf8(writer.finish().unwrap()).unwrap()
+    }
+
+    fn dearmor<R: Read>(src: R) -> Result<Vec<u8>> {
+        let mut reader = reader(src);
+        let mut data = vec![];
+        reader.read_to_end(&mut data)?;
+        Ok(data)
+    }
+
+    #[test]
+    fn test_lenient_reader() {
+        let data = b"age-encryption.org/v1\n".repeat(20);
+        let armored = armor(&data);
+
+        // plain armor and binary input are unchanged
+        assert_eq!(dearmor(armored.as_bytes()).unwrap(), data);
+        assert_eq!(dearmor(data.as_slice()).unwrap(), data);
+
+        // armor surrounded by text
+        let wrapped = format!("Hi,\n\nhere is the file:\n\n{armored}\nCheers,\n-- \nAlice\n");
+        assert_eq!(dearmor(wrapped.as_bytes()).unwrap(), data);
+
+        // armor quoted in an email reply
+        let quoted = armored
+            .lines()
+            .map(|line| format!("> > {line}\r\n"))
+            .collect::<String>();
+        let reply = format!("On Monday, Alice wrote:\n> Hi,\n>\n{quoted}>\n> Cheers\n\nThanks!\n");
+        assert_eq!(dearmor(reply.as_bytes()).unwrap(), data);
+
+        // armor headers, and base64 wrapped at another width with stray whitespace
+        let base64 = armored
+            .lines()
+            .filter(|line| !line.starts_with("-----"))
+            .collect::<String>();
+        let rewrapped = base64
+            .as_bytes()
+            .chunks(76)
+            .map(|line| {
+                let (start, end) = std::str::from_utf8(line).unwrap().split_at(10);
+                format!("\t{start}  {end}\r\n")
+            })
+            .collect::<String>();
+        // made up headers, not produced by a specific tool
+        let other_tool = format!(
+            "-----BEGIN AGE ENCRYPTED FILE-----\nComment: some comment\nVersion: 1\n\n{rewrapped}-----END AGE ENCRYPTED FILE-----\n"
+        );
+        assert_eq!(dearmor(other_tool.as_bytes()).unwrap(), data);
+
+        // no armor to be found
+        assert!(dearmor(b"Hi,\nthere is no file here\n".as_slice()).is_err());
+        assert!(dearmor(b"".as_slice()).is_err());
+
+        // text before the armor is scanned up to a limit, whether it has lines or not
+        let text = "some text\n".repeat(MAX_PREAMBLE_LEN / 10);
+        assert_eq!(
+            dearmor(format!("{text}{armored}").as_bytes()).unwrap(),
+            data
+        );
+        for long in [
+            format!("{text}some text\n"),
+            "x".repeat(MAX_PREAMBLE_LEN + 1),
+        ] {
+            let input = format!("{long}{armored}");
+            assert!(dearmor(input.as_bytes()).is_err());
+            // reading stops right after the limit
+            let mut src = input.as_bytes();
+            assert!(dearmor(&mut src).is_err());
+            assert!(input.len() - src.len() <= MAX_PREAMBLE_LEN + 8 * 1024);
+            #[cfg(feature = "async")]
+            assert!(dearmor_async(input.as_bytes()).is_err());
+        }
+
+        #[cfg(feature = "async")]
+        for input in [
+            armored.as_bytes(),
+            data.as_slice(),
+            wrapped.as_bytes(),
+            reply.as_bytes(),
+            other_tool.as_bytes(),
+        ] {
+            assert_eq!(dearmor_async(input).unwrap(), data);
+            // reads of a single byte, lines span several reads
+            let mut lenient = AsyncLenientReader::new(OneByte(input));
+            let mut lines = vec![];
+            futures::executor::block_on(lenient.read_to_end(&mut lines)).unwrap();
+            assert_eq!(lines, dearmor_lines(input));
+        }
+        #[cfg(feature = "async")]
+        assert!(dearmor_async(b"Hi,\nthere is no file here\n".as_slice()).is_err());
+    }
+
+    #[cfg(feature = "async")]
+    fn dearmor_lines(src: &[u8]) -> Vec<u8> {
+        let mut lines = vec![];
+        LenientReader::new(src).read_to_end(&mut lines).unwrap();
+        lines
+    }
+
+    #[cfg(feature = "async")]
+    fn dearmor_async(src: &[u8]) -> Result<Vec<u8>> {
+        let mut reader = async_reader(src);
+        let mut data = vec![];
+        futures::executor::block_on(reader.read_to_end(&mut data))?;
+        Ok(data)
+    }
+
+    /// Async reader returning a single byte per read.
+    #[cfg(feature = "async")]
+    struct OneByte<'a>(&'a [u8]);
+
+    #[cfg(feature = "async")]
+    impl AsyncRead for OneByte<'_> {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            buf: &mut [u8],
+        ) -> Poll<Result<usize>> {
+            let n = self.0.len().min(buf.len()).min(1);
+            buf[..n].copy_from_slice(&self.0[..n]);
+            self.0 = &self.0[n..];
+            Poll::Ready(Ok(n))
+        }
+    }
+}
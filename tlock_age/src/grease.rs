@@ -0,0 +1,130 @@
+//! Removal of the `grease-the-join` stanza age adds to every header.
+//!
+//! age does not allow to opt out of greasing. Instead, the header is rewritten as it is written, and its MAC recomputed.
+//! The payload key only depends on the file key and the payload nonce, so the payload is left untouched.
+
+use std::{
+    io::{self, Write},
+    sync::{Arc, Mutex},
+};
+
+use age::secrecy::ExposeSecret;
+use age_core::format::{FileKey, Stanza};
+use base64::{engine::general_purpose::STANDARD_NO_PAD as BASE64, Engine};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use zeroize::Zeroizing;
+
+/// Suffix of the tag of stanzas generated by age to grease the joint.
+const GREASE_TAG_SUFFIX: &[u8] = b"-grease";
+/// HKDF label of the header MAC key.
+const HEADER_KEY_LABEL: &[u8] = b"header";
+/// Prefix of the line holding the header MAC. The MAC covers the header up to and including it.
+const MAC_LINE_PREFIX: &[u8] = b"---";
+
+type SharedFileKey = Arc<Mutex<Option<Zeroizing<Vec<u8>>>>>;
+
+/// Wrap `recipient` and `dst` so that the header written to `dst` has no grease stanza.
+///
+/// The returned recipient has to be the only one passed to [`age::Encryptor`].
+pub(crate) fn strip<R: age::Recipient + Send + 'static, W: Write>(
+    recipient: R,
+    dst: W,
+) -> (Box<dyn age::Recipient + Send>, GreaselessWriter<W>) {
+    let file_key = SharedFileKey::default();
+    let recipient = KeyCapture {
+        inner: recipient,
+        file_key: file_key.clone(),
+    };
+    let writer = GreaselessWriter {
+        inner: dst,
+        file_key,
+        header: Some(vec![]),
+        line_start: 0,
+    };
+    (Box::new(recipient), writer)
+}
+
+/// Recipient keeping a copy of the file key it wraps, to recompute the header MAC.
+struct KeyCapture<R> {
+    inner: R,
+    file_key: SharedFileKey,
+}
+
+impl<R: age::Recipient> age::Recipient for KeyCapture<R> {
+    fn wrap_file_key(&self, file_key: &FileKey) -> Result<Vec<Stanza>, age::EncryptError> {
+        *self.file_key.lock().unwrap() = Some(Zeroizing::new(file_key.expose_secret().to_vec()));
+        self.inner.wrap_file_key(file_key)
+    }
+}
+
+/// Writer buffering the age header, to write it without grease stanzas. The payload is passed through.
+pub(crate) struct GreaselessWriter<W: Write> {
+    inner: W,
+    file_key: SharedFileKey,
+    /// Header being buffered, `None` once it has been written.
+    header: Option<Vec<u8>>,
+    line_start: usize,
+}
+
+impl<W: Write> GreaselessWriter<W> {
+    /// Drop grease stanzas from a complete header, and recompute its MAC.
+    fn rewrite(&self, header: &[u8]) -> io::Result<Vec<u8>> {
+        let mut rewritten = Vec::with_capacity(header.len());
+        let mut grease = false;
+        for line in header.split_inclusive(|b| *b == b'\n') {
+            if line.starts_with(MAC_LINE_PREFIX) {
+                rewritten.extend_from_slice(MAC_LINE_PREFIX);
+                break;
+            }
+            // a stanza spans from its argument line to the next one
+            if let Some(args) = line.strip_prefix(b"-> ") {
+                grease = args
+                    .split(|b| *b == b' ' || *b == b'\n')
+                    .next()
+                    .is_some_and(|tag| tag.ends_with(GREASE_TAG_SUFFIX));
+            }
+            if !grease {
+                rewritten.extend_from_slice(line);
+            }
+        }
+
+        let file_key = self.file_key.lock().unwrap().take().ok_or_else(|| {
+            io::Error::other("file key was not wrapped before writing the header")
+        })?;
+        let mac_key = age_core::primitives::hkdf(&[], HEADER_KEY_LABEL, &file_key);
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(&mac_key).expect("HMAC accepts keys of any size");
+        mac.update(&rewritten);
+        rewritten.push(b' ');
+        rewritten.extend_from_slice(BASE64.encode(mac.finalize().into_bytes()).as_bytes());
+        rewritten.push(b'\n');
+        Ok(rewritten)
+    }
+}
+
+impl<W: Write> Write for GreaselessWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let Some(header) = self.header.as_mut() else {
+            return self.inner.write(buf);
+        };
+        for (i, b) in buf.iter().enumerate() {
+            header.push(*b);
+            if *b != b'\n' {
+                continue;
+            }
+            if header[self.line_start..].starts_with(MAC_LINE_PREFIX) {
+                let header = self.header.take().unwrap_or_default();
+                let rewritten = self.rewrite(&header)?;
+                self.inner.write_all(&rewritten)?;
+                return Ok(i + 1);
+            }
+            self.line_start = header.len();
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
@@ -3,28 +3,153 @@ use std::{
     sync::{Arc, Mutex},
 };
 
-use age::secrecy::{ExposeSecret, Zeroize};
+use age::secrecy::ExposeSecret;
 use age_core::format::{FileKey, Stanza};
-
+use tlock::{ibe::GAffine, ChainHash, Round, TLockError};
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+/// Tag of tlock stanzas.
+///
+/// A tlock stanza is `-> tlock <round> <chain hash>`, where round is a decimal number and chain hash is hex encoded.
+/// This is the only layout produced by this crate and by [drand/tlock](https://github.com/drand/tlock) since their first release, there is no legacy layout to support.
+/// Stanzas with the tlock tag and any other shape are rejected as an invalid header, reported as
+/// [`TLockAgeError::UnsupportedStanza`](crate::TLockAgeError::UnsupportedStanza) by the functions of this crate.
 pub const STANZA_TAG: &str = "tlock";
 
+/// Tag of the optional stanza storing the estimated unlock time.
+///
+/// The stanza is `-> tlock-unlock-time <unix timestamp>`, with an empty body.
+/// It uses a distinct tag so that implementations unaware of it, including drand/tlock, ignore it.
+pub const UNLOCK_TIME_STANZA_TAG: &str = "tlock-unlock-time";
+
+/// Tag of the optional stanza marking a compressed payload.
+///
+/// The stanza is `-> tlock-compression <algorithm>`, with an empty body. [`COMPRESSION_ZSTD`] is the only supported algorithm.
+/// Implementations unaware of it, including drand/tlock, ignore the stanza and output the payload still compressed. Compression is specific to this crate.
+pub const COMPRESSION_STANZA_TAG: &str = "tlock-compression";
+
+/// Algorithm of payloads compressed with zstd, before age encryption.
+#[cfg_attr(not(any(feature = "internal", feature = "zstd")), allow(dead_code))]
+pub const COMPRESSION_ZSTD: &str = "zstd";
+
+/// Default maximum number of tlock stanzas processed per file.
+/// Each stanza costs a pairing to decrypt, bounding them protects against crafted files.
+pub const MAX_STANZAS: usize = 8;
+
 // Identity implements the age Identity interface. This is used to decrypt
 // data with the age Decrypt API.
+//
+// The signature is parsed once, and reused for every stanza.
+// It unlocks every ciphertext for its round, it is zeroized when the identity is dropped.
+//
+// Identity is Send and Sync, a single identity can decrypt many files concurrently.
+// Every call to `unwrap_stanzas` locks to record the file compression and failures, the pairing runs unlocked.
+// These diagnostics are stored on the identity, not returned per call: when an identity is shared across
+// threads, `mismatched_round`, `excessive_stanzas`, `unsupported_stanza`, and `compression` may describe another file decrypted
+// concurrently. Functions of this crate create one identity per call, and are not affected.
 pub struct Identity {
-    hash: Vec<u8>,
-    signature: Vec<u8>,
+    hash: ChainHash,
+    signature: GAffine,
+    body_len: usize,
+    max_stanzas: usize,
+    mismatched_round: Mutex<Option<Round>>,
+    excessive_stanzas: Mutex<Option<usize>>,
+    unsupported_stanza: Mutex<Option<Vec<String>>>,
+    compression: Mutex<Option<String>>,
 }
 
 impl Identity {
-    pub fn new(hash: &[u8], signature: &[u8]) -> Self {
-        Self {
-            hash: hash.to_vec(),
-            signature: signature.to_vec(),
-        }
+    /// Fails if `signature` is not a valid point, as parsed by [`tlock::parse_signature`].
+    pub fn new(hash: impl Into<ChainHash>, signature: &[u8]) -> Result<Self, TLockError> {
+        Ok(Self {
+            hash: hash.into(),
+            signature: tlock::parse_signature(signature)?,
+            body_len: tlock::ciphertext_len_for_signature(signature)?,
+            max_stanzas: MAX_STANZAS,
+            mismatched_round: Mutex::new(None),
+            excessive_stanzas: Mutex::new(None),
+            unsupported_stanza: Mutex::new(None),
+            compression: Mutex::new(None),
+        })
+    }
+
+    /// Set the maximum number of tlock stanzas processed per file. Defaults to [`MAX_STANZAS`].
+    #[cfg_attr(not(feature = "internal"), allow(dead_code))]
+    pub fn with_max_stanzas(mut self, max_stanzas: usize) -> Self {
+        self.max_stanzas = max_stanzas;
+        self
+    }
+
+    pub fn max_stanzas(&self) -> usize {
+        self.max_stanzas
+    }
+
+    /// Number of tlock stanzas in the last file, if it exceeded the maximum.
+    /// With an identity shared across threads, the last file may not be the caller's.
+    pub fn excessive_stanzas(&self) -> Option<usize> {
+        *self.excessive_stanzas.lock().unwrap()
+    }
+
+    /// Round of the last stanza the signature failed to decrypt, if any.
+    /// This happens when the signature is provided for a different round than the one in the header.
+    /// With an identity shared across threads, the last stanza may not be from the caller's file.
+    pub fn mismatched_round(&self) -> Option<Round> {
+        *self.mismatched_round.lock().unwrap()
+    }
+
+    /// Arguments of the last tlock stanza with an unrecognised layout, if any.
+    /// The only supported layout is `<round> <chain hash>`, see [`STANZA_TAG`].
+    /// With an identity shared across threads, the last stanza may not be from the caller's file.
+    pub fn unsupported_stanza(&self) -> Option<Vec<String>> {
+        self.unsupported_stanza.lock().unwrap().clone()
+    }
+
+    /// Compression algorithm of the last file, from its [`COMPRESSION_STANZA_TAG`] stanza.
+    /// With an identity shared across threads, the last file may not be the caller's.
+    pub fn compression(&self) -> Option<String> {
+        self.compression.lock().unwrap().clone()
+    }
+}
+
+impl Zeroize for Identity {
+    fn zeroize(&mut self) {
+        self.signature.zeroize();
+    }
+}
+
+impl Drop for Identity {
+    fn drop(&mut self) {
+        self.zeroize();
     }
 }
 
+impl ZeroizeOnDrop for Identity {}
+
 impl age::Identity for Identity {
+    fn unwrap_stanzas(&self, stanzas: &[Stanza]) -> Option<Result<FileKey, age::DecryptError>> {
+        *self.compression.lock().unwrap() = compression(stanzas.iter());
+        let count = stanzas
+            .iter()
+            .filter(|stanza| stanza.tag == STANZA_TAG)
+            .count();
+        if count > self.max_stanzas {
+            *self.excessive_stanzas.lock().unwrap() = Some(count);
+            return Some(Err(age::DecryptError::InvalidHeader));
+        }
+        // Stanzas are order independent, a failing stanza does not prevent a later one from succeeding
+        let mut error = None;
+        for stanza in stanzas {
+            match self.unwrap_stanza(stanza) {
+                Some(Ok(file_key)) => return Some(Ok(file_key)),
+                Some(Err(e)) => {
+                    error.get_or_insert(e);
+                }
+                None => {}
+            }
+        }
+        error.map(Err)
+    }
+
     // Unwrap is called by the age Decrypt API and is provided the DEK that was time
     // lock encrypted by the Wrap function via the Stanza. Inside of Unwrap we decrypt
     // the DEK and provide back to age.
@@ -32,53 +157,88 @@ impl age::Identity for Identity {
         if stanza.tag != STANZA_TAG {
             return None;
         }
-        if stanza.args.len() != 2 {
+        let layout = match stanza.args.as_slice() {
+            [round, hash] => round
+                .parse::<u64>()
+                .ok()
+                .map(Round::from)
+                .zip(hash.parse::<ChainHash>().ok()),
+            _ => None,
+        };
+        let Some((round, hash)) = layout else {
+            *self.unsupported_stanza.lock().unwrap() = Some(stanza.args.clone());
             return Some(Err(age::DecryptError::InvalidHeader));
-        }
-        let args: [String; 2] = [stanza.args[0].clone(), stanza.args[1].clone()];
+        };
 
-        let _round = args[0]
-            .parse::<u64>()
-            .map_err(|_| age::DecryptError::InvalidHeader)
-            .ok()?;
+        if self.hash != hash {
+            return Some(Err(age::DecryptError::InvalidHeader));
+        }
 
-        if self.hash != hex::decode(&args[1]).ok()? {
+        // A valid body has a fixed size, reject anything else before decrypting
+        if self.body_len != stanza.body.len() {
             return Some(Err(age::DecryptError::InvalidHeader));
         }
 
         let dst = InMemoryWriter::new();
-        let decryption = tlock::decrypt(dst.to_owned(), stanza.body.as_slice(), &self.signature);
+        let decryption =
+            tlock::decrypt_with_point(dst.to_owned(), stanza.body.as_slice(), &self.signature);
+        if let Err(tlock::TLockError::IBE(tlock::IBEError::SignatureMismatch)) = decryption {
+            *self.mismatched_round.lock().unwrap() = Some(round);
+            return Some(Err(age::DecryptError::DecryptionFailed));
+        }
         decryption
             .map_err(|_| age::DecryptError::DecryptionFailed)
             .ok()?;
         let mut dst = dst.memory();
-        dst.resize(16, 0);
-        let file_key: [u8; 16] = dst[..].try_into().ok()?;
-        Some(Ok(file_key.into()))
+        let file_key: Option<[u8; 16]> = dst[..].try_into().ok();
+        dst.zeroize();
+        Some(Ok(file_key?.into()))
     }
 }
 
 // Identity implements the age Identity interface. This is used to decrypt
 // data with the age Decrypt API.
+//
+// Header information is behind a single lock, so that round and hash are always read from the same stanza.
 pub struct HeaderIdentity {
-    hash: Mutex<Option<Vec<u8>>>,
-    round: Mutex<Option<u64>>,
+    info: Mutex<HeaderInfo>,
+}
+
+#[derive(Default)]
+struct HeaderInfo {
+    compression: Option<String>,
+    hash: Option<ChainHash>,
+    malformed: bool,
+    round: Option<Round>,
+    unlock_time: Option<u64>,
 }
 
 impl HeaderIdentity {
     pub fn new() -> Self {
         Self {
-            hash: Mutex::new(None),
-            round: Mutex::new(None),
+            info: Mutex::new(HeaderInfo::default()),
         }
     }
 
-    pub fn hash(&self) -> Option<Vec<u8>> {
-        self.hash.lock().unwrap().clone()
+    /// Whether the header has a malformed tlock stanza, and no valid one.
+    pub fn malformed(&self) -> bool {
+        self.info.lock().unwrap().malformed
+    }
+
+    pub fn hash(&self) -> Option<ChainHash> {
+        self.info.lock().unwrap().hash
+    }
+
+    pub fn round(&self) -> Option<Round> {
+        self.info.lock().unwrap().round
+    }
+
+    pub fn unlock_time(&self) -> Option<u64> {
+        self.info.lock().unwrap().unlock_time
     }
 
-    pub fn round(&self) -> Option<u64> {
-        *self.round.lock().unwrap()
+    pub fn compression(&self) -> Option<String> {
+        self.info.lock().unwrap().compression.clone()
     }
 }
 
@@ -93,44 +253,116 @@ impl age::Identity for HeaderIdentity {
     // lock encrypted by the Wrap function via the Stanza. Inside of Unwrap we extract
     // tlock header and assign it to the identity.
     fn unwrap_stanza(&self, stanza: &Stanza) -> Option<Result<FileKey, age::DecryptError>> {
+        if stanza.tag == UNLOCK_TIME_STANZA_TAG {
+            // The unlock time is informative, a malformed value is ignored
+            self.info.lock().unwrap().unlock_time = stanza.args.first()?.parse::<u64>().ok();
+            return None;
+        }
+        if stanza.tag == COMPRESSION_STANZA_TAG {
+            self.info.lock().unwrap().compression = compression(std::iter::once(stanza));
+            return None;
+        }
         if stanza.tag != STANZA_TAG {
             return None;
         }
-        if stanza.args.len() != 2 {
-            return Some(Err(age::DecryptError::InvalidHeader));
+        let round = stanza
+            .args
+            .first()
+            .and_then(|round| round.parse::<u64>().ok())
+            .map(Round::from);
+        let hash = stanza
+            .args
+            .get(1)
+            .and_then(|hash| hash.parse::<ChainHash>().ok());
+        let valid = stanza.args.len() == 2 && round.is_some() && hash.is_some();
+
+        // A malformed stanza keeps whatever could be parsed as partial information, unless a valid stanza was already found
+        let mut info = self.info.lock().unwrap();
+        if valid || info.malformed || info.round.is_none() {
+            *info = HeaderInfo {
+                compression: info.compression.take(),
+                hash,
+                malformed: !valid,
+                round,
+                unlock_time: info.unlock_time,
+            };
         }
-        let args: [String; 2] = [stanza.args[0].clone(), stanza.args[1].clone()];
-
-        let round = args[0]
-            .parse::<u64>()
-            .map_err(|_| age::DecryptError::InvalidHeader)
-            .ok()?;
-        let hash = hex::decode(&args[1])
-            .map_err(|_| age::DecryptError::InvalidHeader)
-            .ok()?;
-
-        *self.round.lock().unwrap() = Some(round);
-        *self.hash.lock().unwrap() = Some(hash);
         None
     }
 }
 
 /// Recipient implements the age Recipient interface. This is used to encrypt
 /// data with the age Encrypt API.
+///
+/// tlock stanzas carry no recipient label. Recipient labels were introduced in later age releases,
+/// and the age 0.9 `Recipient` trait this crate implements has no way to report them.
+/// A tlock recipient can therefore be mixed with any other recipient, as before.
+///
+/// Combined with a native age recipient, the file can be decrypted either once the round is reached, or at any time by the holder of the age key.
+/// tlock decryption and [`decrypt_header`](crate::decrypt_header) ignore stanzas from other recipients.
+///
+/// ```rust
+/// use std::io::Write;
+///
+/// # let pk_bytes = hex::decode("8200fc249deb0148eb918d6e213980c5d01acd7fc251900d9260136da3b54836ce125172399ddc69c4e3e11429b62c11").unwrap();
+/// let chain_hash: tlock_age::ChainHash = "7672797f548f3f4748ac4bf3352fc6c6b6468c9ad40ad456a397545c6e2df5bf".parse().unwrap();
+/// let escrow = age::x25519::Identity::generate();
+/// let recipients: Vec<Box<dyn age::Recipient + Send>> = vec![
+///     Box::new(tlock_age::Recipient::new(chain_hash, &pk_bytes, 1000)),
+///     Box::new(escrow.to_public()),
+/// ];
+///
+/// let mut encrypted = vec![];
+/// let encryptor = age::Encryptor::with_recipients(recipients).unwrap();
+/// let mut writer = encryptor.wrap_output(&mut encrypted).unwrap();
+/// writer.write_all(b"hello").unwrap();
+/// writer.finish().unwrap();
+///
+/// let header = tlock_age::decrypt_header(encrypted.as_slice()).unwrap();
+/// assert_eq!(header.round(), 1000.into());
+/// ```
 pub struct Recipient {
-    hash: Vec<u8>,
+    hash: ChainHash,
     public_key_bytes: Vec<u8>,
-    round: u64,
+    round: Round,
+    unlock_time: Option<u64>,
+    compression: Option<String>,
 }
 
 impl Recipient {
-    pub fn new(hash: &[u8], public_key_bytes: &[u8], round: u64) -> Self {
+    pub fn new(
+        hash: impl Into<ChainHash>,
+        public_key_bytes: &[u8],
+        round: impl Into<Round>,
+    ) -> Self {
         Self {
-            hash: hash.to_vec(),
+            hash: hash.into(),
             public_key_bytes: public_key_bytes.to_vec(),
-            round,
+            round: round.into(),
+            unlock_time: None,
+            compression: None,
         }
     }
+
+    /// Store the estimated unlock time, as a unix timestamp, in a [`UNLOCK_TIME_STANZA_TAG`] stanza.
+    pub fn with_unlock_time(mut self, unlock_time: u64) -> Self {
+        self.unlock_time = Some(unlock_time);
+        self
+    }
+
+    /// Mark the payload as compressed with `algorithm`, in a [`COMPRESSION_STANZA_TAG`] stanza.
+    /// Compressing the payload is up to the caller.
+    pub(crate) fn with_compression(mut self, algorithm: &str) -> Self {
+        self.compression = Some(algorithm.to_string());
+        self
+    }
+}
+
+/// Compression algorithm from the first [`COMPRESSION_STANZA_TAG`] stanza, if any.
+/// A stanza without argument names an empty algorithm, which no decoder supports.
+fn compression<'a>(mut stanzas: impl Iterator<Item = &'a Stanza>) -> Option<String> {
+    let stanza = stanzas.find(|stanza| stanza.tag == COMPRESSION_STANZA_TAG)?;
+    Some(stanza.args.first().cloned().unwrap_or_default())
 }
 
 #[derive(Clone)]
@@ -145,8 +377,9 @@ impl InMemoryWriter {
         }
     }
 
+    /// Take the written bytes, leaving no copy behind.
     pub fn memory(&self) -> Vec<u8> {
-        self.memory.lock().unwrap().to_owned()
+        std::mem::take(&mut *self.memory.lock().unwrap())
     }
 }
 
@@ -157,7 +390,6 @@ impl io::Write for InMemoryWriter {
     }
 
     fn flush(&mut self) -> io::Result<()> {
-        self.memory.lock().unwrap().to_owned().zeroize();
         Ok(())
     }
 }
@@ -167,15 +399,34 @@ impl age::Recipient for Recipient {
     /// age that is used for encrypting/decrypting data. Inside of Wrap we encrypt
     /// the DEK using time lock encryption.
     fn wrap_file_key(&self, file_key: &FileKey) -> Result<Vec<Stanza>, age::EncryptError> {
+        // The DEK is read straight from the file key, and the ciphertext written straight into
+        // the stanza body, so no copy of either outlives this call.
         let src = file_key.expose_secret().as_slice();
-        let dst = InMemoryWriter::new();
-        let _ = tlock::encrypt(dst.to_owned(), src, &self.public_key_bytes, self.round);
+        let mut body =
+            Vec::with_capacity(tlock::ciphertext_len(&self.public_key_bytes).unwrap_or(0));
+        tlock::encrypt(&mut body, src, &self.public_key_bytes, self.round)
+            .map_err(|e| age::EncryptError::Io(io::Error::new(io::ErrorKind::InvalidInput, e)))?;
 
-        Ok(vec![Stanza {
+        let mut stanzas = vec![Stanza {
             tag: STANZA_TAG.to_string(),
-            args: vec![self.round.to_string(), hex::encode(&self.hash)],
-            body: dst.memory(),
-        }])
+            args: vec![self.round.to_string(), self.hash.to_string()],
+            body,
+        }];
+        if let Some(unlock_time) = self.unlock_time {
+            stanzas.push(Stanza {
+                tag: UNLOCK_TIME_STANZA_TAG.to_string(),
+                args: vec![unlock_time.to_string()],
+                body: vec![],
+            });
+        }
+        if let Some(compression) = &self.compression {
+            stanzas.push(Stanza {
+                tag: COMPRESSION_STANZA_TAG.to_string(),
+                args: vec![compression.clone()],
+                body: vec![],
+            });
+        }
+        Ok(stanzas)
     }
 }
 
@@ -188,7 +439,17 @@ mod tests {
 
     use drand_core::HttpClient;
 
-    use crate::{Identity, Recipient};
+    use age::{Identity as _, Recipient as _};
+    use age_core::format::{FileKey, Stanza};
+
+    use super::HeaderIdentity;
+    use super::STANZA_TAG;
+    use crate::{ChainHash, Identity, Recipient};
+    use age::secrecy::ExposeSecret;
+    use zeroize::{Zeroize, ZeroizeOnDrop};
+
+    // testnet-unchained-3s, signature on G2 for round 1000
+    const SIGNATURE: &str = "a4721e6c3eafcd823f138cd29c6c82e8c5149101d0bb4bafddbac1c2d1fe3738895e4e21dd4b8b41bf007046440220910bb1cdb91f50a84a0d7f33ff2e8577aa62ac64b35a291a728a9db5ac91e06d1312b48a376138d77b4d6ad27c24221afe";
 
     #[test]
     fn it_works() {
@@ -200,8 +461,9 @@ mod tests {
 
         let round = 100;
         let beacon = client.get(round).unwrap();
-        let id = Identity::new(&info.hash(), &beacon.signature());
-        let recipient = Recipient::new(&info.hash(), &info.public_key(), round);
+        let hash = ChainHash::try_from(info.hash().as_slice()).unwrap();
+        let id = Identity::new(hash, &beacon.signature()).unwrap();
+        let recipient = Recipient::new(hash, &info.public_key(), round);
 
         let mut plaintext = vec![0u8; 1000];
         plaintext.fill_with(rand::random);
@@ -234,4 +496,185 @@ mod tests {
 
         assert_eq!(decrypted, plaintext);
     }
+
+    #[test]
+    fn test_wrap_file_key_invalid_public_key() {
+        let recipient = Recipient::new([0u8; 32], &[0u8; 10], 1000);
+        let file_key = FileKey::from([0u8; 16]);
+        assert!(matches!(
+            recipient.wrap_file_key(&file_key),
+            Err(age::EncryptError::Io(_))
+        ));
+    }
+
+    #[test]
+    fn test_unwrap_stanza_unsupported_layout() {
+        let hash = [0u8; 32];
+        let signature = hex::decode(SIGNATURE).unwrap();
+        let id = Identity::new(hash, &signature).unwrap();
+
+        for args in [
+            vec![],
+            vec!["1000".to_string()],
+            vec!["1000".to_string(), hex::encode(hash), "extra".to_string()],
+            vec![hex::encode(hash), "1000".to_string()],
+            vec!["-1".to_string(), hex::encode(hash)],
+            vec!["1000".to_string(), "not hex".to_string()],
+        ] {
+            let stanza = Stanza {
+                tag: STANZA_TAG.to_string(),
+                args: args.clone(),
+                body: vec![0u8; 80],
+            };
+            assert!(matches!(
+                id.unwrap_stanza(&stanza),
+                Some(Err(age::DecryptError::InvalidHeader))
+            ));
+            assert_eq!(id.unsupported_stanza(), Some(args));
+        }
+    }
+
+    #[test]
+    fn test_unwrap_stanza_body_size() {
+        let hash = [0u8; 32];
+        let signature = hex::decode(SIGNATURE).unwrap();
+        let id = Identity::new(hash, &signature).unwrap();
+
+        for len in [0, 79, 81, 1 << 20] {
+            let stanza = Stanza {
+                tag: STANZA_TAG.to_string(),
+                args: vec!["1000".to_string(), hex::encode(hash)],
+                body: vec![0u8; len],
+            };
+            assert!(matches!(
+                id.unwrap_stanza(&stanza),
+                Some(Err(age::DecryptError::InvalidHeader))
+            ));
+        }
+        assert_eq!(id.unsupported_stanza(), None);
+    }
+
+    #[test]
+    fn test_identity_zeroize() {
+        fn assert_zeroize_on_drop<T: ZeroizeOnDrop>() {}
+        assert_zeroize_on_drop::<Identity>();
+
+        let hash: ChainHash = "7672797f548f3f4748ac4bf3352fc6c6b6468c9ad40ad456a397545c6e2df5bf"
+            .parse()
+            .unwrap();
+        let signature = hex::decode(SIGNATURE).unwrap();
+        let mut id = Identity::new(hash, &signature).unwrap();
+        id.zeroize();
+
+        // the signature coordinates are cleared, the chain hash is public and kept
+        let mut zeroed = tlock::parse_signature(&signature).unwrap();
+        zeroed.zeroize();
+        assert_ne!(id.signature, tlock::parse_signature(&signature).unwrap());
+        assert_eq!(id.signature, zeroed);
+        assert_eq!(id.hash, hash);
+    }
+
+    #[test]
+    fn test_identity_concurrent() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Identity>();
+        assert_send_sync::<HeaderIdentity>();
+        assert_send_sync::<Recipient>();
+
+        let hash: ChainHash = "7672797f548f3f4748ac4bf3352fc6c6b6468c9ad40ad456a397545c6e2df5bf"
+            .parse()
+            .unwrap();
+        let pk_bytes = hex::decode("8200fc249deb0148eb918d6e213980c5d01acd7fc251900d9260136da3b54836ce125172399ddc69c4e3e11429b62c11").unwrap();
+        let signature = hex::decode(SIGNATURE).unwrap();
+        let id = Identity::new(hash, &signature).unwrap();
+
+        let files: Vec<(Vec<u8>, Vec<u8>)> = (0..8u8)
+            .map(|i| {
+                let plaintext = vec![i; 1000];
+                let encryptor = age::Encryptor::with_recipients(vec![Box::new(Recipient::new(
+                    hash, &pk_bytes, 1000,
+                ))])
+                .unwrap();
+                let mut encrypted = vec![];
+                let mut writer = encryptor.wrap_output(&mut encrypted).unwrap();
+                writer.write_all(&plaintext).unwrap();
+                writer.finish().unwrap();
+                (plaintext, encrypted)
+            })
+            .collect();
+
+        std::thread::scope(|scope| {
+            for (plaintext, encrypted) in &files {
+                let id = &id;
+                scope.spawn(move || {
+                    let decryptor = match age::Decryptor::new(encrypted.as_slice()).unwrap() {
+                        age::Decryptor::Recipients(d) => d,
+                        _ => unreachable!(),
+                    };
+                    let mut decrypted = vec![];
+                    let mut reader = decryptor
+                        .decrypt(iter::once(id as &dyn age::Identity))
+                        .unwrap();
+                    reader.read_to_end(&mut decrypted).unwrap();
+                    assert_eq!(&decrypted, plaintext);
+                });
+            }
+        });
+        assert_eq!(id.mismatched_round(), None);
+    }
+
+    #[test]
+    fn test_identity_invalid_signature() {
+        let signature = hex::decode(SIGNATURE).unwrap();
+        for invalid in [
+            &signature[1..],
+            &[0u8; 96],
+            hex::encode(&signature).as_bytes(),
+        ] {
+            assert!(Identity::new([0u8; 32], invalid).is_err());
+        }
+    }
+
+    #[test]
+    fn test_unwrap_stanzas_order_independent() {
+        let hash: ChainHash = "7672797f548f3f4748ac4bf3352fc6c6b6468c9ad40ad456a397545c6e2df5bf"
+            .parse()
+            .unwrap();
+        let pk_bytes = hex::decode("8200fc249deb0148eb918d6e213980c5d01acd7fc251900d9260136da3b54836ce125172399ddc69c4e3e11429b62c11").unwrap();
+        let signature = hex::decode(SIGNATURE).unwrap();
+        let id = Identity::new(hash, &signature).unwrap();
+
+        let file_key = FileKey::from([7u8; 16]);
+        let wrap = |hash: ChainHash, round: u64| {
+            Recipient::new(hash, &pk_bytes, round)
+                .wrap_file_key(&file_key)
+                .unwrap()
+                .remove(0)
+        };
+        let valid = wrap(hash, 1000);
+        let other_round = wrap(hash, 1001);
+        let other_chain = wrap([1u8; 32].into(), 1000);
+        let grease = age_core::format::grease_the_joint();
+
+        let copy = |stanza: &Stanza| Stanza {
+            tag: stanza.tag.clone(),
+            args: stanza.args.clone(),
+            body: stanza.body.clone(),
+        };
+        for stanzas in [
+            vec![copy(&grease), copy(&valid)],
+            vec![copy(&other_round), copy(&valid)],
+            vec![copy(&other_chain), copy(&grease), copy(&valid)],
+            vec![copy(&valid), copy(&other_round)],
+        ] {
+            let unwrapped = id.unwrap_stanzas(&stanzas).unwrap().unwrap();
+            assert_eq!(unwrapped.expose_secret(), &[7u8; 16]);
+        }
+
+        assert!(id.unwrap_stanzas(&[copy(&grease)]).is_none());
+        assert!(matches!(
+            id.unwrap_stanzas(&[other_chain, grease, other_round]),
+            Some(Err(age::DecryptError::InvalidHeader))
+        ));
+    }
 }
@@ -4,14 +4,19 @@
 //! The reference interroperable Go implementation is available at [drand/tlock](https://github.com/drand/tlock).
 //!
 //! tlock relies on BLS, content is encrypted against BLS public key. Public key group is assessed based on the public key size.
-//! age implementation is [str4d/rage](https://github.com/str4d/rage). When encrypting file, it generates an additional stanza `grease-the-join`, which other tlock implementation have to ignore.
+//! age implementation is [str4d/rage](https://github.com/str4d/rage). When encrypting file, it generates an additional stanza `grease-the-join`, which other tlock implementation have to ignore. [`encrypt_without_grease`] omits it.
 //!
 //! Both encryption and decryption are performed wihout network access. tlock_age expects the caller interact with the drand network of their choice, through whichever method they wish. To that end, a dedicated method `decrypt_header` is provided.
 //! It's important to note that `decrypt_header` consumes bytes from the reader, and reader requires these headers to be passed as well. The caller needs to duplicates these inputs if needed.
 //!
 //! ## Features
 //!
-//! - `armor`: Enable armor output. This is required if you want to output bytes are ASCII printable.
+//! - `armor`: Enable armor output. This is required if you want to output bytes are ASCII printable. Decryption and header parsing detect armored inputs at runtime, with or without this feature.
+//! - `async`: Enable [`decrypt_async`], to decrypt from a [`futures::io::AsyncRead`] without blocking.
+//! - `registry`: Enable a registry of well-known drand networks, to display a network name instead of a chain hash.
+//! - `serde`: Implement `Serialize` and `Deserialize` for [`UnlockCondition`].
+//! - `tracing`: Emit tracing spans around age encryption and decryption, in addition to tlock ones. Enabled by default.
+//! - `zstd`: Enable [`encrypt_compressed`], and decompression of compressed files by [`decrypt`], and by [`decrypt_async`] with the `async` feature.
 //!
 //! ## Example
 //!
@@ -19,33 +24,83 @@
 
 #[cfg(feature = "armor")]
 pub mod armor;
+mod dearmor;
+mod grease;
 #[cfg(not(feature = "internal"))]
 mod internal;
 #[cfg(feature = "internal")]
 pub mod internal;
+#[cfg(feature = "registry")]
+pub mod registry;
 
-use internal::{HeaderIdentity, Identity, Recipient};
+use age::secrecy::{ExposeSecret, Secret};
+use age_core::format::Stanza;
+#[cfg(feature = "async")]
+use futures::io::{AsyncRead, AsyncWrite};
+pub use internal::Recipient;
+use internal::{HeaderIdentity, Identity};
+use rand::{rngs::OsRng, RngCore};
 use std::{
-    io::{self, copy, Read, Write},
+    fs::{self, File},
+    io::{self, copy, BufRead, Read, Write},
     iter,
+    path::{Path, PathBuf},
+    time::Duration,
 };
 use thiserror::Error;
+pub use tlock::{ChainHash, Round};
+#[cfg(feature = "tracing")]
+use tracing::info_span;
 
 #[derive(Error, Debug)]
 pub enum TLockAgeError {
+    #[error(transparent)]
+    ChainHash(tlock::TLockError),
+    #[error("payload is corrupted or truncated: {0}")]
+    Corrupt(io::Error),
     #[error(transparent)]
     Decrypt(#[from] age::DecryptError),
     #[error(transparent)]
     Encrypt(#[from] age::EncryptError),
+    #[error("cannot retrieve signature: {0}")]
+    FetchSignature(#[source] anyhow::Error),
+    /// `source` is [`age::DecryptError::NoMatchingKeys`] when the header has no tlock stanza,
+    /// and [`age::DecryptError::InvalidHeader`] when a tlock stanza is malformed.
     #[error("cannot parse header. partial information: round {round:?}, chain {chain:?}")]
     Header {
         round: Option<String>,
         chain: Option<String>,
+        #[source]
+        source: Option<age::DecryptError>,
     },
     #[error("recipient cannot be a passphrase")]
     InvalidRecipient,
     #[error(transparent)]
     IO(#[from] io::Error),
+    #[error("cannot write plaintext, output is incomplete after {written} bytes: {source}")]
+    PartialWrite {
+        written: u64,
+        #[source]
+        source: io::Error,
+    },
+    #[error("signature does not match round {round} the message is encrypted to")]
+    RoundMismatch { round: Round },
+    #[error("invalid signature: {0}")]
+    Signature(#[source] tlock::TLockError),
+    #[error("too many tlock stanzas: {count}, maximum is {limit}")]
+    TooManyStanzas { count: usize, limit: usize },
+    #[error("invalid unlock request {0:?}, expected tlock:<round>:<chain hash>")]
+    UnlockRequest(String),
+    #[error(
+        "payload is compressed with {0:?}, which is not supported. zstd requires the zstd feature"
+    )]
+    UnsupportedCompression(String),
+    #[error(
+        "unsupported tlock stanza version, got arguments {args:?}, expected <round> <chain hash>"
+    )]
+    UnsupportedStanza { args: Vec<String> },
+    #[error("round 0 is never emitted by drand, the message could not be decrypted")]
+    ZeroRound,
 }
 
 /// Encrypt using tlock encryption scheme and age encryption.
@@ -54,6 +109,9 @@ pub enum TLockAgeError {
 ///
 /// If you want to armor the output to output bytes are ASCII printable, you must enable `armor` feature.
 ///
+/// drand rounds start at 1, encrypting to round 0 fails with [`TLockAgeError::ZeroRound`].
+/// `chain_hash` must be 32 bytes, other sizes fail with [`TLockAgeError::ChainHash`] before anything is written.
+///
 /// Example using an empty 100-byte message, fastnet public key, at round 1000
 ///
 /// ```rust
@@ -75,17 +133,254 @@ pub enum TLockAgeError {
 /// ```
 pub fn encrypt<W: Write, R: Read>(
     dst: W,
-    mut src: R,
+    src: R,
+    chain_hash: &[u8],
+    public_key_bytes: &[u8],
+    round: impl Into<Round>,
+) -> anyhow::Result<(), TLockAgeError> {
+    let recipient = Recipient::new(
+        check_chain_hash(chain_hash)?,
+        public_key_bytes,
+        check_round(round.into())?,
+    );
+    encrypt_with_recipient(dst, src, Box::new(recipient))
+}
+
+/// Encrypt using tlock encryption scheme and age encryption, storing the estimated unlock time in the header.
+///
+/// The unlock time is computed from the chain `genesis_time` and `period`, both in seconds, and returned by [`decrypt_header`] as [`Header::unlock_time`].
+/// It is stored in a dedicated stanza, which other tlock implementations ignore.
+///
+/// ```rust
+/// // quicknet chain information
+/// let chain_hash = hex::decode("52db9ba70e0cc0f6eaf7803dd07447a1f5477735fd3f661792ba94600c84e971").unwrap();
+/// let pk_bytes = hex::decode("83cf0f2896adee7eb8b5f01fcad3912212c437e0073e911fb90022d3e760183c8c4b450b6a0a6c3ac6a5776a2d1064510d1fec758c921cc22b0e17e63aaf4bcb5ed66304de9cf809bd274ca73bab4af5a6e9c76a4bc09e76eae8991ef5ece45a").unwrap();
+/// let (genesis_time, period) = (1692803367, 3);
+///
+/// let mut encrypted = vec![];
+/// tlock_age::encrypt_with_unlock_time(&mut encrypted, [0u8; 100].as_slice(), &chain_hash, &pk_bytes, 1000, genesis_time, period).unwrap();
+///
+/// let header = tlock_age::decrypt_header(encrypted.as_slice()).unwrap();
+/// assert_eq!(header.unlock_time(), Some(genesis_time + 999 * period));
+/// ```
+pub fn encrypt_with_unlock_time<W: Write, R: Read>(
+    dst: W,
+    src: R,
     chain_hash: &[u8],
     public_key_bytes: &[u8],
-    round: u64,
+    round: impl Into<Round>,
+    genesis_time: u64,
+    period: u64,
 ) -> anyhow::Result<(), TLockAgeError> {
+    let round = check_round(round.into())?;
+    let recipient = Recipient::new(check_chain_hash(chain_hash)?, public_key_bytes, round)
+        .with_unlock_time(unlock_time(round, genesis_time, period));
+    encrypt_with_recipient(dst, src, Box::new(recipient))
+}
+
+/// Estimated time `round` is emitted at, as a unix timestamp.
+fn unlock_time(round: Round, genesis_time: u64, period: u64) -> u64 {
+    // Round 1 is emitted at genesis time
+    genesis_time.saturating_add(round.value().saturating_sub(1).saturating_mul(period))
+}
+
+/// Condition to decrypt a file, to be stored apart from it.
+///
+/// Returned by [`encrypt_with_unlock_condition`]. With the `serde` feature, it implements `Serialize` and `Deserialize`, and deserializing rejects an invalid chain hash.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UnlockCondition {
+    /// Round the file is encrypted to.
+    pub round: Round,
+    /// Hash of the chain emitting the round, serialized hex encoded.
+    pub chain_hash: ChainHash,
+    /// drand scheme id of the chain, as returned by [`tlock::scheme_id`].
+    pub scheme: String,
+    /// Estimated time the round is emitted at, as a unix timestamp.
+    pub unlock_time: u64,
+}
+
+/// Encrypt using tlock encryption scheme and age encryption, and return the condition to decrypt the output.
+///
+/// The output is the same as [`encrypt`]. The returned [`UnlockCondition`] can be stored in a database, without parsing the header with [`decrypt_header`] later.
+/// The unlock time is computed from the chain `genesis_time` and `period`, both in seconds.
+///
+/// ```rust
+/// // quicknet chain information
+/// let chain_hash = hex::decode("52db9ba70e0cc0f6eaf7803dd07447a1f5477735fd3f661792ba94600c84e971").unwrap();
+/// let pk_bytes = hex::decode("83cf0f2896adee7eb8b5f01fcad3912212c437e0073e911fb90022d3e760183c8c4b450b6a0a6c3ac6a5776a2d1064510d1fec758c921cc22b0e17e63aaf4bcb5ed66304de9cf809bd274ca73bab4af5a6e9c76a4bc09e76eae8991ef5ece45a").unwrap();
+/// let (genesis_time, period) = (1692803367, 3);
+///
+/// let mut encrypted = vec![];
+/// let condition = tlock_age::encrypt_with_unlock_condition(&mut encrypted, [0u8; 100].as_slice(), &chain_hash, &pk_bytes, 1000, genesis_time, period).unwrap();
+/// assert_eq!(condition.round, 1000.into());
+/// assert_eq!(condition.unlock_time, genesis_time + 999 * period);
+/// ```
+pub fn encrypt_with_unlock_condition<W: Write, R: Read>(
+    dst: W,
+    src: R,
+    chain_hash: &[u8],
+    public_key_bytes: &[u8],
+    round: impl Into<Round>,
+    genesis_time: u64,
+    period: u64,
+) -> anyhow::Result<UnlockCondition, TLockAgeError> {
+    let round = check_round(round.into())?;
+    let chain_hash = check_chain_hash(chain_hash)?;
     let recipient = Recipient::new(chain_hash, public_key_bytes, round);
-    let encryptor = age::Encryptor::with_recipients(vec![Box::new(recipient)])
-        .expect("we provided a recipient");
+    encrypt_with_recipient(dst, src, Box::new(recipient))?;
+    Ok(UnlockCondition {
+        round,
+        chain_hash,
+        scheme: tlock::scheme_id(public_key_bytes)
+            .expect("encryption succeeded, the public key has a valid size")
+            .to_owned(),
+        unlock_time: unlock_time(round, genesis_time, period),
+    })
+}
+
+/// Encrypt using tlock encryption scheme and age encryption, without the `grease-the-join` stanza.
+///
+/// age adds a stanza with a random tag to every header, to make sure implementations ignore stanzas they do not know.
+/// Omitting it gives a smaller file, and helps testing interoperability with implementations that do not expect it.
+/// The header MAC is recomputed, the output is a regular age file that decrypts as one from [`encrypt`].
+///
+/// ```rust
+/// # let chain_hash = hex::decode("7672797f548f3f4748ac4bf3352fc6c6b6468c9ad40ad456a397545c6e2df5bf").unwrap();
+/// # let pk_bytes = hex::decode("8200fc249deb0148eb918d6e213980c5d01acd7fc251900d9260136da3b54836ce125172399ddc69c4e3e11429b62c11").unwrap();
+/// let mut encrypted = vec![];
+/// tlock_age::encrypt_without_grease(&mut encrypted, [0u8; 100].as_slice(), &chain_hash, &pk_bytes, 1000).unwrap();
+/// assert_eq!(tlock_age::decrypt_header(encrypted.as_slice()).unwrap().round(), 1000.into());
+/// ```
+pub fn encrypt_without_grease<W: Write, R: Read>(
+    dst: W,
+    src: R,
+    chain_hash: &[u8],
+    public_key_bytes: &[u8],
+    round: impl Into<Round>,
+) -> anyhow::Result<(), TLockAgeError> {
+    let recipient = Recipient::new(
+        check_chain_hash(chain_hash)?,
+        public_key_bytes,
+        check_round(round.into())?,
+    );
+    let (recipient, dst) = grease::strip(recipient, dst);
+    encrypt_with_recipient(dst, src, recipient)
+}
+
+/// Encrypt using tlock encryption scheme and age encryption, with the plaintext provided as a sequence of chunks.
+///
+/// Chunks are written to the age stream as they come, so that a message spread across several buffers does not have to be concatenated first.
+/// Their boundaries are not preserved, the output is the same as [`encrypt`] on their concatenation.
+///
+/// ```rust
+/// # let chain_hash = hex::decode("7672797f548f3f4748ac4bf3352fc6c6b6468c9ad40ad456a397545c6e2df5bf").unwrap();
+/// # let pk_bytes = hex::decode("8200fc249deb0148eb918d6e213980c5d01acd7fc251900d9260136da3b54836ce125172399ddc69c4e3e11429b62c11").unwrap();
+/// let (head, body) = (b"head".as_slice(), vec![0u8; 100]);
+/// let mut encrypted = vec![];
+/// tlock_age::encrypt_chunks(&mut encrypted, [head, &body], &chain_hash, &pk_bytes, 1000).unwrap();
+/// ```
+pub fn encrypt_chunks<'a, W: Write, I: IntoIterator<Item = &'a [u8]>>(
+    dst: W,
+    chunks: I,
+    chain_hash: &[u8],
+    public_key_bytes: &[u8],
+    round: impl Into<Round>,
+) -> anyhow::Result<(), TLockAgeError> {
+    let recipient = Recipient::new(
+        check_chain_hash(chain_hash)?,
+        public_key_bytes,
+        check_round(round.into())?,
+    );
+    encrypt_with_writer(dst, Box::new(recipient), |writer| {
+        for chunk in chunks {
+            writer.write_all(chunk)?;
+        }
+        Ok(())
+    })
+}
+
+/// Encrypt using tlock encryption scheme and age encryption, compressing the plaintext with zstd first.
+///
+/// The header has a `tlock-compression zstd` stanza, which tells [`decrypt`] to decompress the payload.
+/// This stanza is specific to this crate: other implementations, including drand/tlock, ignore it and output the compressed plaintext.
+///
+/// Compression reveals how compressible the plaintext is through the ciphertext size.
+/// Do not compress secrets along with data an attacker can influence.
+///
+/// Requires the `zstd` feature.
+///
+/// ```rust
+/// # let chain_hash = hex::decode("7672797f548f3f4748ac4bf3352fc6c6b6468c9ad40ad456a397545c6e2df5bf").unwrap();
+/// # let pk_bytes = hex::decode("8200fc249deb0148eb918d6e213980c5d01acd7fc251900d9260136da3b54836ce125172399ddc69c4e3e11429b62c11").unwrap();
+/// let logs = b"INFO request served\n".repeat(1000);
+/// let mut encrypted = vec![];
+/// tlock_age::encrypt_compressed(&mut encrypted, logs.as_slice(), &chain_hash, &pk_bytes, 1000).unwrap();
+/// assert!(encrypted.len() < logs.len());
+/// ```
+#[cfg(feature = "zstd")]
+pub fn encrypt_compressed<W: Write, R: Read>(
+    dst: W,
+    mut src: R,
+    chain_hash: &[u8],
+    public_key_bytes: &[u8],
+    round: impl Into<Round>,
+) -> anyhow::Result<(), TLockAgeError> {
+    let recipient = Recipient::new(
+        check_chain_hash(chain_hash)?,
+        public_key_bytes,
+        check_round(round.into())?,
+    )
+    .with_compression(internal::COMPRESSION_ZSTD);
+    encrypt_with_writer(dst, Box::new(recipient), |writer| {
+        let mut encoder = zstd::Encoder::new(writer, zstd::DEFAULT_COMPRESSION_LEVEL)?;
+        copy(&mut src, &mut encoder)?;
+        encoder.finish()?;
+        Ok(())
+    })
+}
+
+/// Reject round 0, which drand never emits.
+///
+/// Encrypting to round 0 is still possible for testing, using [`Recipient`] with [`age::Encryptor`].
+fn check_round(round: Round) -> Result<Round, TLockAgeError> {
+    if round.value() == 0 {
+        return Err(TLockAgeError::ZeroRound);
+    }
+    Ok(round)
+}
+
+/// Parse `chain_hash`, so that an invalid hash fails before anything is encrypted or decrypted.
+fn check_chain_hash(chain_hash: &[u8]) -> Result<ChainHash, TLockAgeError> {
+    ChainHash::try_from(chain_hash).map_err(TLockAgeError::ChainHash)
+}
+
+fn encrypt_with_recipient<W: Write, R: Read>(
+    dst: W,
+    mut src: R,
+    recipient: Box<dyn age::Recipient + Send>,
+) -> anyhow::Result<(), TLockAgeError> {
+    encrypt_with_writer(dst, recipient, |writer| {
+        copy(&mut src, writer)?;
+        Ok(())
+    })
+}
 
+/// Encrypt to `recipient`, with `write` providing the plaintext to the age stream.
+///
+/// The stream is finished once `write` returns, `write` must not finish it.
+fn encrypt_with_writer<W: Write>(
+    dst: W,
+    recipient: Box<dyn age::Recipient + Send>,
+    write: impl FnOnce(&mut dyn Write) -> Result<(), TLockAgeError>,
+) -> anyhow::Result<(), TLockAgeError> {
+    let encryptor =
+        age::Encryptor::with_recipients(vec![recipient]).expect("we provided a recipient");
+
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("age::encryption").entered();
     let mut writer = encryptor.wrap_output(dst)?;
-    copy(&mut src, &mut writer)?;
+    write(&mut writer)?;
     writer.finish()?;
 
     Ok(())
@@ -93,26 +388,139 @@ pub fn encrypt<W: Write, R: Read>(
 
 /// Information stored in tlock age header
 pub struct Header {
-    round: u64,
-    hash: Vec<u8>,
+    round: Round,
+    hash: ChainHash,
+    unlock_time: Option<u64>,
+    compression: Option<String>,
+    len: Option<usize>,
 }
 
 impl Header {
-    pub fn new(round: u64, hash: &[u8]) -> Self {
+    pub fn new(round: impl Into<Round>, hash: impl Into<ChainHash>) -> Self {
         Self {
-            round,
-            hash: hash.to_vec(),
+            round: round.into(),
+            hash: hash.into(),
+            unlock_time: None,
+            compression: None,
+            len: None,
         }
     }
 
     /// Round the message is encrypted to.
-    pub fn round(&self) -> u64 {
+    pub fn round(&self) -> Round {
         self.round
     }
 
     /// Hash of the chain used to encrypt the message.
     pub fn hash(&self) -> Vec<u8> {
-        self.hash.clone()
+        self.hash.as_ref().to_vec()
+    }
+
+    /// Hash of the chain used to encrypt the message, as a [`ChainHash`].
+    pub fn chain_hash(&self) -> ChainHash {
+        self.hash
+    }
+
+    /// Estimated unlock time as a unix timestamp, if stored by [`encrypt_with_unlock_time`].
+    pub fn unlock_time(&self) -> Option<u64> {
+        self.unlock_time
+    }
+
+    /// Unix timestamp the round is emitted at, on a chain with `genesis_time` and `period`, both in seconds.
+    ///
+    /// Unlike [`Header::unlock_time`], it is computed from chain information, and is available for every header.
+    /// The result only depends on the round and the chain, and saturates at `u64::MAX`.
+    ///
+    /// ```rust
+    /// // testnet-unchained-3s genesis time and period
+    /// let (genesis_time, period) = (1654677099, 3);
+    /// let header = tlock_age::Header::new(1000, [0u8; 32]);
+    /// assert_eq!(header.unlock_time_with(genesis_time, period), genesis_time + 999 * period);
+    /// ```
+    pub fn unlock_time_with(&self, genesis_time: u64, period: u64) -> u64 {
+        unlock_time(self.round, genesis_time, period)
+    }
+
+    /// Time left before the round is emitted, given the current unix timestamp `now` in seconds.
+    ///
+    /// Returns [`Duration::ZERO`] once the round is emitted, the signature can then be fetched.
+    /// `now` is passed explicitly so that callers choose their time source, the local clock or the latest round time.
+    pub fn time_remaining(&self, genesis_time: u64, period: u64, now: u64) -> Duration {
+        Duration::from_secs(
+            self.unlock_time_with(genesis_time, period)
+                .saturating_sub(now),
+        )
+    }
+
+    /// Algorithm the payload is compressed with, if compressed by [`encrypt_compressed`].
+    pub fn compression(&self) -> Option<&str> {
+        self.compression.as_deref()
+    }
+
+    /// Estimated plaintext size of a binary encrypted file of `encrypted_len` bytes, with this header.
+    ///
+    /// The age payload is made of a nonce, followed by 64 KiB plaintext chunks each carrying a 16-byte tag.
+    /// The estimate is exact for binary files, and an upper bound for armored ones.
+    /// Only available on headers returned by [`decrypt_header`], which know their size.
+    pub fn estimated_plaintext_len(&self, encrypted_len: usize) -> Option<usize> {
+        let payload_len = encrypted_len
+            .saturating_sub(self.len?)
+            .saturating_sub(PAYLOAD_NONCE_SIZE as usize);
+        // the last chunk is always present, even for an empty plaintext
+        let chunks = payload_len
+            .div_ceil(PAYLOAD_CHUNK_SIZE + PAYLOAD_TAG_SIZE)
+            .max(1);
+        Some(payload_len.saturating_sub(chunks * PAYLOAD_TAG_SIZE))
+    }
+
+    /// Whether both headers target the same unlock condition, that is the same round on the same chain.
+    ///
+    /// The chain determines the public key group, comparing chain hashes covers it.
+    /// The estimated unlock time is informative, and is not compared.
+    pub fn same_unlock_condition(&self, other: &Header) -> bool {
+        self.round == other.round && self.hash == other.hash
+    }
+
+    /// Compact token identifying the beacon needed to decrypt, formatted as `tlock:<round>:<chain hash>`.
+    ///
+    /// It allows to request the signature from a third party without sharing the ciphertext, and to feed it back to [`decrypt`].
+    /// The token is parsed back with [`Header::from_unlock_request`].
+    ///
+    /// ```rust
+    /// # let chain_hash = hex::decode("7672797f548f3f4748ac4bf3352fc6c6b6468c9ad40ad456a397545c6e2df5bf").unwrap();
+    /// # let pk_bytes = hex::decode("8200fc249deb0148eb918d6e213980c5d01acd7fc251900d9260136da3b54836ce125172399ddc69c4e3e11429b62c11").unwrap();
+    /// # let mut encrypted = vec![];
+    /// # tlock_age::encrypt(&mut encrypted, [0u8; 100].as_slice(), &chain_hash, &pk_bytes, 1000).unwrap();
+    /// let request = tlock_age::decrypt_header(encrypted.as_slice()).unwrap().to_unlock_request();
+    /// assert_eq!(request, "tlock:1000:7672797f548f3f4748ac4bf3352fc6c6b6468c9ad40ad456a397545c6e2df5bf");
+    ///
+    /// let header = tlock_age::Header::from_unlock_request(&request).unwrap();
+    /// assert_eq!(header.round(), 1000.into());
+    /// ```
+    pub fn to_unlock_request(&self) -> String {
+        format!("{UNLOCK_REQUEST_PREFIX}:{}:{}", self.round, self.hash)
+    }
+
+    /// Parse a token produced by [`Header::to_unlock_request`]. Surrounding whitespace is ignored.
+    ///
+    /// The returned header only holds the round and the chain hash.
+    pub fn from_unlock_request(request: &str) -> anyhow::Result<Self, TLockAgeError> {
+        let invalid = || TLockAgeError::UnlockRequest(request.to_string());
+        let mut parts = request.trim().split(':');
+        let (Some(UNLOCK_REQUEST_PREFIX), Some(round), Some(hash), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(invalid());
+        };
+        let round = round.parse::<u64>().map_err(|_| invalid())?;
+        let hash = hash.parse::<ChainHash>().map_err(|_| invalid())?;
+        Ok(Header::new(check_round(round.into())?, hash))
+    }
+
+    /// Name of the well-known drand network the message is encrypted to, if any.
+    #[cfg(feature = "registry")]
+    pub fn network_name(&self) -> Option<&'static str> {
+        registry::lookup(self.hash.as_ref()).map(|n| n.name)
     }
 }
 
@@ -121,6 +529,9 @@ impl Header {
 /// tlock_age uses age encryption, and age header. These information might be needed before decryption.
 /// For instance, one need to retrieve the round a message is encrypted to, in order to retrieve it.
 ///
+/// Binary and armored inputs are both accepted, armor is detected at runtime as in [`split`].
+/// Features only affect encryption output, and never how a header is parsed.
+///
 /// Example using an empty 100-byte message, fastnet public key, at round 1000
 ///
 /// ```rust
@@ -147,37 +558,187 @@ impl Header {
 /// ```
 pub fn decrypt_header<R: Read>(src: R) -> anyhow::Result<Header, TLockAgeError> {
     let identity = HeaderIdentity::new();
-    #[cfg(feature = "armor")]
-    let src = age::armor::ArmoredReader::new(src);
+    let (header, _) = split(src)?;
+    // age reads the payload nonce along with the header, it is not needed to inspect stanzas
+    let src = header
+        .as_slice()
+        .chain(io::repeat(0).take(PAYLOAD_NONCE_SIZE));
     let decryptor = match age::Decryptor::new(src) {
         Ok(age::Decryptor::Recipients(d)) => d,
         Ok(age::Decryptor::Passphrase(_)) => return Err(TLockAgeError::InvalidRecipient),
         Err(e) => return Err(TLockAgeError::Decrypt(e)),
     };
 
-    let _ = decryptor.decrypt(iter::once(&identity as &dyn age::Identity));
+    // HeaderIdentity never unwraps the file key, decryption fails with NoMatchingKeys unless something else went wrong
+    let result = decryptor.decrypt(iter::once(&identity as &dyn age::Identity));
     match (identity.round(), identity.hash()) {
-        (Some(round), Some(hash)) => Ok(Header::new(round, &hash)),
-        (Some(round), None) => Err(TLockAgeError::Header {
-            round: Some(round.to_string()),
-            chain: None,
-        }),
-        (None, Some(hash)) => Err(TLockAgeError::Header {
-            round: None,
-            chain: Some(hex::encode(hash)),
+        (Some(round), Some(hash)) if !identity.malformed() => Ok(Header {
+            unlock_time: identity.unlock_time(),
+            compression: identity.compression(),
+            len: Some(header.len()),
+            ..Header::new(round, hash)
         }),
-        _ => Err(TLockAgeError::Header {
-            round: None,
-            chain: None,
+        (round, hash) => Err(TLockAgeError::Header {
+            round: round.map(|round| round.to_string()),
+            chain: hash.map(|hash| hash.to_string()),
+            source: if identity.malformed() {
+                Some(age::DecryptError::InvalidHeader)
+            } else {
+                result.err()
+            },
         }),
     }
 }
 
+/// Whether two encrypted files can be decrypted with the same beacon.
+///
+/// Only headers are compared. tlock encryption is randomised, so two encryptions of the same plaintext differ, and payload equality cannot be determined without decrypting.
+/// As with [`decrypt_header`], bytes are consumed from both readers.
+pub fn same_unlock_condition<R1: Read, R2: Read>(
+    a: R1,
+    b: R2,
+) -> anyhow::Result<bool, TLockAgeError> {
+    Ok(decrypt_header(a)?.same_unlock_condition(&decrypt_header(b)?))
+}
+
+/// Split an encrypted file into its raw age header and its payload.
+///
+/// The header is returned as binary bytes, including tlock stanzas and the header MAC.
+/// The payload reader is positioned right after the header, and is not decrypted nor buffered.
+/// The header can be inspected on its own with [`decrypt_header`].
+/// Concatenating both gives back a binary age file, which allows to store headers separately from payloads and fetch the latter lazily.
+///
+/// Armored inputs are detected and dearmored first, whether the `armor` feature is enabled or not. Text around the armor, such as email quoting, armor header lines, and base64 wrapped at other widths are accepted.
+/// At most 64 KiB of text is read looking for the armor, and headers longer than 1 MiB are rejected, so that inputs which are not age files are not read to the end.
+///
+/// ```rust
+/// # let chain_hash = hex::decode("7672797f548f3f4748ac4bf3352fc6c6b6468c9ad40ad456a397545c6e2df5bf").unwrap();
+/// # let pk_bytes = hex::decode("8200fc249deb0148eb918d6e213980c5d01acd7fc251900d9260136da3b54836ce125172399ddc69c4e3e11429b62c11").unwrap();
+/// # let mut encrypted = vec![];
+/// # tlock_age::encrypt(&mut encrypted, [0u8; 100].as_slice(), &chain_hash, &pk_bytes, 1000).unwrap();
+/// let (header, payload) = tlock_age::split(encrypted.as_slice()).unwrap();
+/// assert_eq!(tlock_age::decrypt_header(header.as_slice()).unwrap().round(), 1000.into());
+/// ```
+pub fn split<R: Read>(src: R) -> anyhow::Result<(Vec<u8>, impl Read), TLockAgeError> {
+    let mut src = dearmor::reader(src);
+
+    let mut header = vec![];
+    (&mut src)
+        .take(MAX_HEADER_LEN)
+        .read_until(b'\n', &mut header)?;
+    if header != HEADER_VERSION_LINE {
+        return Err(TLockAgeError::Decrypt(age::DecryptError::InvalidHeader));
+    }
+    // stanzas are followed by the MAC line, which is the last line of the header
+    loop {
+        let start = header.len();
+        let left = MAX_HEADER_LEN.saturating_sub(start as u64);
+        (&mut src).take(left).read_until(b'\n', &mut header)?;
+        if header.last() != Some(&b'\n') || header.len() == start {
+            return Err(TLockAgeError::Decrypt(age::DecryptError::InvalidHeader));
+        }
+        if header[start..].starts_with(HEADER_MAC_PREFIX) {
+            break;
+        }
+    }
+
+    Ok((header, src))
+}
+
+/// Check whether `src` is an age file with a tlock stanza.
+///
+/// Only header lines are read and parsed, stanzas are neither decoded nor validated. This allows to quickly filter tlock files among other age files, which [`decrypt_header`] then inspects.
+/// Unreadable inputs and files that are not age files return `false`.
+///
+/// Armored inputs are supported, as in [`split`].
+///
+/// ```rust
+/// # let chain_hash = hex::decode("7672797f548f3f4748ac4bf3352fc6c6b6468c9ad40ad456a397545c6e2df5bf").unwrap();
+/// # let pk_bytes = hex::decode("8200fc249deb0148eb918d6e213980c5d01acd7fc251900d9260136da3b54836ce125172399ddc69c4e3e11429b62c11").unwrap();
+/// # let mut encrypted = vec![];
+/// # tlock_age::encrypt(&mut encrypted, [0u8; 100].as_slice(), &chain_hash, &pk_bytes, 1000).unwrap();
+/// assert!(tlock_age::is_tlock_file(encrypted.as_slice()));
+/// assert!(!tlock_age::is_tlock_file(b"hello world".as_slice()));
+/// ```
+pub fn is_tlock_file<R: Read>(src: R) -> bool {
+    let Ok((header, _)) = split(src) else {
+        return false;
+    };
+    header
+        .split(|b| *b == b'\n')
+        .filter_map(|line| line.strip_prefix(STANZA_PREFIX))
+        .any(|args| args.split(|b| *b == b' ').next() == Some(internal::STANZA_TAG.as_bytes()))
+}
+
+/// Prefix of the tokens produced by [`Header::to_unlock_request`].
+const UNLOCK_REQUEST_PREFIX: &str = "tlock";
+/// Maximum size of an age header read by [`split`], well above headers with thousands of stanzas.
+const MAX_HEADER_LEN: u64 = 1024 * 1024;
+/// First line of an age v1 header.
+const HEADER_VERSION_LINE: &[u8] = b"age-encryption.org/v1\n";
+/// Prefix of the first line of a stanza, followed by its tag and arguments.
+const STANZA_PREFIX: &[u8] = b"-> ";
+/// Prefix of the last line of an age header, holding its MAC.
+const HEADER_MAC_PREFIX: &[u8] = b"--- ";
+/// Size of the nonce starting an age payload.
+const PAYLOAD_NONCE_SIZE: u64 = 16;
+/// Size of an age payload plaintext chunk, all but the last chunk are full.
+const PAYLOAD_CHUNK_SIZE: usize = 64 * 1024;
+/// Size of the authentication tag of an age payload chunk.
+const PAYLOAD_TAG_SIZE: usize = 16;
+/// Size of the buffers used to read and copy data while decrypting: dearmoring readers, and the copy to `dst`.
+const DECRYPT_IO_BUFFERS_SIZE: usize = 4 * 8 * 1024;
+
+/// Approximate peak memory, in bytes, used to decrypt an in-memory file of `input_len` bytes to memory, as [`decrypt_to_vec`] does.
+///
+/// The estimate accounts for the input, the plaintext output bounded by the input size,
+/// the age encrypted and decrypted chunk buffers, and read buffers. The header and allocator overhead are a few KiB.
+/// This allows WASM callers to reject or stream inputs that would not fit in linear memory, before allocating.
+///
+/// ```rust
+/// let available = 64 * 1024 * 1024;
+/// assert!(tlock_age::estimate_peak_memory(10 * 1024 * 1024) < available);
+/// assert!(tlock_age::estimate_peak_memory(100 * 1024 * 1024) > available);
+/// ```
+pub fn estimate_peak_memory(input_len: usize) -> usize {
+    input_len
+        .saturating_mul(2)
+        .saturating_add(2 * (PAYLOAD_CHUNK_SIZE + PAYLOAD_TAG_SIZE))
+        .saturating_add(DECRYPT_IO_BUFFERS_SIZE)
+}
+
+/// Decrypt an in-memory encrypted file to a `Vec`, allocated once from the size estimated by [`Header::estimated_plaintext_len`].
+///
+/// Behaves as [`decrypt`], avoiding repeated reallocations when decrypting large files in memory.
+///
+/// ```rust
+/// # let chain_hash = hex::decode("7672797f548f3f4748ac4bf3352fc6c6b6468c9ad40ad456a397545c6e2df5bf").unwrap();
+/// # let pk_bytes = hex::decode("8200fc249deb0148eb918d6e213980c5d01acd7fc251900d9260136da3b54836ce125172399ddc69c4e3e11429b62c11").unwrap();
+/// # let signature = hex::decode("a4721e6c3eafcd823f138cd29c6c82e8c5149101d0bb4bafddbac1c2d1fe3738895e4e21dd4b8b41bf007046440220910bb1cdb91f50a84a0d7f33ff2e8577aa62ac64b35a291a728a9db5ac91e06d1312b48a376138d77b4d6ad27c24221afe").unwrap();
+/// # let mut encrypted = vec![];
+/// # tlock_age::encrypt(&mut encrypted, [0u8; 100].as_slice(), &chain_hash, &pk_bytes, 1000).unwrap();
+/// let decrypted = tlock_age::decrypt_to_vec(&encrypted, &chain_hash, &signature).unwrap();
+/// assert_eq!(decrypted.len(), 100);
+/// ```
+pub fn decrypt_to_vec(
+    src: &[u8],
+    chain_hash: &[u8],
+    signature: &[u8],
+) -> anyhow::Result<Vec<u8>, TLockAgeError> {
+    let capacity = decrypt_header(src)?
+        .estimated_plaintext_len(src.len())
+        .unwrap_or_default();
+    let mut dst = Vec::with_capacity(capacity);
+    decrypt(&mut dst, src, chain_hash, signature)?;
+    Ok(dst)
+}
+
 /// Decrypt using tlock encryption scheme and age encryption.
 ///
 /// round and public key information are retrieved from age header.
 /// signature has to be the one for that round.
-/// src can be armored or not, decryption supports both.
+/// src can be armored or not, decryption supports both. Text around the armor, such as email quoting, armor header lines, and base64 wrapped at other widths are accepted.
+/// At most 64 KiB of text is read looking for the armor.
 ///
 /// Example using an empty 100-byte message, fastnet public key, at round 1000
 ///
@@ -204,26 +765,1626 @@ pub fn decrypt_header<R: Read>(src: R) -> anyhow::Result<Header, TLockAgeError>
 /// let decrypted = vec![];
 /// tlock_age::decrypt(decrypted, encrypted, &chain_hash, &signature).unwrap();
 /// ```
+///
+/// Plaintext is streamed to `dst` as it is decrypted. If writing to `dst` fails, for instance because the disk is full,
+/// the bytes written so far are left in place and [`TLockAgeError::PartialWrite`] reports how many there are.
+/// Use [`decrypt_to_path`] with [`PathOptions::atomic`] to only produce a file once decryption fully succeeded.
 pub fn decrypt<W: Write, R: Read>(
-    mut dst: W,
+    dst: W,
+    src: R,
+    chain_hash: &[u8],
+    signature: &[u8],
+) -> anyhow::Result<(), TLockAgeError> {
+    decrypt_with_progress(dst, src, chain_hash, signature, |_| {})
+}
+
+/// Decrypt using tlock encryption scheme and age encryption, retrieving the signature once the header is known.
+///
+/// `fetch_signature` is called once with the file header, and returns the signature for [`Header::round`].
+/// This allows signatures to come from a source decryption has to wait on, such as a hardware module or a manual transfer.
+/// Its errors are reported as [`TLockAgeError::FetchSignature`].
+///
+/// ```rust
+/// # let chain_hash = hex::decode("7672797f548f3f4748ac4bf3352fc6c6b6468c9ad40ad456a397545c6e2df5bf").unwrap();
+/// # let pk_bytes = hex::decode("8200fc249deb0148eb918d6e213980c5d01acd7fc251900d9260136da3b54836ce125172399ddc69c4e3e11429b62c11").unwrap();
+/// # let mut encrypted = vec![];
+/// # tlock_age::encrypt(&mut encrypted, [0u8; 100].as_slice(), &chain_hash, &pk_bytes, 1000).unwrap();
+/// let mut decrypted = vec![];
+/// tlock_age::decrypt_with(&mut decrypted, encrypted.as_slice(), &chain_hash, |header| {
+///     println!("fetching signature for round {}", header.round());
+///     Ok(hex::decode("a4721e6c3eafcd823f138cd29c6c82e8c5149101d0bb4bafddbac1c2d1fe3738895e4e21dd4b8b41bf007046440220910bb1cdb91f50a84a0d7f33ff2e8577aa62ac64b35a291a728a9db5ac91e06d1312b48a376138d77b4d6ad27c24221afe")?)
+/// })
+/// .unwrap();
+/// ```
+pub fn decrypt_with<W: Write, R: Read, F: FnMut(&Header) -> anyhow::Result<Vec<u8>>>(
+    dst: W,
+    src: R,
+    chain_hash: &[u8],
+    mut fetch_signature: F,
+) -> anyhow::Result<(), TLockAgeError> {
+    let (header, payload) = split(src)?;
+    let signature = fetch_signature(&decrypt_header(header.as_slice())?)
+        .map_err(TLockAgeError::FetchSignature)?;
+    decrypt(
+        dst,
+        header.as_slice().chain(payload),
+        chain_hash,
+        &signature,
+    )
+}
+
+/// Decrypt using tlock encryption scheme and age encryption, reporting progress.
+///
+/// `progress` is called with the total number of plaintext bytes written to `dst` so far, after each write.
+/// This allows callers to display a progress bar when decrypting large files.
+///
+/// ```rust
+/// # let chain_hash = hex::decode("7672797f548f3f4748ac4bf3352fc6c6b6468c9ad40ad456a397545c6e2df5bf").unwrap();
+/// # let pk_bytes = hex::decode("8200fc249deb0148eb918d6e213980c5d01acd7fc251900d9260136da3b54836ce125172399ddc69c4e3e11429b62c11").unwrap();
+/// # let signature = hex::decode("a4721e6c3eafcd823f138cd29c6c82e8c5149101d0bb4bafddbac1c2d1fe3738895e4e21dd4b8b41bf007046440220910bb1cdb91f50a84a0d7f33ff2e8577aa62ac64b35a291a728a9db5ac91e06d1312b48a376138d77b4d6ad27c24221afe").unwrap();
+/// # let mut encrypted = vec![];
+/// # tlock_age::encrypt(&mut encrypted, [0u8; 100].as_slice(), &chain_hash, &pk_bytes, 1000).unwrap();
+/// let mut decrypted = vec![];
+/// tlock_age::decrypt_with_progress(&mut decrypted, encrypted.as_slice(), &chain_hash, &signature, |written| {
+///     println!("{written} bytes decrypted");
+/// })
+/// .unwrap();
+/// ```
+pub fn decrypt_with_progress<W: Write, R: Read, F: FnMut(u64)>(
+    dst: W,
     src: R,
     chain_hash: &[u8],
     signature: &[u8],
+    progress: F,
 ) -> anyhow::Result<(), TLockAgeError> {
-    let identity = Identity::new(chain_hash, signature);
-    #[cfg(feature = "armor")]
-    let src = age::armor::ArmoredReader::new(src);
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("age::decryption").entered();
+    let identity = Identity::new(check_chain_hash(chain_hash)?, signature)
+        .map_err(TLockAgeError::Signature)?;
+    let src = dearmor::reader(src);
     let decryptor = match age::Decryptor::new(src) {
         Ok(age::Decryptor::Recipients(d)) => d,
         Ok(age::Decryptor::Passphrase(_)) => return Err(TLockAgeError::InvalidRecipient),
         Err(e) => return Err(TLockAgeError::Decrypt(e)),
     };
 
-    let mut reader = match decryptor.decrypt(iter::once(&identity as &dyn age::Identity)) {
-        Ok(reader) => reader,
+    let reader = decryptor
+        .decrypt(iter::once(&identity as &dyn age::Identity))
+        .map_err(|e| unwrap_error(&identity, e))?;
+    let mut reader = decompress(reader, identity.compression())?;
+    let mut dst = ProgressWriter {
+        inner: dst,
+        written: 0,
+        failed: false,
+        progress,
+    };
+    copy(&mut reader, &mut dst).map_err(|e| {
+        if dst.failed {
+            TLockAgeError::PartialWrite {
+                written: dst.written,
+                source: e,
+            }
+        } else {
+            payload_error(e)
+        }
+    })?;
+
+    Ok(())
+}
+
+/// Decrypt using tlock encryption scheme and age encryption, from an async reader to an async writer.
+///
+/// Behaves as [`decrypt`], without blocking on `src` and `dst`.
+/// Ciphertext is decrypted incrementally, which allows to decrypt data streamed from a socket without buffering it in memory first.
+///
+/// Requires the `async` feature. Compressed files also require the `zstd` feature, as with [`decrypt`].
+///
+/// ```rust
+/// # let chain_hash = hex::decode("7672797f548f3f4748ac4bf3352fc6c6b6468c9ad40ad456a397545c6e2df5bf").unwrap();
+/// # let pk_bytes = hex::decode("8200fc249deb0148eb918d6e213980c5d01acd7fc251900d9260136da3b54836ce125172399ddc69c4e3e11429b62c11").unwrap();
+/// # let signature = hex::decode("a4721e6c3eafcd823f138cd29c6c82e8c5149101d0bb4bafddbac1c2d1fe3738895e4e21dd4b8b41bf007046440220910bb1cdb91f50a84a0d7f33ff2e8577aa62ac64b35a291a728a9db5ac91e06d1312b48a376138d77b4d6ad27c24221afe").unwrap();
+/// # let mut encrypted = vec![];
+/// # tlock_age::encrypt(&mut encrypted, [0u8; 100].as_slice(), &chain_hash, &pk_bytes, 1000).unwrap();
+/// # futures::executor::block_on(async {
+/// let mut decrypted = vec![];
+/// tlock_age::decrypt_async(&mut decrypted, encrypted.as_slice(), &chain_hash, &signature)
+///     .await
+///     .unwrap();
+/// # });
+/// ```
+#[cfg(feature = "async")]
+pub async fn decrypt_async<W: AsyncWrite + Unpin, R: AsyncRead + Unpin>(
+    mut dst: W,
+    src: R,
+    chain_hash: &[u8],
+    signature: &[u8],
+) -> anyhow::Result<(), TLockAgeError> {
+    let identity = Identity::new(check_chain_hash(chain_hash)?, signature)
+        .map_err(TLockAgeError::Signature)?;
+    let src = dearmor::async_reader(src);
+    let decryptor = match age::Decryptor::new_async(src).await {
+        Ok(age::Decryptor::Recipients(d)) => d,
+        Ok(age::Decryptor::Passphrase(_)) => return Err(TLockAgeError::InvalidRecipient),
         Err(e) => return Err(TLockAgeError::Decrypt(e)),
     };
-    copy(&mut reader, &mut dst)?;
+
+    let reader = decryptor
+        .decrypt_async(iter::once(&identity as &dyn age::Identity))
+        .map_err(|e| unwrap_error(&identity, e))?;
+    let reader = decompress_async(reader, identity.compression())?;
+    futures::io::copy(reader, &mut dst)
+        .await
+        .map_err(payload_error)?;
 
     Ok(())
 }
+
+/// Re-encrypt a file to another chain, for instance when a drand network is deprecated.
+///
+/// `src` is decrypted with `signature`, the beacon of its round on `chain_hash`, and encrypted to `new_round` of the chain identified by `new_chain_hash` and `new_public_key_bytes`.
+/// This happens in a single streaming pass, the plaintext is never fully held in memory.
+/// The new round should be emitted around the same time as the original one, see [`tlock::Round::after`] and [`tlock::TargetTime`].
+///
+/// As with [`encrypt`], the output is not armored. If decryption fails midway, the output is left unfinished and cannot be decrypted.
+/// Compressed payloads are migrated as is, and stay compressed.
+///
+/// ```rust
+/// # let chain_hash = hex::decode("7672797f548f3f4748ac4bf3352fc6c6b6468c9ad40ad456a397545c6e2df5bf").unwrap();
+/// # let pk_bytes = hex::decode("8200fc249deb0148eb918d6e213980c5d01acd7fc251900d9260136da3b54836ce125172399ddc69c4e3e11429b62c11").unwrap();
+/// # let signature = hex::decode("a4721e6c3eafcd823f138cd29c6c82e8c5149101d0bb4bafddbac1c2d1fe3738895e4e21dd4b8b41bf007046440220910bb1cdb91f50a84a0d7f33ff2e8577aa62ac64b35a291a728a9db5ac91e06d1312b48a376138d77b4d6ad27c24221afe").unwrap();
+/// # let mut encrypted = vec![];
+/// # tlock_age::encrypt(&mut encrypted, [0u8; 100].as_slice(), &chain_hash, &pk_bytes, 1000).unwrap();
+/// // quicknet chain information
+/// let new_chain_hash = hex::decode("52db9ba70e0cc0f6eaf7803dd07447a1f5477735fd3f661792ba94600c84e971").unwrap();
+/// let new_pk_bytes = hex::decode("83cf0f2896adee7eb8b5f01fcad3912212c437e0073e911fb90022d3e760183c8c4b450b6a0a6c3ac6a5776a2d1064510d1fec758c921cc22b0e17e63aaf4bcb5ed66304de9cf809bd274ca73bab4af5a6e9c76a4bc09e76eae8991ef5ece45a").unwrap();
+///
+/// let mut migrated = vec![];
+/// tlock_age::migrate(&mut migrated, encrypted.as_slice(), &chain_hash, &signature, &new_chain_hash, &new_pk_bytes, 2000).unwrap();
+///
+/// let header = tlock_age::decrypt_header(migrated.as_slice()).unwrap();
+/// assert_eq!(header.round(), 2000.into());
+/// ```
+pub fn migrate<W: Write, R: Read>(
+    dst: W,
+    src: R,
+    chain_hash: &[u8],
+    signature: &[u8],
+    new_chain_hash: &[u8],
+    new_public_key_bytes: &[u8],
+    new_round: impl Into<Round>,
+) -> anyhow::Result<(), TLockAgeError> {
+    let recipient = Recipient::new(
+        check_chain_hash(new_chain_hash)?,
+        new_public_key_bytes,
+        check_round(new_round.into())?,
+    );
+
+    let identity = Identity::new(check_chain_hash(chain_hash)?, signature)
+        .map_err(TLockAgeError::Signature)?;
+    let src = dearmor::reader(src);
+    let decryptor = match age::Decryptor::new(src) {
+        Ok(age::Decryptor::Recipients(d)) => d,
+        Ok(age::Decryptor::Passphrase(_)) => return Err(TLockAgeError::InvalidRecipient),
+        Err(e) => return Err(TLockAgeError::Decrypt(e)),
+    };
+    let mut reader = decryptor
+        .decrypt(iter::once(&identity as &dyn age::Identity))
+        .map_err(|e| unwrap_error(&identity, e))?;
+    let recipient = match identity.compression() {
+        Some(compression) => recipient.with_compression(&compression),
+        None => recipient,
+    };
+
+    encrypt_with_writer(dst, Box::new(recipient), |writer| {
+        copy(&mut reader, writer).map_err(payload_error)?;
+        Ok(())
+    })
+}
+
+/// Unwrap the age file key from a tlock stanza, without decrypting the payload.
+///
+/// This is meant for advanced composition, where the file key is used with a custom framing instead of age payload encryption.
+/// Prefer [`decrypt`] otherwise.
+///
+/// ```rust
+/// use age::secrecy::ExposeSecret;
+///
+/// # let chain_hash = hex::decode("7672797f548f3f4748ac4bf3352fc6c6b6468c9ad40ad456a397545c6e2df5bf").unwrap();
+/// # let pk_bytes = hex::decode("8200fc249deb0148eb918d6e213980c5d01acd7fc251900d9260136da3b54836ce125172399ddc69c4e3e11429b62c11").unwrap();
+/// # let signature = hex::decode("a4721e6c3eafcd823f138cd29c6c82e8c5149101d0bb4bafddbac1c2d1fe3738895e4e21dd4b8b41bf007046440220910bb1cdb91f50a84a0d7f33ff2e8577aa62ac64b35a291a728a9db5ac91e06d1312b48a376138d77b4d6ad27c24221afe").unwrap();
+/// # let mut encrypted = vec![];
+/// # tlock_age::encrypt(&mut encrypted, [0u8; 100].as_slice(), &chain_hash, &pk_bytes, 1000).unwrap();
+/// // the first stanza follows the version line
+/// let header = &encrypted[encrypted.iter().position(|&b| b == b'\n').unwrap() + 1..];
+/// let (_, stanza) = age_core::format::read::age_stanza(header).unwrap();
+///
+/// let file_key = tlock_age::unwrap_file_key(&stanza.into(), &chain_hash, &signature).unwrap();
+/// assert_eq!(file_key.expose_secret().len(), 16);
+/// ```
+pub fn unwrap_file_key(
+    stanza: &Stanza,
+    chain_hash: &[u8],
+    signature: &[u8],
+) -> anyhow::Result<Secret<[u8; 16]>, TLockAgeError> {
+    let identity = Identity::new(check_chain_hash(chain_hash)?, signature)
+        .map_err(TLockAgeError::Signature)?;
+    match age::Identity::unwrap_stanza(&identity, stanza) {
+        Some(Ok(file_key)) => Ok(Secret::new(*file_key.expose_secret())),
+        Some(Err(e)) => Err(unwrap_error(&identity, e)),
+        None => Err(TLockAgeError::Decrypt(age::DecryptError::NoMatchingKeys)),
+    }
+}
+
+/// Turn a file key unwrapping failure into the most specific error `identity` observed.
+fn unwrap_error(identity: &Identity, e: age::DecryptError) -> TLockAgeError {
+    match (
+        identity.excessive_stanzas(),
+        identity.mismatched_round(),
+        identity.unsupported_stanza(),
+    ) {
+        (Some(count), _, _) => TLockAgeError::TooManyStanzas {
+            count,
+            limit: identity.max_stanzas(),
+        },
+        (None, Some(round), _) => TLockAgeError::RoundMismatch { round },
+        (None, None, Some(args)) => TLockAgeError::UnsupportedStanza { args },
+        (None, None, None) => TLockAgeError::Decrypt(e),
+    }
+}
+
+/// Wrap a decrypted payload to decompress it, according to the compression stanza of its header.
+fn decompress<'a, R: Read + 'a>(
+    reader: R,
+    compression: Option<String>,
+) -> Result<Box<dyn Read + 'a>, TLockAgeError> {
+    match compression.as_deref() {
+        None => Ok(Box::new(reader)),
+        #[cfg(feature = "zstd")]
+        Some(internal::COMPRESSION_ZSTD) => Ok(Box::new(zstd::Decoder::new(reader)?)),
+        Some(compression) => Err(TLockAgeError::UnsupportedCompression(
+            compression.to_string(),
+        )),
+    }
+}
+
+/// Async counterpart of [`decompress`].
+#[cfg(feature = "async")]
+fn decompress_async<'a, R: AsyncRead + Unpin + 'a>(
+    reader: R,
+    compression: Option<String>,
+) -> Result<Box<dyn AsyncRead + Unpin + 'a>, TLockAgeError> {
+    match compression.as_deref() {
+        None => Ok(Box::new(reader)),
+        #[cfg(feature = "zstd")]
+        Some(internal::COMPRESSION_ZSTD) => Ok(Box::new(
+            async_compression::futures::bufread::ZstdDecoder::new(futures::io::BufReader::new(
+                reader,
+            )),
+        )),
+        Some(compression) => Err(TLockAgeError::UnsupportedCompression(
+            compression.to_string(),
+        )),
+    }
+}
+
+/// age authenticates each payload chunk, tampering and truncation are reported as invalid data.
+fn payload_error(e: io::Error) -> TLockAgeError {
+    match e.kind() {
+        io::ErrorKind::InvalidData | io::ErrorKind::UnexpectedEof => TLockAgeError::Corrupt(e),
+        _ => TLockAgeError::IO(e),
+    }
+}
+
+/// Writer reporting the number of bytes written so far to a callback.
+///
+/// Write failures are recorded, to tell them apart from read failures once the copy stops.
+struct ProgressWriter<W, F> {
+    inner: W,
+    written: u64,
+    failed: bool,
+    progress: F,
+}
+
+impl<W: Write, F: FnMut(u64)> Write for ProgressWriter<W, F> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = match self.inner.write(buf) {
+            Ok(n) => n,
+            Err(e) => {
+                // Interrupted writes are retried by the copy
+                self.failed = e.kind() != io::ErrorKind::Interrupted;
+                return Err(e);
+            }
+        };
+        self.written += n as u64;
+        (self.progress)(self.written);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let result = self.inner.flush();
+        self.failed |= result.is_err();
+        result
+    }
+}
+
+/// Options for [`decrypt_to_path`].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PathOptions {
+    /// Flush the output to disk with `fsync` before returning.
+    /// With `atomic`, the parent directory is flushed as well once the temporary file is renamed, so that the rename survives a crash.
+    pub sync: bool,
+    /// Write to a temporary file next to the output, and rename it once decryption succeeded.
+    /// The temporary file has a random name and is created exclusively, it never replaces or follows an existing file.
+    /// A failed decryption never leaves a truncated plaintext at the output path.
+    pub atomic: bool,
+}
+
+/// Decrypt using tlock encryption scheme and age encryption, streaming plaintext to a file.
+///
+/// Behaves as [`decrypt`], with `dst` being the file at `path`, created or truncated.
+/// Without `atomic` option, a failed decryption can leave a partial plaintext at `path`.
+pub fn decrypt_to_path<P: AsRef<Path>, R: Read>(
+    path: P,
+    src: R,
+    chain_hash: &[u8],
+    signature: &[u8],
+    options: PathOptions,
+) -> anyhow::Result<(), TLockAgeError> {
+    let path = path.as_ref();
+    let (target, mut file) = if options.atomic {
+        create_temporary(path)?
+    } else {
+        (path.to_path_buf(), File::create(path)?)
+    };
+
+    let result = decrypt(&mut file, src, chain_hash, signature).and_then(|()| {
+        if options.sync {
+            file.sync_all()?;
+        }
+        Ok(())
+    });
+    drop(file);
+
+    if options.atomic {
+        // the temporary file is removed whether decryption or the rename failed
+        if let Err(e) = result.and_then(|()| Ok(fs::rename(&target, path)?)) {
+            let _ = fs::remove_file(&target);
+            return Err(e);
+        }
+        if options.sync {
+            sync_parent(path)?;
+        }
+        return Ok(());
+    }
+    result
+}
+
+/// Create a hidden file in the same directory as `path`, so the final rename stays on the same filesystem.
+///
+/// The name has a random suffix and the file is created exclusively. Concurrent decryptions to the same path each get their own file,
+/// and an existing file or symlink at that name is never written through.
+fn create_temporary(path: &Path) -> io::Result<(PathBuf, File)> {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    loop {
+        let suffix = OsRng.next_u64();
+        let target = path.with_file_name(format!(".{name}.{suffix:016x}.tlock.tmp"));
+        match fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&target)
+        {
+            Ok(file) => return Ok((target, file)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Flush the directory entry of `path` to disk, so that a rename to it survives a crash.
+///
+/// Directories cannot be opened as files on Windows, where this is a no-op.
+fn sync_parent(path: &Path) -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        let parent = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        File::open(parent)?.sync_all()?;
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use age_core::format::FileKey;
+
+    // testnet-unchained-3s, public key on G1
+    const CHAIN_HASH: &str = "7672797f548f3f4748ac4bf3352fc6c6b6468c9ad40ad456a397545c6e2df5bf";
+    const PUBLIC_KEY: &str = "8200fc249deb0148eb918d6e213980c5d01acd7fc251900d9260136da3b54836ce125172399ddc69c4e3e11429b62c11";
+    // signature for round 1000
+    const SIGNATURE: &str = "a4721e6c3eafcd823f138cd29c6c82e8c5149101d0bb4bafddbac1c2d1fe3738895e4e21dd4b8b41bf007046440220910bb1cdb91f50a84a0d7f33ff2e8577aa62ac64b35a291a728a9db5ac91e06d1312b48a376138d77b4d6ad27c24221afe";
+
+    fn encrypt_to(msg: &[u8], round: u64) -> Vec<u8> {
+        let chain_hash = hex::decode(CHAIN_HASH).unwrap();
+        let pk_bytes = hex::decode(PUBLIC_KEY).unwrap();
+        let mut encrypted = vec![];
+        encrypt(&mut encrypted, msg, &chain_hash, &pk_bytes, round).unwrap();
+        encrypted
+    }
+
+    #[test]
+    fn test_encrypt_without_grease() {
+        let chain_hash = hex::decode(CHAIN_HASH).unwrap();
+        let pk_bytes = hex::decode(PUBLIC_KEY).unwrap();
+        let signature = hex::decode(SIGNATURE).unwrap();
+        let msg = vec![8u8; 100_000];
+        let stanza_tags = |encrypted: &[u8]| {
+            let (header, _) = split(encrypted).unwrap();
+            header
+                .split(|b| *b == b'\n')
+                .filter_map(|line| line.strip_prefix(STANZA_PREFIX))
+                .map(|args| {
+                    String::from_utf8_lossy(args.split(|b| *b == b' ').next().unwrap()).into_owned()
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let greased = encrypt_to(&msg, 1000);
+        let tags = stanza_tags(&greased);
+        assert_eq!(tags.len(), 2);
+        assert!(tags[1].ends_with("-grease"));
+
+        let mut encrypted = vec![];
+        encrypt_without_grease(&mut encrypted, msg.as_slice(), &chain_hash, &pk_bytes, 1000)
+            .unwrap();
+        assert_eq!(stanza_tags(&encrypted), [internal::STANZA_TAG]);
+        assert!(encrypted.len() < greased.len());
+
+        // age verifies the recomputed header MAC
+        let mut decrypted = vec![];
+        decrypt(
+            &mut decrypted,
+            encrypted.as_slice(),
+            &chain_hash,
+            &signature,
+        )
+        .unwrap();
+        assert!(decrypted == msg);
+
+        #[cfg(feature = "armor")]
+        {
+            let mut writer = armor::ArmoredWriter::wrap_output(vec![]).unwrap();
+            encrypt_without_grease(&mut writer, msg.as_slice(), &chain_hash, &pk_bytes, 1000)
+                .unwrap();
+            let armored = writer.finish().unwrap();
+            let mut decrypted = vec![];
+            decrypt(&mut decrypted, armored.as_slice(), &chain_hash, &signature).unwrap();
+            assert!(decrypted == msg);
+        }
+    }
+
+    #[test]
+    fn test_encrypt_with_unlock_condition() {
+        let chain_hash = hex::decode(CHAIN_HASH).unwrap();
+        let pk_bytes = hex::decode(PUBLIC_KEY).unwrap();
+
+        let mut encrypted = vec![];
+        let condition = encrypt_with_unlock_condition(
+            &mut encrypted,
+            [8u8; 100].as_slice(),
+            &chain_hash,
+            &pk_bytes,
+            1000,
+            1_000_000,
+            3,
+        )
+        .unwrap();
+        assert_eq!(
+            condition,
+            UnlockCondition {
+                round: Round::new(1000),
+                chain_hash: CHAIN_HASH.parse().unwrap(),
+                scheme: "pedersen-bls-unchained".to_owned(),
+                unlock_time: 1_000_000 + 999 * 3,
+            }
+        );
+
+        // the condition matches the header
+        let header = decrypt_header(encrypted.as_slice()).unwrap();
+        assert_eq!(header.round(), condition.round);
+        assert_eq!(header.chain_hash(), condition.chain_hash);
+
+        assert!(matches!(
+            encrypt_with_unlock_condition(
+                vec![],
+                [8u8; 100].as_slice(),
+                &chain_hash,
+                &pk_bytes,
+                0,
+                0,
+                3
+            ),
+            Err(TLockAgeError::ZeroRound)
+        ));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_unlock_condition_serde() {
+        let condition = UnlockCondition {
+            round: Round::new(1000),
+            chain_hash: CHAIN_HASH.parse().unwrap(),
+            scheme: "pedersen-bls-unchained".to_owned(),
+            unlock_time: 1_000_000,
+        };
+        let json = serde_json::to_value(&condition).unwrap();
+        assert_eq!(json["round"], 1000);
+        assert_eq!(json["chain_hash"], CHAIN_HASH);
+        assert_eq!(
+            serde_json::from_value::<UnlockCondition>(json.clone()).unwrap(),
+            condition
+        );
+
+        // stored conditions are validated when deserialized
+        let mut invalid = json.clone();
+        invalid["chain_hash"] = CHAIN_HASH[2..].into();
+        assert!(serde_json::from_value::<UnlockCondition>(invalid).is_err());
+        let mut invalid = json;
+        invalid["round"] = "1000".into();
+        assert!(serde_json::from_value::<UnlockCondition>(invalid).is_err());
+    }
+
+    #[test]
+    fn test_encrypt_decrypt_large() {
+        let chain_hash = hex::decode(CHAIN_HASH).unwrap();
+        let signature = hex::decode(SIGNATURE).unwrap();
+
+        // sizes around and across STREAM chunk boundaries
+        for len in [
+            PAYLOAD_CHUNK_SIZE - 1,
+            PAYLOAD_CHUNK_SIZE,
+            PAYLOAD_CHUNK_SIZE + 1,
+            200 * 1024,
+            1024 * 1024,
+        ] {
+            let msg: Vec<u8> = (0..len).map(|i| (i % 251) as u8).collect();
+            let encrypted = encrypt_to(&msg, 1000);
+
+            let mut decrypted = vec![];
+            decrypt(
+                &mut decrypted,
+                encrypted.as_slice(),
+                &chain_hash,
+                &signature,
+            )
+            .unwrap();
+            assert!(decrypted == msg, "binary, {len} bytes");
+
+            #[cfg(feature = "armor")]
+            {
+                let mut writer = armor::ArmoredWriter::wrap_output(vec![]).unwrap();
+                writer.write_all(&encrypted).unwrap();
+                let armored = writer.finish().unwrap();
+
+                let mut decrypted = vec![];
+                decrypt(&mut decrypted, armored.as_slice(), &chain_hash, &signature).unwrap();
+                assert!(decrypted == msg, "armored, {len} bytes");
+            }
+        }
+    }
+
+    #[test]
+    fn test_decrypt_header_armored() {
+        // armor is detected whether the armor feature is enabled or not
+        let msg = vec![8u8; 100];
+        let encrypted = encrypt_to(&msg, 1000);
+        let mut writer =
+            age::armor::ArmoredWriter::wrap_output(vec![], age::armor::Format::AsciiArmor).unwrap();
+        writer.write_all(&encrypted).unwrap();
+        let armored = writer.finish().unwrap();
+
+        for src in [encrypted.as_slice(), armored.as_slice()] {
+            let header = decrypt_header(src).unwrap();
+            assert_eq!(header.round(), 1000.into());
+            assert_eq!(header.hash(), hex::decode(CHAIN_HASH).unwrap());
+
+            let mut decrypted = vec![];
+            decrypt(
+                &mut decrypted,
+                src,
+                &hex::decode(CHAIN_HASH).unwrap(),
+                &hex::decode(SIGNATURE).unwrap(),
+            )
+            .unwrap();
+            assert_eq!(decrypted, msg);
+        }
+    }
+
+    #[test]
+    fn test_decrypt_header_partial() {
+        let header = |stanzas: &[&str]| {
+            let mut header = HEADER_VERSION_LINE.to_vec();
+            for stanza in stanzas {
+                header.extend_from_slice(format!("-> {stanza}\n\n").as_bytes());
+            }
+            header.extend_from_slice(b"--- AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA\n");
+            header
+        };
+
+        let valid = format!("tlock 1000 {CHAIN_HASH}");
+        assert_eq!(
+            decrypt_header(header(&[&valid]).as_slice())
+                .unwrap()
+                .round(),
+            1000.into()
+        );
+        assert_eq!(
+            decrypt_header(header(&[&valid, "tlock 1001"]).as_slice())
+                .unwrap()
+                .round(),
+            1000.into()
+        );
+        assert_eq!(
+            decrypt_header(header(&["tlock 1001", &valid]).as_slice())
+                .unwrap()
+                .round(),
+            1000.into()
+        );
+
+        for (stanza, round, chain) in [
+            ("tlock 1000".to_string(), Some("1000".to_string()), None),
+            (
+                format!("tlock abc {CHAIN_HASH}"),
+                None,
+                Some(CHAIN_HASH.to_string()),
+            ),
+            ("tlock 1000 xyz".to_string(), Some("1000".to_string()), None),
+            (
+                format!("tlock 1000 {CHAIN_HASH} extra"),
+                Some("1000".to_string()),
+                Some(CHAIN_HASH.to_string()),
+            ),
+        ] {
+            let result = decrypt_header(header(&[&stanza]).as_slice());
+            assert!(
+                matches!(
+                    &result,
+                    Err(TLockAgeError::Header {
+                        round: r,
+                        chain: c,
+                        source: Some(age::DecryptError::InvalidHeader),
+                    }) if *r == round && *c == chain
+                ),
+                "{stanza}"
+            );
+        }
+
+        assert!(matches!(
+            decrypt_header(header(&["other 1000"]).as_slice()),
+            Err(TLockAgeError::Header {
+                round: None,
+                chain: None,
+                source: Some(age::DecryptError::NoMatchingKeys),
+            })
+        ));
+    }
+
+    #[test]
+    fn test_migrate() {
+        let chain_hash = hex::decode(CHAIN_HASH).unwrap();
+        let pk_bytes = hex::decode(PUBLIC_KEY).unwrap();
+        let signature = hex::decode(SIGNATURE).unwrap();
+        let new_chain_hash = [1u8; 32];
+
+        let msg = vec![8u8; 100_000];
+        let encrypted = encrypt_to(&msg, 1000);
+
+        let mut migrated = vec![];
+        migrate(
+            &mut migrated,
+            encrypted.as_slice(),
+            &chain_hash,
+            &signature,
+            &new_chain_hash,
+            &pk_bytes,
+            1000,
+        )
+        .unwrap();
+
+        let header = decrypt_header(migrated.as_slice()).unwrap();
+        assert_eq!(header.hash(), new_chain_hash);
+        assert_eq!(header.round(), 1000.into());
+        let mut decrypted = vec![];
+        decrypt(
+            &mut decrypted,
+            migrated.as_slice(),
+            &new_chain_hash,
+            &signature,
+        )
+        .unwrap();
+        assert_eq!(decrypted, msg);
+
+        let result = migrate(
+            vec![],
+            encrypted.as_slice(),
+            &new_chain_hash,
+            &signature,
+            &new_chain_hash,
+            &pk_bytes,
+            1000,
+        );
+        assert!(matches!(result, Err(TLockAgeError::Decrypt(_))));
+        let result = migrate(
+            vec![],
+            encrypted.as_slice(),
+            &chain_hash,
+            &signature,
+            &new_chain_hash,
+            &pk_bytes,
+            0,
+        );
+        assert!(matches!(result, Err(TLockAgeError::ZeroRound)));
+    }
+
+    #[test]
+    fn test_decrypt_partial_write() {
+        // Accepts `limit` bytes, then fails as a full disk would
+        struct LimitedWriter {
+            written: Vec<u8>,
+            limit: usize,
+        }
+
+        impl Write for LimitedWriter {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                let n = buf.len().min(self.limit - self.written.len());
+                if n == 0 {
+                    return Err(io::Error::other("no space left"));
+                }
+                self.written.extend_from_slice(&buf[..n]);
+                Ok(n)
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let chain_hash = hex::decode(CHAIN_HASH).unwrap();
+        let signature = hex::decode(SIGNATURE).unwrap();
+        let msg = vec![8u8; 100_000];
+        let encrypted = encrypt_to(&msg, 1000);
+
+        let mut dst = LimitedWriter {
+            written: vec![],
+            limit: 70_000,
+        };
+        let result = decrypt(&mut dst, encrypted.as_slice(), &chain_hash, &signature);
+        assert!(matches!(
+            result,
+            Err(TLockAgeError::PartialWrite {
+                written: 70_000,
+                ..
+            })
+        ));
+        assert_eq!(dst.written, msg[..70_000]);
+
+        // Read failures are not reported as partial writes
+        let truncated = &encrypted[..encrypted.len() - 10];
+        let result = decrypt(vec![], truncated, &chain_hash, &signature);
+        assert!(matches!(result, Err(TLockAgeError::Corrupt(_))));
+    }
+
+    #[test]
+    fn test_decrypt_round_mismatch() {
+        let chain_hash = hex::decode(CHAIN_HASH).unwrap();
+        let signature = hex::decode(SIGNATURE).unwrap();
+
+        let msg = vec![8u8; 100];
+        let encrypted = encrypt_to(&msg, 1001);
+
+        let mut decrypted = vec![];
+        let result = decrypt(
+            &mut decrypted,
+            encrypted.as_slice(),
+            &chain_hash,
+            &signature,
+        );
+        assert!(matches!(
+            result,
+            Err(TLockAgeError::RoundMismatch { round }) if round == 1001.into()
+        ));
+        assert!(decrypted.is_empty());
+    }
+
+    #[test]
+    fn test_encrypt_with_unlock_time() {
+        let chain_hash = hex::decode(CHAIN_HASH).unwrap();
+        let pk_bytes = hex::decode(PUBLIC_KEY).unwrap();
+        let signature = hex::decode(SIGNATURE).unwrap();
+
+        let msg = vec![8u8; 100];
+        let mut encrypted = vec![];
+        encrypt_with_unlock_time(
+            &mut encrypted,
+            msg.as_slice(),
+            &chain_hash,
+            &pk_bytes,
+            1000,
+            1_000_000,
+            3,
+        )
+        .unwrap();
+
+        let header = decrypt_header(encrypted.as_slice()).unwrap();
+        assert_eq!(header.round(), Round::new(1000));
+        assert_eq!(header.unlock_time(), Some(1_000_000 + 999 * 3));
+
+        let mut decrypted = vec![];
+        decrypt(
+            &mut decrypted,
+            encrypted.as_slice(),
+            &chain_hash,
+            &signature,
+        )
+        .unwrap();
+        assert_eq!(decrypted, msg);
+
+        let header = decrypt_header(encrypt_to(&msg, 1000).as_slice()).unwrap();
+        assert_eq!(header.unlock_time(), None);
+    }
+
+    #[test]
+    fn test_encrypt_chunks() {
+        let chain_hash = hex::decode(CHAIN_HASH).unwrap();
+        let pk_bytes = hex::decode(PUBLIC_KEY).unwrap();
+        let signature = hex::decode(SIGNATURE).unwrap();
+
+        // chunks straddling age 64 KiB payload chunks
+        let first = vec![1u8; 3];
+        let second = vec![2u8; 64 * 1024];
+        let third = vec![3u8; 100];
+        let cases: [Vec<&[u8]>; 3] = [
+            vec![],
+            vec![&first, &[], &third],
+            vec![&first, &second, &third],
+        ];
+        for chunks in cases {
+            let mut encrypted = vec![];
+            encrypt_chunks(
+                &mut encrypted,
+                chunks.iter().copied(),
+                &chain_hash,
+                &pk_bytes,
+                1000,
+            )
+            .unwrap();
+
+            let mut decrypted = vec![];
+            decrypt(
+                &mut decrypted,
+                encrypted.as_slice(),
+                &chain_hash,
+                &signature,
+            )
+            .unwrap();
+            assert_eq!(decrypted, chunks.concat());
+        }
+
+        assert!(matches!(
+            encrypt_chunks(vec![], [first.as_slice()], &chain_hash, &pk_bytes, 0),
+            Err(TLockAgeError::ZeroRound)
+        ));
+    }
+
+    #[test]
+    fn test_time_remaining() {
+        let (genesis_time, period) = (1_000_000, 3);
+        let header = Header::new(1000, [0u8; 32]);
+        let unlock_time = header.unlock_time_with(genesis_time, period);
+        assert_eq!(unlock_time, 1_000_000 + 999 * 3);
+
+        assert_eq!(
+            header.time_remaining(genesis_time, period, genesis_time),
+            Duration::from_secs(999 * 3)
+        );
+        assert_eq!(
+            header.time_remaining(genesis_time, period, unlock_time - 1),
+            Duration::from_secs(1)
+        );
+        for now in [unlock_time, unlock_time + 1, u64::MAX] {
+            assert_eq!(
+                header.time_remaining(genesis_time, period, now),
+                Duration::ZERO
+            );
+        }
+        assert_eq!(
+            Header::new(u64::MAX, [0u8; 32]).unlock_time_with(genesis_time, period),
+            u64::MAX
+        );
+    }
+
+    #[test]
+    fn test_decrypt_corrupt_payload() {
+        let chain_hash = hex::decode(CHAIN_HASH).unwrap();
+        let signature = hex::decode(SIGNATURE).unwrap();
+
+        let msg = vec![8u8; 100_000];
+        let encrypted = encrypt_to(&msg, 1000);
+
+        let mut flipped = encrypted.clone();
+        *flipped.last_mut().unwrap() ^= 1;
+        let truncated = &encrypted[..encrypted.len() - 100];
+
+        for corrupted in [flipped.as_slice(), truncated] {
+            let result = decrypt(vec![], corrupted, &chain_hash, &signature);
+            assert!(
+                matches!(result, Err(TLockAgeError::Corrupt(_))),
+                "{result:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_same_unlock_condition() {
+        let msg = vec![8u8; 100];
+        let a = encrypt_to(&msg, 1000);
+        let b = encrypt_to(&msg, 1000);
+        let c = encrypt_to(&msg, 1001);
+
+        assert_ne!(a, b);
+        assert!(same_unlock_condition(a.as_slice(), b.as_slice()).unwrap());
+        assert!(!same_unlock_condition(a.as_slice(), c.as_slice()).unwrap());
+
+        let other_chain = Header::new(1000, [0u8; 32]);
+        assert!(!decrypt_header(a.as_slice())
+            .unwrap()
+            .same_unlock_condition(&other_chain));
+    }
+
+    #[test]
+    fn test_decrypt_with_progress() {
+        let chain_hash = hex::decode(CHAIN_HASH).unwrap();
+        let signature = hex::decode(SIGNATURE).unwrap();
+
+        let msg = vec![8u8; 200_000];
+        let encrypted = encrypt_to(&msg, 1000);
+
+        let mut reports = vec![];
+        let mut decrypted = vec![];
+        decrypt_with_progress(
+            &mut decrypted,
+            encrypted.as_slice(),
+            &chain_hash,
+            &signature,
+            |written| reports.push(written),
+        )
+        .unwrap();
+        assert_eq!(decrypted, msg);
+        assert!(reports.len() > 1);
+        assert!(reports.windows(2).all(|w| w[0] <= w[1]));
+        assert_eq!(reports.last(), Some(&(msg.len() as u64)));
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn test_decrypt_async() {
+        let chain_hash = hex::decode(CHAIN_HASH).unwrap();
+        let signature = hex::decode(SIGNATURE).unwrap();
+
+        let msg = vec![8u8; 200_000];
+        let encrypted = encrypt_to(&msg, 1000);
+
+        let mut decrypted = vec![];
+        futures::executor::block_on(decrypt_async(
+            &mut decrypted,
+            encrypted.as_slice(),
+            &chain_hash,
+            &signature,
+        ))
+        .unwrap();
+        assert_eq!(decrypted, msg);
+
+        // armor surrounded by text, as accepted by decrypt
+        let mut writer =
+            age::armor::ArmoredWriter::wrap_output(vec![], age::armor::Format::AsciiArmor).unwrap();
+        writer.write_all(&encrypted).unwrap();
+        let armored = String::from_utf8(writer.finish().unwrap()).unwrap();
+        let wrapped = format!("Hi,\n\n{armored}\nCheers\n");
+        let mut decrypted = vec![];
+        futures::executor::block_on(decrypt_async(
+            &mut decrypted,
+            wrapped.as_bytes(),
+            &chain_hash,
+            &signature,
+        ))
+        .unwrap();
+        assert_eq!(decrypted, msg);
+
+        let encrypted = encrypt_to(&msg, 1001);
+        let result = futures::executor::block_on(decrypt_async(
+            vec![],
+            encrypted.as_slice(),
+            &chain_hash,
+            &signature,
+        ));
+        assert!(matches!(
+            result,
+            Err(TLockAgeError::RoundMismatch { round }) if round == 1001.into()
+        ));
+    }
+
+    #[test]
+    fn test_unwrap_file_key() {
+        let chain_hash = hex::decode(CHAIN_HASH).unwrap();
+        let pk_bytes = hex::decode(PUBLIC_KEY).unwrap();
+        let signature = hex::decode(SIGNATURE).unwrap();
+
+        let mut stanzas = vec![];
+        for round in [1000, 1001] {
+            let recipient =
+                Recipient::new(CHAIN_HASH.parse::<ChainHash>().unwrap(), &pk_bytes, round);
+            let file_key = FileKey::from([7u8; 16]);
+            stanzas.push(age::Recipient::wrap_file_key(&recipient, &file_key).unwrap());
+        }
+
+        let file_key = unwrap_file_key(&stanzas[0][0], &chain_hash, &signature).unwrap();
+        assert_eq!(file_key.expose_secret(), &[7u8; 16]);
+
+        let result = unwrap_file_key(&stanzas[1][0], &chain_hash, &signature);
+        assert!(matches!(
+            result,
+            Err(TLockAgeError::RoundMismatch { round }) if round == 1001.into()
+        ));
+
+        let grease = age_core::format::grease_the_joint();
+        let result = unwrap_file_key(&grease, &chain_hash, &signature);
+        assert!(matches!(
+            result,
+            Err(TLockAgeError::Decrypt(age::DecryptError::NoMatchingKeys))
+        ));
+    }
+
+    #[test]
+    fn test_split() {
+        let chain_hash = hex::decode(CHAIN_HASH).unwrap();
+        let signature = hex::decode(SIGNATURE).unwrap();
+
+        let msg = vec![8u8; 100_000];
+        let encrypted = encrypt_to(&msg, 1000);
+
+        let (header, mut payload) = split(encrypted.as_slice()).unwrap();
+        assert!(header.ends_with(b"\n"));
+        assert_eq!(
+            decrypt_header(header.as_slice()).unwrap().round(),
+            Round::new(1000)
+        );
+        let mut rest = vec![];
+        payload.read_to_end(&mut rest).unwrap();
+        assert_eq!([header.as_slice(), &rest].concat(), encrypted);
+
+        let mut decrypted = vec![];
+        decrypt(
+            &mut decrypted,
+            header.as_slice().chain(rest.as_slice()),
+            &chain_hash,
+            &signature,
+        )
+        .unwrap();
+        assert_eq!(decrypted, msg);
+
+        assert!(split(&encrypted[..header.len() - 1]).is_err());
+        assert!(split(b"not an age file\n".as_slice()).is_err());
+
+        #[cfg(feature = "armor")]
+        {
+            let mut armored = vec![];
+            let mut writer = age::armor::ArmoredWriter::wrap_output(
+                &mut armored,
+                age::armor::Format::AsciiArmor,
+            )
+            .unwrap();
+            writer.write_all(&encrypted).unwrap();
+            writer.finish().unwrap();
+
+            let (armored_header, mut payload) = split(armored.as_slice()).unwrap();
+            assert_eq!(armored_header, header);
+            let mut armored_rest = vec![];
+            payload.read_to_end(&mut armored_rest).unwrap();
+            assert_eq!(armored_rest, rest);
+        }
+    }
+
+    #[test]
+    fn test_is_tlock_file() {
+        let encrypted = encrypt_to(&[8u8; 100], 1000);
+        assert!(is_tlock_file(encrypted.as_slice()));
+
+        #[cfg(feature = "armor")]
+        {
+            let mut writer = armor::ArmoredWriter::wrap_output(vec![]).unwrap();
+            writer.write_all(&encrypted).unwrap();
+            let armored = writer.finish().unwrap();
+            assert!(is_tlock_file(armored.as_slice()));
+        }
+
+        // age file without tlock stanza
+        let recipient = age::x25519::Identity::generate().to_public();
+        let encryptor = age::Encryptor::with_recipients(vec![Box::new(recipient)]).unwrap();
+        let mut other = vec![];
+        let mut writer = encryptor.wrap_output(&mut other).unwrap();
+        writer.write_all(&[8u8; 100]).unwrap();
+        writer.finish().unwrap();
+        assert!(!is_tlock_file(other.as_slice()));
+
+        // not an age file, or a truncated one
+        assert!(!is_tlock_file(b"hello world".as_slice()));
+        assert!(!is_tlock_file(&encrypted[..40]));
+
+        // inputs without armor nor header end are not read to the end
+        let text = "some text\n".repeat(1024 * 1024);
+        let mut src = text.as_bytes();
+        assert!(!is_tlock_file(&mut src));
+        assert!(src.len() > text.len() - 128 * 1024);
+        let mut binary = HEADER_VERSION_LINE.to_vec();
+        binary.resize(4 * MAX_HEADER_LEN as usize, b'x');
+        let mut src = binary.as_slice();
+        assert!(!is_tlock_file(&mut src));
+        assert!(src.len() > binary.len() - 2 * MAX_HEADER_LEN as usize);
+    }
+
+    #[test]
+    fn test_encrypt_with_escrow_recipient() {
+        let chain_hash = hex::decode(CHAIN_HASH).unwrap();
+        let pk_bytes = hex::decode(PUBLIC_KEY).unwrap();
+        let signature = hex::decode(SIGNATURE).unwrap();
+
+        let escrow = age::x25519::Identity::generate();
+        let recipients: Vec<Box<dyn age::Recipient + Send>> = vec![
+            Box::new(escrow.to_public()),
+            Box::new(Recipient::new(
+                CHAIN_HASH.parse::<ChainHash>().unwrap(),
+                &pk_bytes,
+                1000,
+            )),
+        ];
+        let msg = vec![8u8; 100];
+        let mut encrypted = vec![];
+        let encryptor = age::Encryptor::with_recipients(recipients).unwrap();
+        let mut writer = encryptor.wrap_output(&mut encrypted).unwrap();
+        writer.write_all(&msg).unwrap();
+        writer.finish().unwrap();
+
+        let header = decrypt_header(encrypted.as_slice()).unwrap();
+        assert_eq!(header.round(), Round::new(1000));
+        assert_eq!(header.hash(), chain_hash);
+
+        // tlock decryption, once the round is reached
+        let mut decrypted = vec![];
+        decrypt(
+            &mut decrypted,
+            encrypted.as_slice(),
+            &chain_hash,
+            &signature,
+        )
+        .unwrap();
+        assert_eq!(decrypted, msg);
+
+        // escrow decryption, at any time
+        let decryptor = match age::Decryptor::new(encrypted.as_slice()).unwrap() {
+            age::Decryptor::Recipients(d) => d,
+            _ => unreachable!(),
+        };
+        let mut reader = decryptor
+            .decrypt(iter::once(&escrow as &dyn age::Identity))
+            .unwrap();
+        let mut decrypted = vec![];
+        reader.read_to_end(&mut decrypted).unwrap();
+        assert_eq!(decrypted, msg);
+    }
+
+    #[test]
+    fn test_encrypt_zero_round() {
+        let chain_hash = hex::decode(CHAIN_HASH).unwrap();
+        let pk_bytes = hex::decode(PUBLIC_KEY).unwrap();
+
+        let mut encrypted = vec![];
+        let result = encrypt(
+            &mut encrypted,
+            [8u8; 100].as_slice(),
+            &chain_hash,
+            &pk_bytes,
+            0,
+        );
+        assert!(matches!(result, Err(TLockAgeError::ZeroRound)));
+        let result = encrypt_with_unlock_time(
+            &mut encrypted,
+            [8u8; 100].as_slice(),
+            &chain_hash,
+            &pk_bytes,
+            0,
+            1_000_000,
+            3,
+        );
+        assert!(matches!(result, Err(TLockAgeError::ZeroRound)));
+        assert!(encrypted.is_empty());
+
+        // still allowed through a recipient, for testing
+        let recipient = Recipient::new(CHAIN_HASH.parse::<ChainHash>().unwrap(), &pk_bytes, 0);
+        encrypt_with_recipient(&mut encrypted, [8u8; 100].as_slice(), Box::new(recipient)).unwrap();
+        assert_eq!(
+            decrypt_header(encrypted.as_slice()).unwrap().round(),
+            Round::new(0)
+        );
+    }
+
+    #[test]
+    fn test_invalid_chain_hash() {
+        let chain_hash = hex::decode(CHAIN_HASH).unwrap();
+        let pk_bytes = hex::decode(PUBLIC_KEY).unwrap();
+        let signature = hex::decode(SIGNATURE).unwrap();
+
+        let mut encrypted = vec![];
+        let result = encrypt(
+            &mut encrypted,
+            [8u8; 100].as_slice(),
+            &chain_hash[1..],
+            &pk_bytes,
+            1000,
+        );
+        assert!(matches!(
+            result,
+            Err(TLockAgeError::ChainHash(
+                tlock::TLockError::InvalidChainHash(_)
+            ))
+        ));
+        assert!(encrypted.is_empty());
+
+        let encrypted = encrypt_to(&[8u8; 100], 1000);
+        assert!(matches!(
+            decrypt(vec![], encrypted.as_slice(), &[], &signature),
+            Err(TLockAgeError::ChainHash(_))
+        ));
+    }
+
+    #[test]
+    fn test_estimate_peak_memory() {
+        let chunks = 2 * (PAYLOAD_CHUNK_SIZE + PAYLOAD_TAG_SIZE);
+        assert!(estimate_peak_memory(0) >= chunks);
+        for len in [0, 100, 200_000] {
+            let encrypted = encrypt_to(&vec![8u8; len], 1000);
+            let decrypted = decrypt_to_vec(
+                &encrypted,
+                &hex::decode(CHAIN_HASH).unwrap(),
+                &hex::decode(SIGNATURE).unwrap(),
+            )
+            .unwrap();
+            assert!(
+                estimate_peak_memory(encrypted.len())
+                    >= encrypted.len() + decrypted.capacity() + chunks
+            );
+        }
+        assert_eq!(estimate_peak_memory(usize::MAX), usize::MAX);
+    }
+
+    #[test]
+    fn test_unlock_request() {
+        let header = decrypt_header(encrypt_to(&[8u8; 100], 1000).as_slice()).unwrap();
+        let request = header.to_unlock_request();
+        assert_eq!(request, format!("tlock:1000:{CHAIN_HASH}"));
+
+        for request in [request.clone(), format!("  {request}\n")] {
+            let parsed = Header::from_unlock_request(&request).unwrap();
+            assert!(parsed.same_unlock_condition(&header));
+            assert_eq!(parsed.unlock_time(), None);
+        }
+
+        assert!(matches!(
+            Header::from_unlock_request(&format!("tlock:0:{CHAIN_HASH}")),
+            Err(TLockAgeError::ZeroRound)
+        ));
+        for invalid in [
+            "".to_string(),
+            format!("age:1000:{CHAIN_HASH}"),
+            format!("tlock:1000:{CHAIN_HASH}:extra"),
+            format!("tlock:-1:{CHAIN_HASH}"),
+            format!("tlock:1000:{}", &CHAIN_HASH[2..]),
+            "tlock:1000".to_string(),
+        ] {
+            assert!(
+                matches!(
+                    Header::from_unlock_request(&invalid),
+                    Err(TLockAgeError::UnlockRequest(ref request)) if *request == invalid
+                ),
+                "{invalid}"
+            );
+        }
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn test_encrypt_compressed() {
+        let chain_hash = hex::decode(CHAIN_HASH).unwrap();
+        let pk_bytes = hex::decode(PUBLIC_KEY).unwrap();
+        let signature = hex::decode(SIGNATURE).unwrap();
+        let msg = b"{\"level\":\"info\",\"msg\":\"request served\"}\n".repeat(5000);
+
+        let mut encrypted = vec![];
+        encrypt_compressed(&mut encrypted, msg.as_slice(), &chain_hash, &pk_bytes, 1000).unwrap();
+        assert!(encrypted.len() < msg.len() / 10);
+        let header = decrypt_header(encrypted.as_slice()).unwrap();
+        assert_eq!(header.compression(), Some(internal::COMPRESSION_ZSTD));
+        assert_eq!(header.round(), 1000.into());
+
+        let mut decrypted = vec![];
+        decrypt(
+            &mut decrypted,
+            encrypted.as_slice(),
+            &chain_hash,
+            &signature,
+        )
+        .unwrap();
+        assert_eq!(decrypted, msg);
+
+        // migration keeps the payload compressed
+        let mut migrated = vec![];
+        migrate(
+            &mut migrated,
+            encrypted.as_slice(),
+            &chain_hash,
+            &signature,
+            &chain_hash,
+            &pk_bytes,
+            1000,
+        )
+        .unwrap();
+        assert_eq!(
+            decrypt_header(migrated.as_slice()).unwrap().compression(),
+            Some(internal::COMPRESSION_ZSTD)
+        );
+        let mut decrypted = vec![];
+        decrypt(&mut decrypted, migrated.as_slice(), &chain_hash, &signature).unwrap();
+        assert_eq!(decrypted, msg);
+
+        #[cfg(feature = "async")]
+        {
+            let mut decrypted = vec![];
+            futures::executor::block_on(decrypt_async(
+                &mut decrypted,
+                encrypted.as_slice(),
+                &chain_hash,
+                &signature,
+            ))
+            .unwrap();
+            assert_eq!(decrypted, msg);
+        }
+
+        assert_eq!(
+            decrypt_header(encrypt_to(&msg, 1000).as_slice())
+                .unwrap()
+                .compression(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_decrypt_unsupported_compression() {
+        let chain_hash = hex::decode(CHAIN_HASH).unwrap();
+        let pk_bytes = hex::decode(PUBLIC_KEY).unwrap();
+        let signature = hex::decode(SIGNATURE).unwrap();
+
+        let recipient = Recipient::new(CHAIN_HASH.parse::<ChainHash>().unwrap(), &pk_bytes, 1000)
+            .with_compression("brotli");
+        let mut encrypted = vec![];
+        encrypt_with_recipient(&mut encrypted, [8u8; 100].as_slice(), Box::new(recipient)).unwrap();
+        assert_eq!(
+            decrypt_header(encrypted.as_slice()).unwrap().compression(),
+            Some("brotli")
+        );
+        assert!(matches!(
+            decrypt(vec![], encrypted.as_slice(), &chain_hash, &signature),
+            Err(TLockAgeError::UnsupportedCompression(compression)) if compression == "brotli"
+        ));
+        #[cfg(feature = "async")]
+        assert!(matches!(
+            futures::executor::block_on(decrypt_async(vec![], encrypted.as_slice(), &chain_hash, &signature)),
+            Err(TLockAgeError::UnsupportedCompression(compression)) if compression == "brotli"
+        ));
+    }
+
+    #[test]
+    fn test_decrypt_with() {
+        let chain_hash = hex::decode(CHAIN_HASH).unwrap();
+        let msg = vec![8u8; 100];
+        let encrypted = encrypt_to(&msg, 1000);
+
+        let mut calls = 0;
+        let mut decrypted = vec![];
+        decrypt_with(
+            &mut decrypted,
+            encrypted.as_slice(),
+            &chain_hash,
+            |header| {
+                calls += 1;
+                assert_eq!(header.round(), 1000.into());
+                Ok(hex::decode(SIGNATURE)?)
+            },
+        )
+        .unwrap();
+        assert_eq!(calls, 1);
+        assert_eq!(decrypted, msg);
+
+        let result = decrypt_with(vec![], encrypted.as_slice(), &chain_hash, |_| {
+            Err(anyhow::anyhow!("device unavailable"))
+        });
+        assert!(
+            matches!(result, Err(TLockAgeError::FetchSignature(e)) if e.to_string() == "device unavailable")
+        );
+    }
+
+    #[test]
+    fn test_decrypt_to_vec() {
+        let chain_hash = hex::decode(CHAIN_HASH).unwrap();
+        let signature = hex::decode(SIGNATURE).unwrap();
+
+        for len in [0, 100, 64 * 1024, 64 * 1024 + 1, 200_000] {
+            let msg = vec![8u8; len];
+            let encrypted = encrypt_to(&msg, 1000);
+
+            let header = decrypt_header(encrypted.as_slice()).unwrap();
+            assert_eq!(header.estimated_plaintext_len(encrypted.len()), Some(len));
+
+            let decrypted = decrypt_to_vec(&encrypted, &chain_hash, &signature).unwrap();
+            assert_eq!(decrypted, msg);
+            assert_eq!(decrypted.capacity(), len);
+        }
+        assert_eq!(
+            Header::new(1000, CHAIN_HASH.parse::<ChainHash>().unwrap())
+                .estimated_plaintext_len(1000),
+            None
+        );
+    }
+
+    #[test]
+    fn test_decrypt_too_many_stanzas() {
+        let chain_hash = hex::decode(CHAIN_HASH).unwrap();
+        let pk_bytes = hex::decode(PUBLIC_KEY).unwrap();
+        let signature = hex::decode(SIGNATURE).unwrap();
+
+        let recipients = (0..=internal::MAX_STANZAS)
+            .map(|_| {
+                Box::new(Recipient::new(
+                    CHAIN_HASH.parse::<ChainHash>().unwrap(),
+                    &pk_bytes,
+                    1000,
+                )) as Box<dyn age::Recipient + Send>
+            })
+            .collect();
+        let encryptor = age::Encryptor::with_recipients(recipients).unwrap();
+        let mut encrypted = vec![];
+        let mut writer = encryptor.wrap_output(&mut encrypted).unwrap();
+        writer.write_all(&[8u8; 100]).unwrap();
+        writer.finish().unwrap();
+
+        let mut decrypted = vec![];
+        let result = decrypt(
+            &mut decrypted,
+            encrypted.as_slice(),
+            &chain_hash,
+            &signature,
+        );
+        assert!(matches!(
+            result,
+            Err(TLockAgeError::TooManyStanzas { count, limit }) if count == limit + 1
+        ));
+    }
+
+    #[test]
+    fn test_decrypt_unsupported_stanza() {
+        let chain_hash = hex::decode(CHAIN_HASH).unwrap();
+        let pk_bytes = hex::decode(PUBLIC_KEY).unwrap();
+        let signature = hex::decode(SIGNATURE).unwrap();
+
+        let mut encrypted = vec![];
+        encrypt_without_grease(
+            &mut encrypted,
+            [8u8; 100].as_slice(),
+            &chain_hash,
+            &pk_bytes,
+            1000,
+        )
+        .unwrap();
+        // a hypothetical later layout, with an additional argument
+        let stanza = format!("-> tlock 1000 {CHAIN_HASH}\n");
+        let position = encrypted
+            .windows(stanza.len())
+            .position(|window| window == stanza.as_bytes())
+            .unwrap();
+        encrypted.splice(
+            position..position + stanza.len(),
+            format!("-> tlock 1000 {CHAIN_HASH} v2\n").into_bytes(),
+        );
+
+        let result = decrypt(vec![], encrypted.as_slice(), &chain_hash, &signature);
+        assert!(matches!(
+            result,
+            Err(TLockAgeError::UnsupportedStanza { args }) if args == ["1000", CHAIN_HASH, "v2"]
+        ));
+    }
+
+    #[test]
+    fn test_decrypt_to_path() {
+        let chain_hash = hex::decode(CHAIN_HASH).unwrap();
+        let signature = hex::decode(SIGNATURE).unwrap();
+        let dir = std::env::temp_dir();
+
+        let msg = vec![8u8; 100];
+        let encrypted = encrypt_to(&msg, 1000);
+        let path = dir.join("tlock_age_test_decrypt_to_path");
+        let options = PathOptions {
+            sync: true,
+            atomic: true,
+        };
+        decrypt_to_path(
+            &path,
+            encrypted.as_slice(),
+            &chain_hash,
+            &signature,
+            options,
+        )
+        .unwrap();
+        assert_eq!(fs::read(&path).unwrap(), msg);
+        fs::remove_file(&path).unwrap();
+
+        // failed decryption does not leave any file behind
+        let encrypted = encrypt_to(&msg, 1001);
+        let path = dir.join("tlock_age_test_decrypt_to_path_failed");
+        let result = decrypt_to_path(
+            &path,
+            encrypted.as_slice(),
+            &chain_hash,
+            &signature,
+            options,
+        );
+        assert!(result.is_err());
+        assert!(!path.exists());
+        assert!(!fs::read_dir(&dir).unwrap().any(|entry| {
+            let name = entry.unwrap().file_name().to_string_lossy().into_owned();
+            name.starts_with(".tlock_age_test_decrypt_to_path_failed.")
+        }));
+
+        // neither does a failed rename, here onto a directory
+        let encrypted = encrypt_to(&msg, 1000);
+        let path = dir.join("tlock_age_test_decrypt_to_path_rename");
+        fs::create_dir_all(&path).unwrap();
+        let result = decrypt_to_path(
+            &path,
+            encrypted.as_slice(),
+            &chain_hash,
+            &signature,
+            options,
+        );
+        assert!(matches!(result, Err(TLockAgeError::IO(_))));
+        assert!(path.is_dir());
+        assert!(!fs::read_dir(&dir).unwrap().any(|entry| {
+            let name = entry.unwrap().file_name().to_string_lossy().into_owned();
+            name.starts_with(".tlock_age_test_decrypt_to_path_rename.")
+        }));
+        fs::remove_dir(&path).unwrap();
+
+        // temporary files have distinct names, and are never opened through an existing entry
+        let path = dir.join("tlock_age_test_create_temporary");
+        let (first, _) = create_temporary(&path).unwrap();
+        let (second, _) = create_temporary(&path).unwrap();
+        assert_ne!(first, second);
+        assert_eq!(first.parent(), path.parent());
+        fs::remove_file(first).unwrap();
+        fs::remove_file(second).unwrap();
+    }
+}
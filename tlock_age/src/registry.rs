@@ -0,0 +1,64 @@
+//! Registry of well-known drand networks.
+//!
+//! A [`Header`](crate::Header) only stores the chain hash a message is encrypted to.
+//! This registry maps the hash of public drand networks to a human readable name, and a remote to fetch beacons from.
+
+/// A well-known drand network.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Network {
+    /// Human readable name of the network.
+    pub name: &'static str,
+    /// Hex encoded chain hash.
+    pub chain_hash: &'static str,
+    /// HTTP endpoint serving the network beacons.
+    pub remote: &'static str,
+}
+
+/// drand networks operated by the League of Entropy.
+pub const NETWORKS: &[Network] = &[
+    Network {
+        name: "mainnet",
+        chain_hash: "8990e7a9aaed2ffed73dbd7092123d6f289930540d7651336225dc172e51b2ce",
+        remote:
+            "https://api.drand.sh/8990e7a9aaed2ffed73dbd7092123d6f289930540d7651336225dc172e51b2ce",
+    },
+    Network {
+        name: "fastnet",
+        chain_hash: "dbd506d6ef76e5f386f41c651dcb808c5bcbd75471cc4eafa3f4df7ad4e4c493",
+        remote:
+            "https://api.drand.sh/dbd506d6ef76e5f386f41c651dcb808c5bcbd75471cc4eafa3f4df7ad4e4c493",
+    },
+    Network {
+        name: "quicknet",
+        chain_hash: "52db9ba70e0cc0f6eaf7803dd07447a1f5477735fd3f661792ba94600c84e971",
+        remote:
+            "https://api.drand.sh/52db9ba70e0cc0f6eaf7803dd07447a1f5477735fd3f661792ba94600c84e971",
+    },
+];
+
+/// Find the well-known network with the given chain hash.
+///
+/// ```rust
+/// let chain_hash = hex::decode("52db9ba70e0cc0f6eaf7803dd07447a1f5477735fd3f661792ba94600c84e971").unwrap();
+/// let network = tlock_age::registry::lookup(&chain_hash).unwrap();
+/// assert_eq!(network.name, "quicknet");
+/// ```
+pub fn lookup(chain_hash: &[u8]) -> Option<&'static Network> {
+    let chain_hash = hex::encode(chain_hash);
+    NETWORKS.iter().find(|n| n.chain_hash == chain_hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lookup() {
+        for network in NETWORKS {
+            let chain_hash = hex::decode(network.chain_hash).unwrap();
+            assert_eq!(lookup(&chain_hash), Some(network));
+            assert!(network.remote.ends_with(network.chain_hash));
+        }
+        assert_eq!(lookup(&[0u8; 32]), None);
+    }
+}